@@ -50,6 +50,7 @@ impl Camera {
             direction: self.lower_left + s * self.horizontal + t * self.vertical
                 - self.origin
                 - offset,
+            wavelength: None,
         }
     }
 }
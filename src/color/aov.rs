@@ -0,0 +1,165 @@
+use color::buffer::*;
+use color::sample::*;
+use geometry::vec3::*;
+
+/// Auxiliary outputs describing the first surface seen through each pixel.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aov {
+    Albedo,
+    Normal,
+    /// Distance from the camera along the ray.
+    Depth,
+    /// World space position.
+    Position,
+    MaterialId,
+    ObjectId,
+}
+
+impl Aov {
+    pub fn by_name(name: &str) -> Option<Aov> {
+        match name {
+            "albedo" => Some(Aov::Albedo),
+            "normal" => Some(Aov::Normal),
+            "depth" => Some(Aov::Depth),
+            "position" => Some(Aov::Position),
+            "material-id" => Some(Aov::MaterialId),
+            "object-id" => Some(Aov::ObjectId),
+            _ => None,
+        }
+    }
+}
+
+/// What a camera ray hit first.
+pub struct AovSample {
+    pub albedo: ColorSample,
+    pub normal: Vec3,
+    pub depth: Dimension,
+    pub position: Vec3,
+    pub material_id: usize,
+    pub object_id: usize,
+}
+
+/// One buffer per auxiliary output. Scalars are stored in all three channels
+/// so every buffer can go through the image writers. Ids are exact in pfm
+/// files and in exr files up to 2048; png output clamps them.
+pub struct AovBuffers {
+    pub albedo: ColorBuffer,
+    pub normal: ColorBuffer,
+    pub depth: ColorBuffer,
+    pub position: ColorBuffer,
+    pub material_id: ColorBuffer,
+    pub object_id: ColorBuffer,
+}
+
+impl AovBuffers {
+    pub fn new(imgx: usize, imgy: usize) -> AovBuffers {
+        AovBuffers {
+            albedo: ColorBuffer::new(imgx, imgy),
+            normal: ColorBuffer::new(imgx, imgy),
+            depth: ColorBuffer::new(imgx, imgy),
+            position: ColorBuffer::new(imgx, imgy),
+            material_id: ColorBuffer::new(imgx, imgy),
+            object_id: ColorBuffer::new(imgx, imgy),
+        }
+    }
+
+    pub fn get(&self, aov: Aov) -> &ColorBuffer {
+        match aov {
+            Aov::Albedo => &self.albedo,
+            Aov::Normal => &self.normal,
+            Aov::Depth => &self.depth,
+            Aov::Position => &self.position,
+            Aov::MaterialId => &self.material_id,
+            Aov::ObjectId => &self.object_id,
+        }
+    }
+
+    /// Adds the first hit of a camera ray, or None when it hit nothing. Misses
+    /// count as black albedo and zero normals, but don't count towards depth
+    /// and position, which have no finite value for them.
+    pub fn add_sample(&mut self, x: usize, y: usize, sample: Option<&AovSample>) {
+        match sample {
+            Some(sample) => {
+                self.albedo.add_color(x, y, sample.albedo);
+                self.normal.add_color(x, y, from_vec3(sample.normal));
+                self.depth.add_color(x, y, from_scalar(sample.depth));
+                self.position.add_color(x, y, from_vec3(sample.position));
+            }
+            None => {
+                self.albedo.add_color(x, y, ColorSample::BLACK);
+                self.normal.add_color(x, y, ColorSample::BLACK);
+            }
+        }
+    }
+
+    /// Sets the ids of the pixel at (x, y), replacing earlier ones since ids
+    /// can't be averaged. Misses have id zero.
+    pub fn set_ids(&mut self, x: usize, y: usize, sample: Option<&AovSample>) {
+        let (material_id, object_id) = sample.map_or((0, 0), |s| (s.material_id, s.object_id));
+        set_color(
+            &mut self.material_id,
+            x,
+            y,
+            from_scalar(material_id as Dimension),
+        );
+        set_color(
+            &mut self.object_id,
+            x,
+            y,
+            from_scalar(object_id as Dimension),
+        );
+    }
+}
+
+fn set_color(color_buffer: &mut ColorBuffer, x: usize, y: usize, color: ColorSample) {
+    let idx = y * color_buffer.imgx + x;
+    color_buffer.sample_counts[idx] = 0;
+    for v in color_buffer.buffer[idx * 3..idx * 3 + 3].iter_mut() {
+        *v = 0.0;
+    }
+    color_buffer.add_color(x, y, color);
+}
+
+fn from_vec3(v: Vec3) -> ColorSample {
+    ColorSample {
+        red: v.x,
+        green: v.y,
+        blue: v.z,
+    }
+}
+
+fn from_scalar(v: Dimension) -> ColorSample {
+    ColorSample {
+        red: v,
+        green: v,
+        blue: v,
+    }
+}
+
+#[cfg(test)]
+mod test_aov {
+    use super::*;
+
+    #[test]
+    fn misses_leave_depth_empty_and_ids_zero() {
+        let mut buffers = AovBuffers::new(2, 1);
+        let hit = AovSample {
+            albedo: ColorSample::WHITE,
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            depth: 4.0,
+            position: Vec3::new(1.0, 2.0, 3.0),
+            material_id: 7,
+            object_id: 9,
+        };
+        buffers.add_sample(0, 0, Some(&hit));
+        buffers.add_sample(0, 0, None);
+        buffers.set_ids(0, 0, Some(&hit));
+        buffers.set_ids(1, 0, None);
+        assert_eq!(0.5, buffers.albedo.pixel(0, 0).red);
+        assert_eq!(4.0, buffers.depth.pixel(0, 0).red);
+        assert_eq!(9.0, buffers.object_id.pixel(0, 0).red);
+        assert_eq!(7.0, buffers.material_id.pixel(0, 0).green);
+        assert_eq!(0.0, buffers.object_id.pixel(1, 0).red);
+        assert_eq!(1, buffers.object_id.sample_counts[1]);
+    }
+}
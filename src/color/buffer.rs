@@ -1,8 +1,11 @@
 use color::sample::*;
 
+#[derive(Clone)]
 pub struct ColorBuffer {
     pub buffer: Vec<SamplePrecision>,
     pub sample_counts: Vec<usize>,
+    /// Sums of squared sample luminances, for estimating per-pixel variance.
+    pub luminance_squares: Vec<SamplePrecision>,
     pub imgx: usize,
     pub imgy: usize,
 }
@@ -11,10 +14,12 @@ impl ColorBuffer {
     pub fn new(imgx: usize, imgy: usize) -> ColorBuffer {
         let buffer: Vec<SamplePrecision> = vec![0.0; 3 * imgx * imgy];
         let sample_counts: Vec<usize> = vec![0; imgx * imgy];
+        let luminance_squares: Vec<SamplePrecision> = vec![0.0; imgx * imgy];
 
         ColorBuffer {
             buffer: buffer,
             sample_counts: sample_counts,
+            luminance_squares: luminance_squares,
             imgx: imgx,
             imgy: imgy,
         }
@@ -24,11 +29,42 @@ impl ColorBuffer {
         let idx = y * self.imgx + x;
         let idx2 = idx * 3;
         self.sample_counts[idx] += 1;
+        self.luminance_squares[idx] += color.luminance() * color.luminance();
         self.buffer[idx2] += color.red;
         self.buffer[idx2 + 1] += color.green;
         self.buffer[idx2 + 2] += color.blue;
     }
 
+    /// Average of all samples accumulated for the pixel at (x, y). Pixels
+    /// without samples are black.
+    pub fn pixel(&self, x: usize, y: usize) -> ColorSample {
+        let idx = y * self.imgx + x;
+        let idx2 = idx * 3;
+        let color = ColorSample {
+            red: self.buffer[idx2],
+            green: self.buffer[idx2 + 1],
+            blue: self.buffer[idx2 + 2],
+        };
+        match self.sample_counts[idx] {
+            0 => ColorSample::BLACK,
+            n => color / n,
+        }
+    }
+
+    /// Variance of the averaged luminance of the pixel at (x, y), estimated
+    /// from the spread of its samples. Zero for pixels with fewer than two
+    /// samples.
+    pub fn variance(&self, x: usize, y: usize) -> SamplePrecision {
+        let idx = y * self.imgx + x;
+        let n = self.sample_counts[idx];
+        if n < 2 {
+            return 0.0;
+        }
+        let mean = self.pixel(x, y).luminance();
+        let mean_square = self.luminance_squares[idx] / n as SamplePrecision;
+        (mean_square - mean * mean).max(0.0) / (n - 1) as SamplePrecision
+    }
+
     pub fn add_buffer(&mut self, other: ColorBuffer) {
         for (cref, o) in self.buffer.iter_mut().zip(other.buffer.iter()) {
             *cref += o;
@@ -40,5 +76,41 @@ impl ColorBuffer {
         {
             *cref += o;
         }
+        for (cref, o) in self
+            .luminance_squares
+            .iter_mut()
+            .zip(other.luminance_squares.iter())
+        {
+            *cref += o;
+        }
+    }
+
+    /// Adds a smaller buffer whose top left pixel lands on (x, y).
+    pub fn add_buffer_at(&mut self, x: usize, y: usize, other: &ColorBuffer) {
+        for row in 0..other.imgy {
+            let idx = (y + row) * self.imgx + x;
+            let other_idx = row * other.imgx;
+            let counts = &other.sample_counts[other_idx..other_idx + other.imgx];
+            for (cref, o) in self.sample_counts[idx..idx + other.imgx]
+                .iter_mut()
+                .zip(counts.iter())
+            {
+                *cref += o;
+            }
+            let squares = &other.luminance_squares[other_idx..other_idx + other.imgx];
+            for (cref, o) in self.luminance_squares[idx..idx + other.imgx]
+                .iter_mut()
+                .zip(squares.iter())
+            {
+                *cref += o;
+            }
+            let colors = &other.buffer[other_idx * 3..(other_idx + other.imgx) * 3];
+            for (cref, o) in self.buffer[idx * 3..(idx + other.imgx) * 3]
+                .iter_mut()
+                .zip(colors.iter())
+            {
+                *cref += o;
+            }
+        }
     }
 }
@@ -0,0 +1,323 @@
+use color::buffer::*;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"RTCK";
+/// Version 2 added luminance squares; older files are rejected.
+const VERSION: u32 = 2;
+/// Bytes stored for every pixel: three sums, a sample count and a luminance
+/// square.
+const PIXEL_BYTES: u64 = 5 * 8;
+
+/// Accumulated render state, enough to continue sampling where a previous
+/// process stopped.
+pub struct Checkpoint {
+    pub color_buffer: ColorBuffer,
+    pub samples: usize,
+    pub scene_hash: u64,
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+pub enum CheckpointErr {
+    File(io::Error),
+    Format(String),
+    Mismatch(String),
+    Version(u32),
+}
+
+impl fmt::Display for CheckpointErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CheckpointErr::File(ref err) => write!(f, "File error: {}", err),
+            CheckpointErr::Format(ref msg) => write!(f, "Invalid checkpoint: {}", msg),
+            CheckpointErr::Mismatch(ref msg) => write!(f, "Checkpoints don't match: {}", msg),
+            CheckpointErr::Version(version) => {
+                write!(f, "Unsupported checkpoint version: {}", version)
+            }
+        }
+    }
+}
+
+impl error::Error for CheckpointErr {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            CheckpointErr::File(ref err) => Some(err),
+            CheckpointErr::Format(_) => None,
+            CheckpointErr::Mismatch(_) => None,
+            CheckpointErr::Version(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointErr {
+    fn from(err: io::Error) -> CheckpointErr {
+        CheckpointErr::File(err)
+    }
+}
+
+/// Writes to a temporary file first and renames it into place, so a process
+/// killed mid-write leaves the previous checkpoint intact.
+pub fn save_checkpoint<'a>(
+    file_name: &'a str,
+    checkpoint: &Checkpoint,
+) -> Result<(), CheckpointErr> {
+    let temp_name = format!("{}.tmp", file_name);
+    {
+        let file = File::create(Path::new(&temp_name))?;
+        let ref mut w = BufWriter::new(file);
+        write_checkpoint(w, checkpoint)?;
+        w.flush()?;
+    }
+    fs::rename(&temp_name, file_name)?;
+
+    Ok(())
+}
+
+pub fn load_checkpoint<'a>(file_name: &'a str) -> Result<Checkpoint, CheckpointErr> {
+    let file = File::open(Path::new(file_name))?;
+    // a corrupt header can't claim more pixels than the file holds
+    let max_pixels = (file.metadata()?.len() / PIXEL_BYTES) as usize;
+    read_checkpoint(&mut BufReader::new(file), max_pixels)
+}
+
+/// Sums independently rendered partial renders of the same scene into one
+/// checkpoint.
+pub fn merge_checkpoints(checkpoints: Vec<Checkpoint>) -> Result<Checkpoint, CheckpointErr> {
+    let mut checkpoints = checkpoints.into_iter();
+    let mut merged = checkpoints
+        .next()
+        .ok_or_else(|| CheckpointErr::Mismatch("nothing to merge".to_string()))?;
+    for checkpoint in checkpoints {
+        if checkpoint.scene_hash != merged.scene_hash {
+            return Err(CheckpointErr::Mismatch(format!(
+                "scene seed {} and scene seed {} differ in scene or settings",
+                merged.seed, checkpoint.seed
+            )));
+        }
+        let (imgx, imgy) = (checkpoint.color_buffer.imgx, checkpoint.color_buffer.imgy);
+        if imgx != merged.color_buffer.imgx || imgy != merged.color_buffer.imgy {
+            return Err(CheckpointErr::Mismatch(format!(
+                "{}x{} and {}x{} images",
+                merged.color_buffer.imgx, merged.color_buffer.imgy, imgx, imgy
+            )));
+        }
+        merged.samples += checkpoint.samples;
+        merged.color_buffer.add_buffer(checkpoint.color_buffer);
+    }
+    Ok(merged)
+}
+
+pub fn write_checkpoint<W: Write>(w: &mut W, checkpoint: &Checkpoint) -> Result<(), CheckpointErr> {
+    w.write_all(&MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    for value in [
+        checkpoint.scene_hash,
+        checkpoint.seed,
+        checkpoint.samples as u64,
+    ]
+    .iter()
+    {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    write_color_buffer(w, &checkpoint.color_buffer)
+}
+
+/// Writes the dimensions, sums, sample counts and luminance squares of a
+/// `ColorBuffer`.
+pub fn write_color_buffer<W: Write>(
+    w: &mut W,
+    color_buffer: &ColorBuffer,
+) -> Result<(), CheckpointErr> {
+    w.write_all(&(color_buffer.imgx as u64).to_le_bytes())?;
+    w.write_all(&(color_buffer.imgy as u64).to_le_bytes())?;
+    for value in color_buffer.buffer.iter() {
+        w.write_all(&value.to_bits().to_le_bytes())?;
+    }
+    for count in color_buffer.sample_counts.iter() {
+        w.write_all(&(*count as u64).to_le_bytes())?;
+    }
+    for value in color_buffer.luminance_squares.iter() {
+        w.write_all(&value.to_bits().to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Reads a checkpoint of at most `max_pixels` pixels.
+pub fn read_checkpoint<R: Read>(r: &mut R, max_pixels: usize) -> Result<Checkpoint, CheckpointErr> {
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(CheckpointErr::Format("not a checkpoint file".to_string()));
+    }
+    let mut version = [0u8; 4];
+    r.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(CheckpointErr::Version(version));
+    }
+    let scene_hash = read_u64(r)?;
+    let seed = read_u64(r)?;
+    let samples = read_u64(r)? as usize;
+    let color_buffer = read_color_buffer(r, max_pixels)?;
+
+    Ok(Checkpoint {
+        color_buffer: color_buffer,
+        samples: samples,
+        scene_hash: scene_hash,
+        seed: seed,
+    })
+}
+
+/// Reads a buffer written by `write_color_buffer`, rejecting one of more than
+/// `max_pixels` pixels before allocating it.
+pub fn read_color_buffer<R: Read>(
+    r: &mut R,
+    max_pixels: usize,
+) -> Result<ColorBuffer, CheckpointErr> {
+    let imgx = read_u64(r)? as usize;
+    let imgy = read_u64(r)? as usize;
+    if imgx.checked_mul(imgy).map_or(true, |n| n > max_pixels) {
+        return Err(CheckpointErr::Format(format!(
+            "image too large: {}x{}",
+            imgx, imgy
+        )));
+    }
+    let mut color_buffer = ColorBuffer::new(imgx, imgy);
+    for value in color_buffer.buffer.iter_mut() {
+        *value = f64::from_bits(read_u64(r)?);
+    }
+    for count in color_buffer.sample_counts.iter_mut() {
+        *count = read_u64(r)? as usize;
+    }
+    for value in color_buffer.luminance_squares.iter_mut() {
+        *value = f64::from_bits(read_u64(r)?);
+    }
+
+    Ok(color_buffer)
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64, CheckpointErr> {
+    let mut bytes = [0u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test_checkpoint {
+    use super::*;
+    use color::sample::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn round_trips() {
+        let mut color_buffer = ColorBuffer::new(3, 2);
+        color_buffer.add_color(
+            2,
+            1,
+            ColorSample {
+                red: 0.25,
+                green: 1e9,
+                blue: -0.5,
+            },
+        );
+        let checkpoint = Checkpoint {
+            color_buffer: color_buffer,
+            samples: 7,
+            scene_hash: 0xdead_beef,
+            seed: 42,
+        };
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &checkpoint).unwrap();
+        let loaded = read_checkpoint(&mut bytes.as_slice(), 6).unwrap();
+        assert_eq!(7, loaded.samples);
+        assert_eq!(0xdead_beef, loaded.scene_hash);
+        assert_eq!(42, loaded.seed);
+        assert_eq!(checkpoint.color_buffer.buffer, loaded.color_buffer.buffer);
+        assert_eq!(
+            checkpoint.color_buffer.sample_counts,
+            loaded.color_buffer.sample_counts
+        );
+        assert_eq!(
+            checkpoint.color_buffer.luminance_squares,
+            loaded.color_buffer.luminance_squares
+        );
+    }
+
+    #[test]
+    fn merge_sums_samples() {
+        let partial = |samples| {
+            let mut color_buffer = ColorBuffer::new(2, 2);
+            for _ in 0..samples {
+                color_buffer.add_color(1, 0, ColorSample::WHITE);
+            }
+            Checkpoint {
+                color_buffer: color_buffer,
+                samples: samples,
+                scene_hash: 1,
+                seed: 2,
+            }
+        };
+        let merged = merge_checkpoints(vec![partial(3), partial(5)]).unwrap();
+        assert_eq!(8, merged.samples);
+        assert_eq!(8, merged.color_buffer.sample_counts[1]);
+        assert_eq!(8.0, merged.color_buffer.buffer[3]);
+    }
+
+    #[test]
+    fn merge_rejects_different_scenes() {
+        let partial = |scene_hash| Checkpoint {
+            color_buffer: ColorBuffer::new(2, 2),
+            samples: 1,
+            scene_hash: scene_hash,
+            seed: 2,
+        };
+        match merge_checkpoints(vec![partial(1), partial(2)]) {
+            Err(CheckpointErr::Mismatch(_)) => (),
+            _ => panic!("expected a mismatch error"),
+        }
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        for version in [1, VERSION + 1].iter() {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend_from_slice(&version.to_le_bytes());
+            match read_checkpoint(&mut bytes.as_slice(), 0) {
+                Err(CheckpointErr::Version(v)) => assert_eq!(*version, v),
+                _ => panic!("expected a version error"),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_headers_larger_than_the_file() {
+        let file_name = env::temp_dir()
+            .join(format!("checkpoint-{}.rtck", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        for &(imgx, imgy) in [(1000u64, 1000u64), (1 << 32, 1 << 32)].iter() {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend_from_slice(&VERSION.to_le_bytes());
+            for value in [1, 2, 3, imgx, imgy].iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            // one pixel's worth of data
+            bytes.extend_from_slice(&[0u8; PIXEL_BYTES as usize]);
+            fs::write(&file_name, &bytes).unwrap();
+            match load_checkpoint(&file_name) {
+                Err(CheckpointErr::Format(_)) => (),
+                _ => panic!("expected a format error"),
+            }
+        }
+        fs::remove_file(&file_name).unwrap();
+    }
+}
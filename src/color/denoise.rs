@@ -0,0 +1,279 @@
+use color::aov::*;
+use color::buffer::*;
+use color::sample::*;
+use geometry::vec3::*;
+
+/// B3 spline taps of the à-trous wavelet kernel.
+const KERNEL: [SamplePrecision; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// Edge-aware à-trous wavelet filter. Each iteration blurs with a 5x5 kernel
+/// whose taps are spaced twice as far apart as in the previous one. Weights
+/// fall off with differences in the albedo, normal and depth outputs and with
+/// luminance differences relative to the estimated noise, so edges and
+/// textures survive while noise is averaged out.
+pub struct Denoiser {
+    pub iterations: usize,
+    /// Luminance differences are measured in standard deviations of the
+    /// noise, scaled by this.
+    pub sigma_luminance: SamplePrecision,
+    /// Exponent applied to the cosine between normals.
+    pub sigma_normal: SamplePrecision,
+    /// Relative depth difference per pixel of tap distance.
+    pub sigma_depth: SamplePrecision,
+    pub sigma_albedo: SamplePrecision,
+}
+
+struct Guide {
+    albedo: ColorSample,
+    normal: Vec3,
+    depth: Dimension,
+    /// Whether any camera ray through the pixel hit a surface.
+    hit: bool,
+}
+
+impl Denoiser {
+    pub fn new() -> Denoiser {
+        Denoiser {
+            iterations: 5,
+            sigma_luminance: 4.0,
+            sigma_normal: 64.0,
+            sigma_depth: 0.02,
+            sigma_albedo: 0.1,
+        }
+    }
+
+    /// Filters the averaged radiance of `color_buffer`. `aov_buffers` must
+    /// have the same dimensions. The result holds one sample per pixel.
+    pub fn denoise(&self, color_buffer: &ColorBuffer, aov_buffers: &AovBuffers) -> ColorBuffer {
+        let (imgx, imgy) = (color_buffer.imgx, color_buffer.imgy);
+        let mut guides = Vec::with_capacity(imgx * imgy);
+        let mut colors = Vec::with_capacity(imgx * imgy);
+        let mut variances = Vec::with_capacity(imgx * imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                let normal = aov_buffers.normal.pixel(x, y);
+                guides.push(Guide {
+                    albedo: aov_buffers.albedo.pixel(x, y),
+                    normal: Vec3::new(normal.red, normal.green, normal.blue),
+                    depth: aov_buffers.depth.pixel(x, y).red,
+                    hit: aov_buffers.depth.sample_counts[y * imgx + x] > 0,
+                });
+                colors.push(color_buffer.pixel(x, y));
+                variances.push(color_buffer.variance(x, y));
+            }
+        }
+        for iteration in 0..self.iterations {
+            let step = 1 << iteration;
+            let (c, v) = self.filter(imgx, imgy, step, &guides, &colors, &variances);
+            colors = c;
+            variances = v;
+        }
+        let mut denoised = ColorBuffer::new(imgx, imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                denoised.add_color(x, y, colors[y * imgx + x]);
+            }
+        }
+        denoised
+    }
+
+    /// One filter iteration with taps `step` pixels apart. Variances are
+    /// filtered along with the colors, with squared weights, so later
+    /// iterations see the reduced noise.
+    fn filter(
+        &self,
+        imgx: usize,
+        imgy: usize,
+        step: usize,
+        guides: &[Guide],
+        colors: &[ColorSample],
+        variances: &[SamplePrecision],
+    ) -> (Vec<ColorSample>, Vec<SamplePrecision>) {
+        let blurred = blur_3x3(imgx, imgy, variances);
+        let mut out_colors = Vec::with_capacity(colors.len());
+        let mut out_variances = Vec::with_capacity(colors.len());
+        for y in 0..imgy {
+            for x in 0..imgx {
+                let p = y * imgx + x;
+                let luminance = colors[p].luminance();
+                let luminance_scale = self.sigma_luminance * blurred[p].sqrt() + 1e-6;
+                let mut sum = ColorSample::BLACK;
+                let mut variance = 0.0;
+                let mut total_weight = 0.0;
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    let qy = y as isize + (dy as isize - 2) * step as isize;
+                    if qy < 0 || qy >= imgy as isize {
+                        continue;
+                    }
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let qx = x as isize + (dx as isize - 2) * step as isize;
+                        if qx < 0 || qx >= imgx as isize {
+                            continue;
+                        }
+                        let q = qy as usize * imgx + qx as usize;
+                        let distance =
+                            step * (dx as isize - 2).abs().max((dy as isize - 2).abs()) as usize;
+                        let weight = ky
+                            * kx
+                            * self.guide_weight(&guides[p], &guides[q], distance)
+                            * (-(luminance - colors[q].luminance()).abs() / luminance_scale).exp();
+                        sum += colors[q] * weight;
+                        variance += weight * weight * variances[q];
+                        total_weight += weight;
+                    }
+                }
+                if total_weight * total_weight > 0.0 {
+                    out_colors.push(sum * total_weight.recip());
+                    out_variances.push(variance / (total_weight * total_weight));
+                } else {
+                    // guides no pixel matches, like normals that averaged
+                    // out to zero
+                    out_colors.push(colors[p]);
+                    out_variances.push(variances[p]);
+                }
+            }
+        }
+        (out_colors, out_variances)
+    }
+
+    fn guide_weight(&self, p: &Guide, q: &Guide, distance: usize) -> SamplePrecision {
+        match (p.hit, q.hit) {
+            (false, false) => 1.0,
+            (true, true) => {
+                let normal = p.normal.dot(q.normal).max(0.0).powf(self.sigma_normal);
+                let depth = (p.depth - q.depth).abs()
+                    / (self.sigma_depth * p.depth * distance as Dimension + 1e-6);
+                let albedo = ((p.albedo.red - q.albedo.red).powi(2)
+                    + (p.albedo.green - q.albedo.green).powi(2)
+                    + (p.albedo.blue - q.albedo.blue).powi(2))
+                    / (self.sigma_albedo * self.sigma_albedo);
+                normal * (-depth - albedo).exp()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Gaussian 3x3 blur, which steadies the per-pixel variance estimates.
+fn blur_3x3(imgx: usize, imgy: usize, values: &[SamplePrecision]) -> Vec<SamplePrecision> {
+    const TAPS: [SamplePrecision; 3] = [0.25, 0.5, 0.25];
+    let mut blurred = Vec::with_capacity(values.len());
+    for y in 0..imgy {
+        for x in 0..imgx {
+            let mut sum = 0.0;
+            let mut total_weight = 0.0;
+            for (dy, ky) in TAPS.iter().enumerate() {
+                for (dx, kx) in TAPS.iter().enumerate() {
+                    let (qx, qy) = ((x + dx) as isize - 1, (y + dy) as isize - 1);
+                    if qx >= 0 && qy >= 0 && (qx as usize) < imgx && (qy as usize) < imgy {
+                        sum += ky * kx * values[qy as usize * imgx + qx as usize];
+                        total_weight += ky * kx;
+                    }
+                }
+            }
+            blurred.push(sum / total_weight);
+        }
+    }
+    blurred
+}
+
+#[cfg(test)]
+mod test_denoise {
+    use super::*;
+
+    fn flat_aovs(imgx: usize, imgy: usize) -> AovBuffers {
+        aovs_with_normals(imgx, imgy, |_, _| Vec3::new(0.0, 0.0, 1.0))
+    }
+
+    fn aovs_with_normals<F>(imgx: usize, imgy: usize, normal: F) -> AovBuffers
+    where
+        F: Fn(usize, usize) -> Vec3,
+    {
+        let mut aov_buffers = AovBuffers::new(imgx, imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                let sample = AovSample {
+                    albedo: ColorSample::WHITE,
+                    normal: normal(x, y),
+                    depth: 1.0,
+                    position: Vec3::new(x as Dimension, y as Dimension, 0.0),
+                    material_id: 1,
+                    object_id: 1,
+                };
+                aov_buffers.add_sample(x, y, Some(&sample));
+            }
+        }
+        aov_buffers
+    }
+
+    fn gray(value: SamplePrecision) -> ColorSample {
+        ColorSample {
+            red: value,
+            green: value,
+            blue: value,
+        }
+    }
+
+    #[test]
+    fn smooths_noise_on_flat_surfaces() {
+        let (imgx, imgy) = (16, 16);
+        let mut color_buffer = ColorBuffer::new(imgx, imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                // alternating samples average to 0.5 with some noise left
+                let offset = if (x + y) % 2 == 0 { 0.2 } else { -0.2 };
+                color_buffer.add_color(x, y, gray(0.5 + offset + 0.3));
+                color_buffer.add_color(x, y, gray(0.5 + offset - 0.3));
+            }
+        }
+        let denoised = Denoiser::new().denoise(&color_buffer, &flat_aovs(imgx, imgy));
+        let error = |buffer: &ColorBuffer| (buffer.pixel(8, 8).red - 0.5).abs();
+        assert!(error(&denoised) < 0.25 * error(&color_buffer));
+    }
+
+    #[test]
+    fn keeps_albedo_edges() {
+        let (imgx, imgy) = (16, 8);
+        let mut aov_buffers = AovBuffers::new(imgx, imgy);
+        let mut color_buffer = ColorBuffer::new(imgx, imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                let albedo = if x < imgx / 2 { 0.1 } else { 0.9 };
+                let sample = AovSample {
+                    albedo: gray(albedo),
+                    normal: Vec3::new(0.0, 0.0, 1.0),
+                    depth: 1.0,
+                    position: Vec3::new(x as Dimension, y as Dimension, 0.0),
+                    material_id: 1,
+                    object_id: 1,
+                };
+                aov_buffers.add_sample(x, y, Some(&sample));
+                color_buffer.add_color(x, y, gray(albedo));
+            }
+        }
+        let denoised = Denoiser::new().denoise(&color_buffer, &aov_buffers);
+        assert!((denoised.pixel(imgx / 2 - 1, 4).red - 0.1).abs() < 1e-3);
+        assert!((denoised.pixel(imgx / 2, 4).red - 0.9).abs() < 1e-3);
+    }
+
+    #[test]
+    fn keeps_pixels_no_tap_matches() {
+        let (imgx, imgy) = (8, 8);
+        let aov_buffers = aovs_with_normals(imgx, imgy, |x, y| {
+            if (x, y) == (4, 4) {
+                Vec3::ZERO
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            }
+        });
+        let mut color_buffer = ColorBuffer::new(imgx, imgy);
+        for y in 0..imgy {
+            for x in 0..imgx {
+                let value = if (x, y) == (4, 4) { 0.7 } else { 0.2 };
+                color_buffer.add_color(x, y, gray(value));
+            }
+        }
+        let denoised = Denoiser::new().denoise(&color_buffer, &aov_buffers);
+        assert!((denoised.pixel(4, 4).red - 0.7).abs() < 1e-9);
+    }
+}
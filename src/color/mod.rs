@@ -1,2 +1,7 @@
+pub mod aov;
 pub mod buffer;
+pub mod checkpoint;
+pub mod denoise;
+pub mod outliers;
 pub mod sample;
+pub mod spectrum;
@@ -0,0 +1,89 @@
+use color::buffer::*;
+use color::sample::*;
+
+/// Replaces pixels much brighter than their neighbourhood, the fireflies left
+/// by rare high energy paths. A pixel is an outlier when its luminance exceeds
+/// the mean of its eight neighbours by more than `threshold` of their
+/// standard deviations; it then gets the neighbours' mean color. Sample
+/// counts are kept, so the result can still be averaged like the input.
+pub fn reject_outliers(color_buffer: &ColorBuffer, threshold: SamplePrecision) -> ColorBuffer {
+    let (imgx, imgy) = (color_buffer.imgx, color_buffer.imgy);
+    let mut filtered = color_buffer.clone();
+    for y in 0..imgy {
+        for x in 0..imgx {
+            let mut sum = ColorSample::BLACK;
+            let mut luminance_sum = 0.0;
+            let mut luminance_square_sum = 0.0;
+            let mut n = 0;
+            for qy in y.saturating_sub(1)..(y + 2).min(imgy) {
+                for qx in x.saturating_sub(1)..(x + 2).min(imgx) {
+                    if (qx, qy) == (x, y) {
+                        continue;
+                    }
+                    let color = color_buffer.pixel(qx, qy);
+                    let luminance = color.luminance();
+                    sum += color;
+                    luminance_sum += luminance;
+                    luminance_square_sum += luminance * luminance;
+                    n += 1;
+                }
+            }
+            if n == 0 {
+                continue;
+            }
+            let mean = luminance_sum / n as SamplePrecision;
+            let deviation = (luminance_square_sum / n as SamplePrecision - mean * mean)
+                .max(0.0)
+                .sqrt();
+            if color_buffer.pixel(x, y).luminance() > mean + threshold * deviation {
+                let idx = y * imgx + x;
+                let count = color_buffer.sample_counts[idx];
+                let replacement = sum / n * count as SamplePrecision;
+                filtered.buffer[idx * 3] = replacement.red;
+                filtered.buffer[idx * 3 + 1] = replacement.green;
+                filtered.buffer[idx * 3 + 2] = replacement.blue;
+                let luminance = replacement.luminance();
+                filtered.luminance_squares[idx] = match count {
+                    0 => 0.0,
+                    count => luminance * luminance / count as SamplePrecision,
+                };
+            }
+        }
+    }
+    filtered
+}
+
+#[cfg(test)]
+mod test_outliers {
+    use super::*;
+
+    fn gray(value: SamplePrecision) -> ColorSample {
+        ColorSample {
+            red: value,
+            green: value,
+            blue: value,
+        }
+    }
+
+    #[test]
+    fn replaces_isolated_bright_pixels_only() {
+        let mut color_buffer = ColorBuffer::new(5, 5);
+        for y in 0..5 {
+            for x in 0..5 {
+                let value = match (x, y) {
+                    (2, 2) => 100.0,
+                    (x, _) if x % 2 == 0 => 0.4,
+                    _ => 0.6,
+                };
+                color_buffer.add_color(x, y, gray(value));
+                color_buffer.add_color(x, y, gray(value));
+            }
+        }
+        let filtered = reject_outliers(&color_buffer, 3.0);
+        assert!(filtered.pixel(2, 2).red < 1.0);
+        assert_eq!(2, filtered.sample_counts[12]);
+        for &(x, y) in [(1, 2), (3, 2), (2, 1), (0, 0)].iter() {
+            assert_eq!(color_buffer.pixel(x, y).red, filtered.pixel(x, y).red);
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::{Add, AddAssign, Div, Mul, MulAssign};
 
 pub type SamplePrecision = f64;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct ColorSample {
     pub red: SamplePrecision,
     pub green: SamplePrecision,
@@ -20,6 +20,11 @@ impl ColorSample {
         green: 1.0,
         blue: 1.0,
     };
+
+    /// Relative luminance of linear Rec.709 primaries.
+    pub fn luminance(&self) -> SamplePrecision {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
 }
 
 impl Add for ColorSample {
@@ -0,0 +1,422 @@
+use color::sample::*;
+
+/// Shortest wavelength sampled, in nanometers.
+pub const MIN_WAVELENGTH: SamplePrecision = 380.0;
+/// Longest wavelength sampled, in nanometers.
+pub const MAX_WAVELENGTH: SamplePrecision = 780.0;
+
+/// Per channel factors making the weights of uniformly sampled wavelengths
+/// average to white: the width of the range over the integral of each
+/// channel of `linear_rgb`.
+const WHITE_BALANCE: [SamplePrecision; 3] = [3.116_210_8, 3.939_408_7, 4.120_958_3];
+
+/// Integral of the CIE luminance curve over the sampled range, in nanometers.
+const INTEGRAL_Y: SamplePrecision = 106.919_734_6;
+
+/// Relative spectral power of CIE standard illuminant D65 from 380 to 780 nm
+/// in steps of 10 nm.
+const D65: [SamplePrecision; 41] = [
+    49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788,
+    88.6856, 90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842,
+    69.7213, 71.6091, 74.349, 61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828,
+];
+
+/// Luminance of `D65` as tabulated, used to scale it to a luminance of one.
+const D65_LUMINANCE: SamplePrecision = 98.852_048_2;
+
+/// Basis spectra of Smits, "An RGB to Spectrum Conversion for Reflectances"
+/// (1999), in ten bins from 380 to 720 nm: white, cyan, magenta, yellow, red,
+/// green and blue.
+const SMITS: [[SamplePrecision; 10]; 7] = [
+    [
+        1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+    ],
+    [
+        0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+    ],
+    [
+        1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+    ],
+    [
+        0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+    ],
+    [
+        0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+    ],
+    [
+        0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+    ],
+    [
+        1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+    ],
+];
+
+/// Wavelengths followed together by a path in spectral mode: a hero
+/// wavelength and two more spaced evenly around the visible range. The
+/// channels of the path's colors hold the values at these wavelengths
+/// instead of red, green and blue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wavelengths {
+    pub lambda: [SamplePrecision; 3],
+}
+
+impl Wavelengths {
+    /// Weight keeping only the hero wavelength, for paths that can no longer
+    /// be shared, such as after refraction by dispersive glass.
+    pub const HERO_ONLY: ColorSample = ColorSample {
+        red: 3.0,
+        green: 0.0,
+        blue: 0.0,
+    };
+
+    /// Picks the hero wavelength from a uniform number in [0, 1).
+    pub fn sample(u: SamplePrecision) -> Wavelengths {
+        let at = |offset: SamplePrecision| sample_wavelength((u + offset).fract());
+        Wavelengths {
+            lambda: [at(0.0), at(1.0 / 3.0), at(2.0 / 3.0)],
+        }
+    }
+
+    pub fn hero(&self) -> SamplePrecision {
+        self.lambda[0]
+    }
+
+    fn map<F: Fn(SamplePrecision) -> SamplePrecision>(&self, f: F) -> ColorSample {
+        ColorSample {
+            red: f(self.lambda[0]),
+            green: f(self.lambda[1]),
+            blue: f(self.lambda[2]),
+        }
+    }
+
+    /// Reflectance spectrum of the working space color `rgb`, such as an
+    /// albedo, at these wavelengths.
+    pub fn reflectance(&self, rgb: ColorSample) -> ColorSample {
+        self.map(|lambda| upsample(rgb, lambda))
+    }
+
+    /// Spectrum of light with the working space color `rgb`: its reflectance
+    /// spectrum lit by D65, the white point of the working space.
+    pub fn illuminant(&self, rgb: ColorSample) -> ColorSample {
+        self.map(|lambda| upsample(rgb, lambda) * d65(lambda))
+    }
+
+    /// Working space color of a path carrying `values` at these wavelengths,
+    /// through the CIE XYZ response of the film. Averages to the color of the
+    /// spectrum over uniformly sampled hero wavelengths.
+    pub fn to_rgb(&self, values: ColorSample) -> ColorSample {
+        let scale = (MAX_WAVELENGTH - MIN_WAVELENGTH) / (3.0 * INTEGRAL_Y);
+        let mut xyz = (0.0, 0.0, 0.0);
+        for (lambda, value) in self
+            .lambda
+            .iter()
+            .zip([values.red, values.green, values.blue].iter())
+        {
+            let (x, y, z) = cie_xyz(*lambda);
+            xyz.0 += x * value * scale;
+            xyz.1 += y * value * scale;
+            xyz.2 += z * value * scale;
+        }
+        xyz_to_rgb(xyz)
+    }
+}
+
+/// Standard spectra for light sources.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Illuminant {
+    /// Equal energy at all wavelengths.
+    E,
+    /// Incandescent light, a blackbody at 2856 K.
+    A,
+    /// Average daylight, the white point of the working space.
+    D65,
+    /// Planckian radiator at the given temperature in kelvin.
+    Blackbody(SamplePrecision),
+}
+
+impl Illuminant {
+    /// Looks up an illuminant by name, or a blackbody by its temperature in
+    /// kelvin.
+    pub fn by_name(name: &str) -> Option<Illuminant> {
+        match name.to_lowercase().as_str() {
+            "e" => Some(Illuminant::E),
+            "a" => Some(Illuminant::A),
+            "d65" => Some(Illuminant::D65),
+            kelvin => match kelvin.parse::<SamplePrecision>() {
+                Ok(t) if t >= 500.0 && t <= 50_000.0 => Some(Illuminant::Blackbody(t)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Spectral power at `lambda` nanometers, in arbitrary units.
+    fn power(&self, lambda: SamplePrecision) -> SamplePrecision {
+        match *self {
+            Illuminant::E => 1.0,
+            Illuminant::A => planck(lambda, 2856.0),
+            Illuminant::D65 => d65(lambda),
+            Illuminant::Blackbody(t) => planck(lambda, t),
+        }
+    }
+}
+
+/// An illuminant scaled to a luminance of one, with its working space color
+/// computed once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IlluminantSpectrum {
+    pub illuminant: Illuminant,
+    scale: SamplePrecision,
+    color: ColorSample,
+}
+
+impl IlluminantSpectrum {
+    pub fn new(illuminant: Illuminant) -> IlluminantSpectrum {
+        let n = 800;
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / n as SamplePrecision;
+        let mut xyz = (0.0, 0.0, 0.0);
+        for i in 0..n {
+            let lambda = MIN_WAVELENGTH + (i as SamplePrecision + 0.5) * step;
+            let power = illuminant.power(lambda) * step / INTEGRAL_Y;
+            let (x, y, z) = cie_xyz(lambda);
+            xyz = (xyz.0 + x * power, xyz.1 + y * power, xyz.2 + z * power);
+        }
+        let scale = xyz.1.recip();
+        IlluminantSpectrum {
+            illuminant: illuminant,
+            scale: scale,
+            color: xyz_to_rgb((xyz.0 * scale, 1.0, xyz.2 * scale)),
+        }
+    }
+
+    /// Working space color of the light, with a luminance of one.
+    pub fn color(&self) -> ColorSample {
+        self.color
+    }
+
+    /// Spectrum of light that has the working space color `rgb` once tinted
+    /// by `color`, at `wavelengths`: the tint's reflectance spectrum lit by
+    /// the illuminant.
+    pub fn values(&self, rgb: ColorSample, wavelengths: &Wavelengths) -> ColorSample {
+        let tint = ColorSample {
+            red: untint(rgb.red, self.color.red),
+            green: untint(rgb.green, self.color.green),
+            blue: untint(rgb.blue, self.color.blue),
+        };
+        wavelengths
+            .map(|lambda| upsample(tint, lambda) * self.illuminant.power(lambda) * self.scale)
+    }
+}
+
+fn untint(value: SamplePrecision, tint: SamplePrecision) -> SamplePrecision {
+    if tint > 0.0 {
+        value / tint
+    } else {
+        0.0
+    }
+}
+
+/// Spectral radiance of a blackbody at `kelvin`, in arbitrary units.
+fn planck(lambda: SamplePrecision, kelvin: SamplePrecision) -> SamplePrecision {
+    // second radiation constant in nm K
+    const C2: SamplePrecision = 1.438_777e7;
+    let l = lambda / 1000.0;
+    1.0 / (l.powi(5) * ((C2 / (lambda * kelvin)).exp() - 1.0))
+}
+
+/// D65 scaled to a luminance of one.
+fn d65(lambda: SamplePrecision) -> SamplePrecision {
+    let f = ((lambda - MIN_WAVELENGTH) / 10.0).max(0.0);
+    let i = (f as usize).min(D65.len() - 2);
+    let t = (f - i as SamplePrecision).min(1.0);
+    (D65[i] * (1.0 - t) + D65[i + 1] * t) / D65_LUMINANCE
+}
+
+/// Smits' reflectance spectrum for `rgb` at `lambda`, interpolating between
+/// bin centers. Scales with `rgb`, so it also works for weights outside
+/// [0, 1].
+fn upsample(rgb: ColorSample, lambda: SamplePrecision) -> SamplePrecision {
+    let f = (lambda - MIN_WAVELENGTH) / 34.0 - 0.5;
+    let basis = |spectrum: usize| {
+        let bins = &SMITS[spectrum];
+        if f <= 0.0 {
+            bins[0]
+        } else if f >= 9.0 {
+            bins[9]
+        } else {
+            let i = f as usize;
+            let t = f - i as SamplePrecision;
+            bins[i] * (1.0 - t) + bins[i + 1] * t
+        }
+    };
+    let (white, cyan, magenta, yellow, red, green, blue) = (0, 1, 2, 3, 4, 5, 6);
+    let (r, g, b) = (rgb.red, rgb.green, rgb.blue);
+    if r <= g && r <= b {
+        r * basis(white)
+            + if g <= b {
+                (g - r) * basis(cyan) + (b - g) * basis(blue)
+            } else {
+                (b - r) * basis(cyan) + (g - b) * basis(green)
+            }
+    } else if g <= r && g <= b {
+        g * basis(white)
+            + if r <= b {
+                (r - g) * basis(magenta) + (b - r) * basis(blue)
+            } else {
+                (b - g) * basis(magenta) + (r - b) * basis(red)
+            }
+    } else {
+        b * basis(white)
+            + if r <= g {
+                (r - b) * basis(yellow) + (g - r) * basis(green)
+            } else {
+                (g - b) * basis(yellow) + (r - g) * basis(red)
+            }
+    }
+}
+
+/// Maps a uniform number in [0, 1) to a wavelength, uniformly over the
+/// visible range.
+pub fn sample_wavelength(u: SamplePrecision) -> SamplePrecision {
+    MIN_WAVELENGTH + u * (MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+/// Color carried by a path following only the wavelength `lambda` sampled by
+/// `sample_wavelength`, such that the weights average to white over the
+/// spectrum. Channels can be negative outside the working gamut.
+pub fn wavelength_weight(lambda: SamplePrecision) -> ColorSample {
+    let rgb = linear_rgb(lambda);
+    ColorSample {
+        red: rgb.red * WHITE_BALANCE[0],
+        green: rgb.green * WHITE_BALANCE[1],
+        blue: rgb.blue * WHITE_BALANCE[2],
+    }
+}
+
+/// Working space color of a reflectance spectrum `f`, such that a constant
+/// spectrum keeps its value. Sampled at evenly spaced wavelengths, fine enough
+/// for spectra that swing with the wavelength like thin film interference.
+pub fn reflectance_color<F: Fn(SamplePrecision) -> SamplePrecision>(f: F) -> ColorSample {
+    let n = 48;
+    let mut total = ColorSample::BLACK;
+    for i in 0..n {
+        let lambda = sample_wavelength((i as SamplePrecision + 0.5) / n as SamplePrecision);
+        total += wavelength_weight(lambda) * f(lambda);
+    }
+    total / n
+}
+
+/// CIE 1931 color matching functions at `lambda` converted to the linear
+/// Rec. 709 working space.
+fn linear_rgb(lambda: SamplePrecision) -> ColorSample {
+    xyz_to_rgb(cie_xyz(lambda))
+}
+
+/// Converts CIE XYZ to the linear Rec. 709 working space.
+fn xyz_to_rgb((x, y, z): (SamplePrecision, SamplePrecision, SamplePrecision)) -> ColorSample {
+    ColorSample {
+        red: 3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        green: -0.969_266_0 * x + 1.876_010_8 * y + 0.041_556_0 * z,
+        blue: 0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    }
+}
+
+/// Multi-lobe Gaussian fit of the CIE 1931 2° color matching functions by
+/// Wyman, Sloan and Shirley, "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions" (2013).
+fn cie_xyz(lambda: SamplePrecision) -> (SamplePrecision, SamplePrecision, SamplePrecision) {
+    let g = |mu: SamplePrecision, sigma_below: SamplePrecision, sigma_above: SamplePrecision| {
+        let sigma = if lambda < mu {
+            sigma_below
+        } else {
+            sigma_above
+        };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    (
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+#[cfg(test)]
+mod test_spectrum {
+    use super::*;
+
+    #[test]
+    fn weights_average_to_white() {
+        let n = 10_000;
+        let mut total = ColorSample::BLACK;
+        for i in 0..n {
+            let u = (i as SamplePrecision + 0.5) / n as SamplePrecision;
+            total += wavelength_weight(sample_wavelength(u));
+        }
+        let mean = total / n;
+        for channel in [mean.red, mean.green, mean.blue].iter() {
+            assert!((channel - 1.0).abs() < 1e-4);
+        }
+        let red = wavelength_weight(650.0);
+        assert!(red.red > red.green && red.red > red.blue);
+    }
+
+    /// Mean film color of `spectrum` over stratified hero wavelengths.
+    fn film_color<F: Fn(&Wavelengths) -> ColorSample>(spectrum: F) -> ColorSample {
+        let n = 3_000;
+        let mut total = ColorSample::BLACK;
+        for i in 0..n {
+            let wavelengths =
+                Wavelengths::sample((i as SamplePrecision + 0.5) / n as SamplePrecision);
+            total += wavelengths.to_rgb(spectrum(&wavelengths));
+        }
+        total / n
+    }
+
+    #[test]
+    fn upsampled_colors_round_trip() {
+        let colors = [
+            ColorSample::WHITE,
+            ColorSample {
+                red: 0.8,
+                green: 0.3,
+                blue: 0.1,
+            },
+            ColorSample {
+                red: 0.2,
+                green: 0.4,
+                blue: 0.9,
+            },
+        ];
+        for rgb in colors.iter() {
+            let film = film_color(|wavelengths| wavelengths.illuminant(*rgb));
+            for (a, b) in [
+                (film.red, rgb.red),
+                (film.green, rgb.green),
+                (film.blue, rgb.blue),
+            ]
+            .iter()
+            {
+                assert!((a - b).abs() < 0.03, "{:?} became {:?}", rgb, film);
+            }
+        }
+    }
+
+    #[test]
+    fn illuminants_have_unit_luminance() {
+        let d65 = IlluminantSpectrum::new(Illuminant::D65);
+        assert!((d65.color().red - 1.0).abs() < 1e-2);
+        assert!((d65.color().blue - 1.0).abs() < 1e-2);
+        let a = IlluminantSpectrum::new(Illuminant::A);
+        assert!((a.color().luminance() - 1.0).abs() < 1e-2);
+        assert!(a.color().red > a.color().blue);
+        let film = film_color(|wavelengths| a.values(a.color(), wavelengths));
+        assert!((film.red - a.color().red).abs() < 0.03);
+        assert!((film.blue - a.color().blue).abs() < 0.03);
+        assert_eq!(
+            Some(Illuminant::Blackbody(3200.0)),
+            Illuminant::by_name("3200")
+        );
+        assert_eq!(None, Illuminant::by_name("d50"));
+    }
+}
@@ -0,0 +1,315 @@
+use color::buffer::*;
+use distributed::protocol::*;
+use render::*;
+use std::collections::VecDeque;
+use std::error;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::*;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const TILE_SIZE: usize = 64;
+pub const SAMPLES_PER_ASSIGNMENT: usize = 16;
+
+/// Assignments handed to workers, including those taken back from workers
+/// that were lost.
+struct WorkQueue {
+    pending: Mutex<VecDeque<Assignment>>,
+    remaining: AtomicUsize,
+}
+
+impl WorkQueue {
+    /// Waits for an assignment while any are still outstanding, since lost
+    /// workers put theirs back.
+    fn next(&self) -> Option<Assignment> {
+        loop {
+            if let Some(assignment) = self.pending.lock().unwrap().pop_front() {
+                return Some(assignment);
+            }
+            if self.remaining.load(Ordering::SeqCst) == 0 {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn retry(&self, assignment: Assignment) {
+        self.pending.lock().unwrap().push_front(assignment);
+    }
+
+    fn complete(&self) {
+        self.remaining.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Splits the image into tiles and the samples for each tile into
+/// assignments of at most `samples_per_assignment` samples.
+pub fn assignments(
+    imgx: usize,
+    imgy: usize,
+    samples: usize,
+    tile_size: usize,
+    samples_per_assignment: usize,
+) -> Vec<Assignment> {
+    let mut assignments = Vec::new();
+    let mut scheduled = 0;
+    while scheduled < samples {
+        let n_samples = samples_per_assignment.min(samples - scheduled);
+        for y in (0..imgy).step_by(tile_size) {
+            for x in (0..imgx).step_by(tile_size) {
+                assignments.push(Assignment {
+                    id: assignments.len(),
+                    samples: n_samples,
+                    tile: Tile {
+                        x: x,
+                        y: y,
+                        width: tile_size.min(imgx - x),
+                        height: tile_size.min(imgy - y),
+                    },
+                });
+            }
+        }
+        scheduled += n_samples;
+    }
+    assignments
+}
+
+/// Hands `assignments` out to workers connecting on `listener` and collects
+/// their tiles. Workers that disconnect, misbehave or don't answer within
+/// `worker_timeout` are dropped and their assignment goes to another worker.
+/// `on_progress` gets the partial image every `progress_interval`. Returns
+/// once every connected worker has been sent `Done`.
+pub fn coordinate<F>(
+    listener: TcpListener,
+    scene: SceneDescription,
+    assignments: Vec<Assignment>,
+    worker_timeout: Duration,
+    progress_interval: Duration,
+    mut on_progress: F,
+) -> Result<ColorBuffer, Box<error::Error>>
+where
+    F: FnMut(&ColorBuffer) -> Result<(), Box<error::Error>>,
+{
+    let total = assignments.len();
+    let queue = Arc::new(WorkQueue {
+        pending: Mutex::new(assignments.into_iter().collect()),
+        remaining: AtomicUsize::new(total),
+    });
+    let mut color_buffer = ColorBuffer::new(scene.imgx, scene.imgy);
+    let (tx, rx) = channel::<(Assignment, ColorBuffer)>();
+    let workers = Arc::new(Mutex::new(Vec::new()));
+    {
+        let queue = queue.clone();
+        let workers = workers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(err) => {
+                        eprintln!("worker connection failed: {}", err);
+                        continue;
+                    }
+                };
+                let queue = queue.clone();
+                let tx = tx.clone();
+                let scene = scene.clone();
+                let worker = thread::spawn(move || {
+                    let peer = stream
+                        .peer_addr()
+                        .map(|a| a.to_string())
+                        .unwrap_or_default();
+                    if let Err(err) = serve_worker(stream, &scene, &queue, &tx, worker_timeout) {
+                        eprintln!("lost worker {}: {}", peer, err);
+                    }
+                });
+                workers.lock().unwrap().push(worker);
+            }
+        });
+    }
+
+    let mut completed = 0;
+    let mut last_progress = Instant::now();
+    while completed < total {
+        let (assignment, tile_buffer) = rx.recv()?;
+        let tile = assignment.tile;
+        color_buffer.add_buffer_at(tile.x, tile.y, &tile_buffer);
+        completed += 1;
+        if last_progress.elapsed() >= progress_interval {
+            println!("assignment {}/{}", completed, total);
+            on_progress(&color_buffer)?;
+            last_progress = Instant::now();
+        }
+    }
+    // workers are told they are done before the coordinator goes away
+    let workers: Vec<_> = workers.lock().unwrap().drain(..).collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(color_buffer)
+}
+
+fn serve_worker(
+    stream: TcpStream,
+    scene: &SceneDescription,
+    queue: &WorkQueue,
+    results: &Sender<(Assignment, ColorBuffer)>,
+    worker_timeout: Duration,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(worker_timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    write_message(&mut writer, &Message::Scene(Box::new(scene.clone())))?;
+    writer.flush()?;
+    while let Some(assignment) = queue.next() {
+        let tile = assignment.tile;
+        let result = write_message(&mut writer, &Message::Assign(assignment))
+            .and_then(|_| writer.flush())
+            .and_then(|_| read_message(&mut reader, tile.width * tile.height));
+        match result {
+            Ok(Message::Tile(id, color_buffer))
+                if id == assignment.id
+                    && color_buffer.imgx == tile.width
+                    && color_buffer.imgy == tile.height =>
+            {
+                if results.send((assignment, color_buffer)).is_err() {
+                    return Ok(());
+                }
+                queue.complete();
+            }
+            Ok(_) => {
+                queue.retry(assignment);
+                return Err(unexpected_message());
+            }
+            Err(err) => {
+                queue.retry(assignment);
+                return Err(err);
+            }
+        }
+    }
+    write_message(&mut writer, &Message::Done)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test_coordinator {
+    use super::*;
+    use distributed::worker::*;
+    use scene::*;
+    use std::io::Read;
+
+    const IMGX: usize = 12;
+    const IMGY: usize = 8;
+
+    fn scene() -> SceneDescription {
+        SceneDescription {
+            imgx: IMGX,
+            imgy: IMGY,
+            scene_hash: scene_hash(3, IMGX, IMGY, &SceneOptions::new(), None),
+            seed: 3,
+            clamp: None,
+            options: SceneOptions::new(),
+        }
+    }
+
+    fn spawn_coordinator(listener: TcpListener, samples: usize) -> thread::JoinHandle<ColorBuffer> {
+        thread::spawn(move || {
+            let assignments = assignments(IMGX, IMGY, samples, 5, 2);
+            let second = Duration::from_secs(1);
+            coordinate(
+                listener,
+                scene(),
+                assignments,
+                second * 30,
+                second * 30,
+                |_| Ok(()),
+            )
+            .unwrap()
+        })
+    }
+
+    #[test]
+    fn assignments_cover_every_pixel_and_sample() {
+        let mut color_buffer = ColorBuffer::new(IMGX, IMGY);
+        for assignment in assignments(IMGX, IMGY, 5, 5, 2) {
+            let tile = assignment.tile;
+            let mut tile_buffer = ColorBuffer::new(tile.width, tile.height);
+            for count in tile_buffer.sample_counts.iter_mut() {
+                *count = assignment.samples;
+            }
+            color_buffer.add_buffer_at(tile.x, tile.y, &tile_buffer);
+        }
+        assert!(color_buffer.sample_counts.iter().all(|&c| c == 5));
+    }
+
+    #[test]
+    fn workers_on_localhost_render_every_pixel() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let coordinator = spawn_coordinator(listener, 3);
+        let workers: Vec<_> = (0..3)
+            .map(|_| {
+                let address = address.clone();
+                thread::spawn(move || serve_coordinator(&address))
+            })
+            .collect();
+        let color_buffer = coordinator.join().unwrap();
+        assert!(color_buffer.sample_counts.iter().all(|&c| c == 3));
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+    }
+
+    #[test]
+    fn work_of_lost_workers_is_reassigned() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let coordinator = spawn_coordinator(listener, 2);
+        {
+            // takes an assignment, then disconnects without answering
+            let stream = TcpStream::connect(&address).unwrap();
+            let mut reader = BufReader::new(stream);
+            read_message(&mut reader, 0).unwrap();
+            match read_message(&mut reader, 0).unwrap() {
+                Message::Assign(_) => (),
+                _ => panic!("expected an assignment"),
+            }
+        }
+        serve_coordinator(&address).unwrap();
+        let color_buffer = coordinator.join().unwrap();
+        assert!(color_buffer.sample_counts.iter().all(|&c| c == 2));
+    }
+
+    #[test]
+    fn oversized_tiles_are_rejected_and_reassigned() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let coordinator = spawn_coordinator(listener, 2);
+        {
+            // answers with a tile far larger than the assignment
+            let stream = TcpStream::connect(&address).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = BufWriter::new(stream);
+            read_message(&mut reader, 0).unwrap();
+            let id = match read_message(&mut reader, 0).unwrap() {
+                Message::Assign(assignment) => assignment.id,
+                _ => panic!("expected an assignment"),
+            };
+            // a tile message's tag, id and dimensions
+            writer.write_all(&[3]).unwrap();
+            for value in [id as u64, 1 << 20, 1 << 20].iter() {
+                writer.write_all(&value.to_le_bytes()).unwrap();
+            }
+            writer.flush().unwrap();
+            // the coordinator hangs up instead of reading the pixels
+            let mut rest = Vec::new();
+            reader.read_to_end(&mut rest).unwrap();
+        }
+        serve_coordinator(&address).unwrap();
+        let color_buffer = coordinator.join().unwrap();
+        assert!(color_buffer.sample_counts.iter().all(|&c| c == 2));
+    }
+}
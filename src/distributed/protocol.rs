@@ -0,0 +1,178 @@
+use color::buffer::*;
+use color::checkpoint::*;
+use render::*;
+use scene::*;
+use std::io;
+use std::io::{Read, Write};
+
+const SCENE: u8 = 1;
+const ASSIGN: u8 = 2;
+const TILE: u8 = 3;
+const DONE: u8 = 4;
+
+/// Longest scene options a worker accepts, far more than any scene needs.
+const MAX_OPTIONS_LENGTH: u64 = 1 << 16;
+
+/// Everything a worker needs to build the scene being rendered. Files the
+/// options name are not sent: workers read them at the same paths, from a
+/// shared file system. The hash covers their contents, so workers refuse
+/// scenes their build or their copies of the files would make differently.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneDescription {
+    pub imgx: usize,
+    pub imgy: usize,
+    pub scene_hash: u64,
+    pub seed: u64,
+    pub clamp: Option<SampleClamp>,
+    pub options: SceneOptions,
+}
+
+/// A number of samples to render for every pixel of a tile.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub id: usize,
+    pub samples: usize,
+    pub tile: Tile,
+}
+
+pub enum Message {
+    /// Sent to a worker once, when it connects.
+    Scene(Box<SceneDescription>),
+    Assign(Assignment),
+    /// A worker's result for the assignment with the given id.
+    Tile(usize, ColorBuffer),
+    /// No work is left; the worker should disconnect.
+    Done,
+}
+
+pub fn write_message<W: Write>(w: &mut W, message: &Message) -> io::Result<()> {
+    match *message {
+        Message::Scene(ref scene) => {
+            w.write_all(&[SCENE])?;
+            write_u64s(
+                w,
+                &[
+                    scene.imgx as u64,
+                    scene.imgy as u64,
+                    scene.scene_hash,
+                    scene.seed,
+                ],
+            )?;
+            let (mode, max) = match scene.clamp {
+                None => (0, 0.0),
+                Some(clamp) if clamp.indirect_only => (2, clamp.max),
+                Some(clamp) => (1, clamp.max),
+            };
+            let options = scene.options.to_bytes();
+            write_u64s(w, &[mode, max.to_bits(), options.len() as u64])?;
+            w.write_all(&options)
+        }
+        Message::Assign(ref assignment) => {
+            w.write_all(&[ASSIGN])?;
+            write_u64s(
+                w,
+                &[
+                    assignment.id as u64,
+                    assignment.samples as u64,
+                    assignment.tile.x as u64,
+                    assignment.tile.y as u64,
+                    assignment.tile.width as u64,
+                    assignment.tile.height as u64,
+                ],
+            )
+        }
+        Message::Tile(id, ref color_buffer) => {
+            w.write_all(&[TILE])?;
+            write_u64s(w, &[id as u64])?;
+            write_color_buffer(w, color_buffer).map_err(to_io_error)
+        }
+        Message::Done => w.write_all(&[DONE]),
+    }
+}
+
+/// Reads the next message. Tiles of more than `max_tile_pixels` pixels are
+/// rejected before they are allocated.
+pub fn read_message<R: Read>(r: &mut R, max_tile_pixels: usize) -> io::Result<Message> {
+    let mut tag = [0u8];
+    r.read_exact(&mut tag)?;
+    match tag[0] {
+        SCENE => {
+            let imgx = read_u64(r)? as usize;
+            let imgy = read_u64(r)? as usize;
+            let scene_hash = read_u64(r)?;
+            let seed = read_u64(r)?;
+            let mode = read_u64(r)?;
+            let max = f64::from_bits(read_u64(r)?);
+            let clamp = match mode {
+                0 => None,
+                1 | 2 => Some(SampleClamp {
+                    max: max,
+                    indirect_only: mode == 2,
+                }),
+                _ => return Err(unexpected_message()),
+            };
+            let length = read_u64(r)?;
+            if length > MAX_OPTIONS_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("scene options of {} bytes", length),
+                ));
+            }
+            let mut options = vec![0u8; length as usize];
+            r.read_exact(&mut options)?;
+            let options = SceneOptions::from_bytes(&options).ok_or_else(unexpected_message)?;
+            Ok(Message::Scene(Box::new(SceneDescription {
+                imgx: imgx,
+                imgy: imgy,
+                scene_hash: scene_hash,
+                seed: seed,
+                clamp: clamp,
+                options: options,
+            })))
+        }
+        ASSIGN => Ok(Message::Assign(Assignment {
+            id: read_u64(r)? as usize,
+            samples: read_u64(r)? as usize,
+            tile: Tile {
+                x: read_u64(r)? as usize,
+                y: read_u64(r)? as usize,
+                width: read_u64(r)? as usize,
+                height: read_u64(r)? as usize,
+            },
+        })),
+        TILE => {
+            let id = read_u64(r)? as usize;
+            let color_buffer = read_color_buffer(r, max_tile_pixels).map_err(to_io_error)?;
+            Ok(Message::Tile(id, color_buffer))
+        }
+        DONE => Ok(Message::Done),
+        tag => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown message type {}", tag),
+        )),
+    }
+}
+
+pub fn unexpected_message() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "unexpected message")
+}
+
+fn write_u64s<W: Write>(w: &mut W, values: &[u64]) -> io::Result<()> {
+    for value in values.iter() {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn to_io_error(err: CheckpointErr) -> io::Error {
+    match err {
+        CheckpointErr::File(err) => err,
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
@@ -0,0 +1,73 @@
+use distributed::protocol::*;
+use render::*;
+use scene::*;
+use std::error;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::net::TcpStream;
+use std::thread;
+
+/// Opens `n_threads` connections to the coordinator at `address`, each
+/// rendering assignments on its own thread until the coordinator is done.
+pub fn run_worker(address: &str, n_threads: usize) -> Result<(), Box<error::Error>> {
+    let handles: Vec<_> = (0..n_threads)
+        .map(|_| {
+            let address = address.to_string();
+            thread::spawn(move || serve_coordinator(&address))
+        })
+        .collect();
+    for handle in handles {
+        match handle.join() {
+            Ok(result) => result?,
+            Err(_) => return Err(From::from("worker thread panicked")),
+        }
+    }
+    Ok(())
+}
+
+pub fn serve_coordinator(address: &str) -> io::Result<()> {
+    let stream = TcpStream::connect(address)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    // coordinators never send tiles
+    let description = match read_message(&mut reader, 0)? {
+        Message::Scene(description) => description,
+        _ => return Err(unexpected_message()),
+    };
+    let (imgx, imgy) = (description.imgx, description.imgy);
+    if description.scene_hash
+        != scene_hash(
+            description.seed,
+            imgx,
+            imgy,
+            &description.options,
+            description.clamp,
+        )
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the coordinator builds a different scene for this seed or reads different files",
+        ));
+    }
+    let scene = random_scene(description.seed, imgx, imgy, &description.options)
+        .map_err(|err| io::Error::other(err.to_string()))?;
+    loop {
+        match read_message(&mut reader, 0)? {
+            Message::Assign(assignment) => {
+                let tile = assignment.tile;
+                let color_buffer = render_tile(
+                    &scene,
+                    imgx,
+                    imgy,
+                    &tile,
+                    assignment.samples,
+                    description.clamp,
+                );
+                write_message(&mut writer, &Message::Tile(assignment.id, color_buffer))?;
+                writer.flush()?;
+            }
+            Message::Done => return Ok(()),
+            _ => return Err(unexpected_message()),
+        }
+    }
+}
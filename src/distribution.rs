@@ -0,0 +1,130 @@
+use geometry::vec3::*;
+
+/// Piecewise constant probability density on [0, 1) proportional to a
+/// function sampled at equally sized steps.
+pub struct Distribution1D {
+    function: Vec<Dimension>,
+    cdf: Vec<Dimension>,
+    integral: Dimension,
+}
+
+impl Distribution1D {
+    /// `function` must not be negative. A function that is zero everywhere
+    /// gets a uniform density.
+    pub fn new(function: Vec<Dimension>) -> Distribution1D {
+        let n = function.len() as Dimension;
+        let mut cdf = Vec::with_capacity(function.len() + 1);
+        cdf.push(0.0);
+        for value in function.iter() {
+            let previous = cdf[cdf.len() - 1];
+            cdf.push(previous + value / n);
+        }
+        let integral = cdf[cdf.len() - 1];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as Dimension / n
+            };
+        }
+        Distribution1D {
+            function: function,
+            cdf: cdf,
+            integral: integral,
+        }
+    }
+
+    /// Maps a uniform `u` in [0, 1) to a sample. Returns the sample, its
+    /// density and the index of the step it fell in.
+    pub fn sample(&self, u: Dimension) -> (Dimension, Dimension, usize) {
+        // last step starting at or below u; skips steps of zero width
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .saturating_sub(1)
+            .min(self.function.len() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let x = (index as Dimension + offset) / self.function.len() as Dimension;
+        (x, self.pdf(index), index)
+    }
+
+    pub fn pdf(&self, index: usize) -> Dimension {
+        if self.integral > 0.0 {
+            self.function[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+
+    pub fn integral(&self) -> Dimension {
+        self.integral
+    }
+}
+
+/// Piecewise constant density on the unit square, sampled by choosing a row
+/// from the marginal density and then a column within it.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `values` holds `height` rows of `width` values.
+    pub fn new(values: &[Dimension], width: usize, height: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = values
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral()).collect());
+        Distribution2D {
+            rows: rows,
+            marginal: marginal,
+        }
+    }
+
+    /// Maps two uniform numbers to a point (x, y) of the unit square, with y
+    /// increasing with the row, and returns its density.
+    pub fn sample(&self, u: Dimension, v: Dimension) -> (Dimension, Dimension, Dimension) {
+        let (y, marginal_pdf, row) = self.marginal.sample(v);
+        let (x, row_pdf, _) = self.rows[row].sample(u);
+        (x, y, marginal_pdf * row_pdf)
+    }
+}
+
+#[cfg(test)]
+mod test_distribution {
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_function() {
+        let distribution = Distribution1D::new(vec![0.0, 1.0, 3.0, 0.0]);
+        let (x, pdf, index) = distribution.sample(0.0);
+        assert_eq!((0.25, 1.0, 1), (x, pdf, index));
+        let (x, pdf, index) = distribution.sample(0.5);
+        assert_eq!(2, index);
+        assert!((x - (2.0 + 1.0 / 3.0) / 4.0).abs() < 1e-12);
+        assert_eq!(3.0, pdf);
+        assert_eq!(2, distribution.sample(0.999_999).2);
+    }
+
+    #[test]
+    fn density_is_the_normalized_function() {
+        let values = [1.0, 2.0, 0.0, 4.0, 0.5, 0.5];
+        let mean = values.iter().sum::<Dimension>() / 6.0;
+        let distribution = Distribution2D::new(&values, 3, 2);
+        for i in 0..10 {
+            for j in 0..10 {
+                let (u, v) = ((i as Dimension + 0.5) / 10.0, (j as Dimension + 0.5) / 10.0);
+                let (x, y, pdf) = distribution.sample(u, v);
+                let cell = (y * 2.0) as usize * 3 + (x * 3.0) as usize;
+                assert!((pdf - values[cell] / mean).abs() < 1e-12);
+            }
+        }
+    }
+}
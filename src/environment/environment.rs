@@ -0,0 +1,10 @@
+use color::sample::*;
+use geometry::vec3::*;
+
+/// Radiance arriving from infinitely far away, seen by rays that escape the
+/// scene.
+pub trait Environment {
+    fn radiance(&self, direction: &Vec3) -> ColorSample;
+}
+
+pub type EnvironmentSS = Environment + Send + Sync;
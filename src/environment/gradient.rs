@@ -0,0 +1,18 @@
+use color::sample::*;
+use environment::environment::*;
+use geometry::vec3::*;
+
+/// White at the horizon blending into light blue overhead.
+pub struct Gradient;
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vec3) -> ColorSample {
+        const LIGHT_BLUE: ColorSample = ColorSample {
+            red: 0.5,
+            green: 0.7,
+            blue: 1.0,
+        };
+        let t = 0.5 * (direction.unit().y + 1.0);
+        (1.0 - t) * ColorSample::WHITE + t * LIGHT_BLUE
+    }
+}
@@ -0,0 +1,125 @@
+use color::buffer::*;
+use color::sample::*;
+use distribution::*;
+use geometry::vec3::*;
+use light::light::*;
+use rand::{thread_rng, Rng};
+
+/// Light from an equirectangular image surrounding the scene, with +y up
+/// through the top row. Sampled as a light in proportion to the luminance
+/// of its pixels, so small bright features like the sun in an HDRI light the
+/// scene without fireflies.
+pub struct EnvironmentMap {
+    pixels: Vec<ColorSample>,
+    width: usize,
+    height: usize,
+    /// Turn around the y axis in radians.
+    rotation: Dimension,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// Scales the image by `intensity` and turns it by `rotation` radians
+    /// around the y axis.
+    pub fn new(
+        image: &ColorBuffer,
+        rotation: Dimension,
+        intensity: SamplePrecision,
+    ) -> EnvironmentMap {
+        let (width, height) = (image.imgx, image.imgy);
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            // rows near the poles cover less of the sphere
+            let sin_theta = ((y as Dimension + 0.5) / height as Dimension * PI_DIMENSION).sin();
+            for x in 0..width {
+                let color = image.pixel(x, y) * intensity;
+                pixels.push(color);
+                weights.push(color.luminance().max(0.0) * sin_theta);
+            }
+        }
+        EnvironmentMap {
+            pixels: pixels,
+            width: width,
+            height: height,
+            rotation: rotation,
+            distribution: Distribution2D::new(&weights, width, height),
+        }
+    }
+
+    fn lookup(&self, u: Dimension, v: Dimension) -> ColorSample {
+        let x = ((u * self.width as Dimension) as usize).min(self.width - 1);
+        let y = ((v * self.height as Dimension) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+
+    /// Image coordinates in [0, 1) of a unit direction.
+    fn to_image(&self, direction: &Vec3) -> (Dimension, Dimension) {
+        let theta = direction.y.max(-1.0).min(1.0).acos();
+        let phi = direction.z.atan2(direction.x) - self.rotation;
+        let u = (phi / (2.0 * PI_DIMENSION)).rem_euclid(1.0);
+        (u, theta / PI_DIMENSION)
+    }
+}
+
+impl Light for EnvironmentMap {
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        let mut rng = thread_rng();
+        let (u, v, pdf) = self.distribution.sample(
+            rng.gen_range::<Dimension>(0.0, 1.0),
+            rng.gen_range::<Dimension>(0.0, 1.0),
+        );
+        let (theta, phi) = (v * PI_DIMENSION, u * 2.0 * PI_DIMENSION + self.rotation);
+        let sin_theta = theta.sin();
+        // the image maps onto the sphere with area 2 pi^2 sin theta
+        let pdf = pdf / (2.0 * PI_DIMENSION * PI_DIMENSION * sin_theta);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        Some(LightSample {
+            direction: Vec3::new(sin_theta * phi.cos(), theta.cos(), sin_theta * phi.sin()),
+            distance: MAX_DIMENSION,
+            radiance: self.lookup(u, v) * pdf.recip(),
+        })
+    }
+
+    fn emitted(&self, direction: &Vec3) -> ColorSample {
+        let (u, v) = self.to_image(&direction.unit());
+        self.lookup(u, v)
+    }
+}
+
+#[cfg(test)]
+mod test_environment_map {
+    use super::*;
+
+    #[test]
+    fn samples_match_lookups_and_rotation() {
+        let mut image = ColorBuffer::new(8, 4);
+        for y in 0..4 {
+            for x in 0..8 {
+                let v = if (x, y) == (2, 1) { 100.0 } else { 1.0 };
+                image.add_color(
+                    x,
+                    y,
+                    ColorSample {
+                        red: v,
+                        green: v,
+                        blue: v,
+                    },
+                );
+            }
+        }
+        let map = EnvironmentMap::new(&image, 0.3, 2.0);
+        let mut bright = 0;
+        for _ in 0..100 {
+            let sample = map.sample(&Vec3::ZERO).unwrap();
+            let emitted = map.emitted(&sample.direction);
+            assert!(emitted.red == 2.0 || emitted.red == 200.0);
+            if emitted.red == 200.0 {
+                bright += 1;
+            }
+        }
+        assert!(bright > 50);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod environment;
+pub mod gradient;
+pub mod map;
+pub mod preetham;
+pub mod uniform;
@@ -0,0 +1,222 @@
+use color::sample::*;
+use environment::environment::*;
+use geometry::vec3::*;
+use light::sun::*;
+
+/// Radiance units per kcd/m², scaling daylight so a white surface lit by a
+/// high sun is around one.
+const SKY_SCALE: SamplePrecision = 0.05;
+/// Illuminance of the sun outside the atmosphere, in klx.
+const EXTRATERRESTRIAL_SUN: SamplePrecision = 128.0;
+/// Wavelengths in micrometers standing in for the red, green and blue
+/// channels when attenuating sunlight.
+const WAVELENGTHS: [SamplePrecision; 3] = [0.680, 0.550, 0.440];
+
+/// Clear sky model by Preetham, Shirley and Smits, "A Practical Analytic
+/// Model for Daylight" (1999). Below the horizon the sky is replaced by
+/// diffuse ground of the given albedo lit by the sun and sky.
+pub struct PreethamSky {
+    sun_direction: Vec3,
+    turbidity: SamplePrecision,
+    /// Perez coefficients A to E for luminance and the x and y chromaticities.
+    perez: [[SamplePrecision; 5]; 3],
+    /// Luminance and chromaticities at the zenith, divided by the Perez
+    /// function there so directions only need multiplying.
+    zenith: [SamplePrecision; 3],
+    ground: ColorSample,
+}
+
+impl PreethamSky {
+    /// `sun_direction` must point above the horizon. The model is fitted for
+    /// turbidities from 2, a very clear sky, to 10, a hazy one.
+    pub fn new(
+        sun_direction: Vec3,
+        turbidity: SamplePrecision,
+        ground_albedo: SamplePrecision,
+    ) -> PreethamSky {
+        let sun_direction = sun_direction.unit();
+        let t = turbidity;
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+        let theta_s = sun_direction.y.min(1.0).acos();
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI_DIMENSION - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[SamplePrecision; 4]; 3]| {
+            let thetas = [theta_s.powi(3), theta_s.powi(2), theta_s, 1.0];
+            let row = |r: [SamplePrecision; 4]| {
+                r.iter()
+                    .zip(thetas.iter())
+                    .map(|(a, b)| a * b)
+                    .sum::<SamplePrecision>()
+            };
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let mut zenith = [luminance, x, y];
+        for (z, coefficients) in zenith.iter_mut().zip(perez.iter()) {
+            *z /= perez_function(coefficients, 1.0, theta_s, sun_direction.y);
+        }
+        let mut sky = PreethamSky {
+            sun_direction: sun_direction,
+            turbidity: turbidity,
+            perez: perez,
+            zenith: zenith,
+            ground: ColorSample::BLACK,
+        };
+        let irradiance = sky.sky_irradiance() + sky.sun_irradiance() * sun_direction.y;
+        sky.ground = irradiance * (ground_albedo / PI_DIMENSION);
+        sky
+    }
+
+    /// The sun matching this sky, to be sampled as a light.
+    pub fn sun(&self) -> Sun {
+        Sun::new(
+            self.sun_direction,
+            self.sun_irradiance(),
+            SUN_ANGULAR_RADIUS,
+        )
+    }
+
+    /// Sunlight after Rayleigh and aerosol scattering along the sun's path
+    /// through the atmosphere, on a surface facing the sun.
+    fn sun_irradiance(&self) -> ColorSample {
+        let theta_degrees = self.sun_direction.y.min(1.0).acos().to_degrees();
+        let relative_air_mass =
+            (self.sun_direction.y + 0.15 * (93.885 - theta_degrees).powf(-1.253)).recip();
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |lambda: SamplePrecision| {
+            let rayleigh = (-0.008735 * lambda.powf(-4.08) * relative_air_mass).exp();
+            let aerosol = (-beta * lambda.powf(-1.3) * relative_air_mass).exp();
+            EXTRATERRESTRIAL_SUN * SKY_SCALE * rayleigh * aerosol
+        };
+        ColorSample {
+            red: transmittance(WAVELENGTHS[0]),
+            green: transmittance(WAVELENGTHS[1]),
+            blue: transmittance(WAVELENGTHS[2]),
+        }
+    }
+
+    /// Irradiance from the sky dome, without the sun, on an upward facing
+    /// surface.
+    fn sky_irradiance(&self) -> ColorSample {
+        const STEPS: usize = 32;
+        let d_theta = PI_DIMENSION / 2.0 / STEPS as Dimension;
+        let d_phi = 2.0 * PI_DIMENSION / (2 * STEPS) as Dimension;
+        let mut irradiance = ColorSample::BLACK;
+        for i in 0..STEPS {
+            let theta = (i as Dimension + 0.5) * d_theta;
+            for j in 0..2 * STEPS {
+                let phi = (j as Dimension + 0.5) * d_phi;
+                let direction = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let weight = theta.cos() * theta.sin() * d_theta * d_phi;
+                irradiance += self.sky_radiance(&direction) * weight;
+            }
+        }
+        irradiance
+    }
+
+    fn sky_radiance(&self, direction: &Vec3) -> ColorSample {
+        let cos_theta = direction.y.max(1e-3);
+        let cos_gamma = direction.dot(self.sun_direction).max(-1.0).min(1.0);
+        let gamma = cos_gamma.acos();
+        let mut value = self.zenith;
+        for (v, coefficients) in value.iter_mut().zip(self.perez.iter()) {
+            *v *= perez_function(coefficients, cos_theta, gamma, cos_gamma);
+        }
+        let [luminance, x, y] = value;
+        let xyz = [x / y * luminance, luminance, (1.0 - x - y) / y * luminance];
+        let to_rgb = |m: [SamplePrecision; 3]| {
+            (m[0] * xyz[0] + m[1] * xyz[1] + m[2] * xyz[2]).max(0.0) * SKY_SCALE
+        };
+        ColorSample {
+            red: to_rgb([3.240_454_2, -1.537_138_5, -0.498_531_4]),
+            green: to_rgb([-0.969_266_0, 1.876_010_8, 0.041_556_0]),
+            blue: to_rgb([0.055_643_4, -0.204_025_9, 1.057_225_2]),
+        }
+    }
+}
+
+impl Environment for PreethamSky {
+    fn radiance(&self, direction: &Vec3) -> ColorSample {
+        let direction = direction.unit();
+        if direction.y < 0.0 {
+            self.ground
+        } else {
+            self.sky_radiance(&direction)
+        }
+    }
+}
+
+/// Relative sky brightness by zenith angle and angle to the sun, after Perez
+/// et al.
+fn perez_function(
+    coefficients: &[SamplePrecision; 5],
+    cos_theta: Dimension,
+    gamma: Dimension,
+    cos_gamma: Dimension,
+) -> SamplePrecision {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+#[cfg(test)]
+mod test_preetham {
+    use super::*;
+
+    fn sky(elevation_degrees: Dimension) -> PreethamSky {
+        let elevation = elevation_degrees.to_radians();
+        PreethamSky::new(Vec3::new(elevation.cos(), elevation.sin(), 0.0), 3.0, 0.3)
+    }
+
+    #[test]
+    fn is_brightest_around_the_sun_and_blue_overhead() {
+        let sky = sky(30.0);
+        let near_sun = sky.radiance(&Vec3::new(1.0, 0.7, 0.1));
+        let away = sky.radiance(&Vec3::new(-1.0, 0.7, 0.0));
+        let zenith = sky.radiance(&Vec3::new(0.0, 1.0, 0.0));
+        assert!(near_sun.luminance() > away.luminance());
+        assert!(zenith.blue > zenith.red);
+    }
+
+    #[test]
+    fn low_sun_is_dimmer_and_redder() {
+        let high = sky(60.0).sun_irradiance();
+        let low = sky(5.0).sun_irradiance();
+        assert!(low.luminance() < high.luminance());
+        assert!(low.blue / low.red < high.blue / high.red);
+    }
+}
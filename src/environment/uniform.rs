@@ -0,0 +1,14 @@
+use color::sample::*;
+use environment::environment::*;
+use geometry::vec3::*;
+
+/// The same radiance from every direction.
+pub struct Uniform {
+    pub radiance: ColorSample,
+}
+
+impl Environment for Uniform {
+    fn radiance(&self, _direction: &Vec3) -> ColorSample {
+        self.radiance
+    }
+}
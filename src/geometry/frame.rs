@@ -0,0 +1,74 @@
+use geometry::vec3::*;
+
+/// Orthonormal basis around a direction, for turning directions sampled
+/// around the z axis into world space.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Frame {
+    /// Builds a frame whose w axis is the unit vector `w`.
+    pub fn from_w(w: Vec3) -> Frame {
+        // branchless construction by Duff et al.
+        let sign = 1.0f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        Frame {
+            u: Vec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: Vec3::new(b, sign + w.y * w.y * a, -w.y),
+            w: w,
+        }
+    }
+
+    /// Builds a frame whose w axis is the unit vector `w` and whose u axis is
+    /// `tangent` made perpendicular to it, falling back to `from_w` when the
+    /// two are parallel.
+    pub fn from_w_and_tangent(w: Vec3, tangent: Vec3) -> Frame {
+        let u = tangent - w * w.dot(tangent);
+        if u.squared_length() < 1e-12 {
+            return Frame::from_w(w);
+        }
+        let u = u.unit();
+        Frame {
+            u: u,
+            v: w.cross(u),
+            w: w,
+        }
+    }
+
+    pub fn to_world(&self, local: Vec3) -> Vec3 {
+        local.x * self.u + local.y * self.v + local.z * self.w
+    }
+
+    pub fn to_local(&self, world: Vec3) -> Vec3 {
+        Vec3::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
+
+#[cfg(test)]
+mod test_frame {
+    use super::*;
+
+    #[test]
+    fn is_orthonormal() {
+        for w in [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit(),
+        ]
+        .iter()
+        {
+            let frame = Frame::from_w(*w);
+            assert!(frame.u.dot(frame.v).abs() < 1e-12);
+            assert!(frame.u.dot(frame.w).abs() < 1e-12);
+            assert!(frame.v.dot(frame.w).abs() < 1e-12);
+            assert!((frame.u.length() - 1.0).abs() < 1e-12);
+            assert!((frame.v.length() - 1.0).abs() < 1e-12);
+            let back = frame.to_world(frame.to_local(Vec3::new(0.3, -0.2, 0.9)));
+            assert!((back.x - 0.3).abs() < 1e-12 && (back.z - 0.9).abs() < 1e-12);
+        }
+    }
+}
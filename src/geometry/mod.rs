@@ -1,2 +1,3 @@
+pub mod frame;
 pub mod ray;
 pub mod vec3;
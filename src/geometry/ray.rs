@@ -4,6 +4,10 @@ use geometry::vec3::*;
 pub struct Ray {
     pub direction: Vec3,
     pub origin: Vec3,
+    /// Wavelength in nanometers carried by paths that were split into
+    /// wavelengths, such as by dispersive glass. None for paths carrying
+    /// all of the visible spectrum.
+    pub wavelength: Option<Dimension>,
 }
 
 impl Ray {
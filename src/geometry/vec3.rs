@@ -7,7 +7,7 @@ pub type Dimension = f64;
 pub const MAX_DIMENSION: Dimension = f64::MAX;
 pub const PI_DIMENSION: Dimension = PI;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Vec3 {
     pub x: Dimension,
     pub y: Dimension,
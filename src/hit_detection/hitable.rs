@@ -6,7 +6,16 @@ use world::bounds::*;
 pub struct HitRecord {
     pub t: Dimension,
     pub p: Vec3,
+    /// Geometric normal, kept for telling the sides of the surface apart.
     pub normal: Vec3,
+    /// Normal for shading, which normal and bump maps perturb.
+    pub shading_normal: Vec3,
+    /// Surface coordinates, each in [0, 1].
+    pub u: Dimension,
+    pub v: Dimension,
+    /// Rates of change of the position with the surface coordinates.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
 pub trait Hitable {
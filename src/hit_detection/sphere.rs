@@ -55,10 +55,30 @@ pub fn hit_sphere(
             continue;
         }
         let p = ray.point_at_parameter(temp);
+        let normal = (p - center) / radius;
+        // u turns around the y axis and v rises from the bottom pole to the
+        // top, so the tangents and the outward normal are right handed
+        let (outward, r) = ((p - center) / radius.abs(), radius.abs());
+        let phi = (-outward.z).atan2(outward.x);
+        let theta = outward.y.max(-1.0).min(1.0).acos();
+        let sin_theta = (outward.x * outward.x + outward.z * outward.z)
+            .sqrt()
+            .max(1e-12);
         return Some(HitRecord {
             t: temp,
             p: p,
-            normal: (p - center) / radius,
+            normal: normal,
+            shading_normal: normal,
+            u: (phi + PI_DIMENSION) / (2.0 * PI_DIMENSION),
+            v: 1.0 - theta / PI_DIMENSION,
+            dpdu: 2.0 * PI_DIMENSION * r * Vec3::new(outward.z, 0.0, -outward.x),
+            dpdv: PI_DIMENSION
+                * r
+                * Vec3::new(
+                    -outward.y * outward.x / sin_theta,
+                    sin_theta,
+                    -outward.y * outward.z / sin_theta,
+                ),
         });
     }
 
@@ -1,6 +1,7 @@
 use color::buffer::*;
-use color::sample::*;
+use image::color_space::*;
 use image::convert::*;
+use image::tone_map::*;
 use png;
 
 const COLORS_PER_PIXEL: usize = 3;
@@ -19,6 +20,7 @@ pub struct ImageBuffer {
     pub bytes_per_row: usize,
     pub imgx: usize,
     pub imgy: usize,
+    pub primaries: OutputPrimaries,
 }
 
 impl ImageBuffer {
@@ -38,23 +40,21 @@ impl ImageBuffer {
             bytes_per_row: bytes_per_row,
             imgx: imgx,
             imgy: imgy,
+            primaries: OutputPrimaries::Rec709,
         }
     }
     pub fn from_color_buffer(
         color_buffer: &ColorBuffer,
         bytes_per_color: BytesPerColor,
+        display: &DisplayTransform,
     ) -> ImageBuffer {
         let mut buffer = ImageBuffer::new(color_buffer.imgx, color_buffer.imgy, bytes_per_color);
-        let sample_counts = color_buffer.sample_counts.iter().flat_map(|c| {
-            let c = *c as SamplePrecision;
-            vec![c, c, c]
-        });
-        let colors = color_buffer
-            .buffer
-            .iter()
-            .zip(sample_counts)
-            .map(|(c, n)| c / n)
-            .map(|c| gamma_2(c));
+        buffer.primaries = display.primaries;
+        let colors = (0..color_buffer.imgy)
+            .flat_map(|y| (0..color_buffer.imgx).map(move |x| (x, y)))
+            .map(|(x, y)| display.apply(color_buffer.pixel(x, y)))
+            .flat_map(|c| vec![c.red, c.green, c.blue])
+            .map(|c| display.primaries.oetf(c.max(0.0)));
         match bytes_per_color {
             BytesPerColor::Two => {
                 for color in colors {
@@ -0,0 +1,106 @@
+use color::sample::*;
+use image::convert::*;
+
+/// Chromaticity coordinates of the D65 white point shared by all outputs.
+const D65: (f64, f64) = (0.3127, 0.3290);
+
+/// Primaries of the output color space. Rendering happens with linear Rec.709
+/// primaries, which is also the sRGB gamut.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputPrimaries {
+    Rec709,
+    DisplayP3,
+    Rec2020,
+}
+
+impl OutputPrimaries {
+    /// Looks up output primaries by their command line name.
+    pub fn by_name(name: &str) -> Option<OutputPrimaries> {
+        match name {
+            "rec709" | "srgb" => Some(OutputPrimaries::Rec709),
+            "p3" | "display-p3" => Some(OutputPrimaries::DisplayP3),
+            "rec2020" => Some(OutputPrimaries::Rec2020),
+            _ => None,
+        }
+    }
+
+    /// Converts a linear color from the working space to these primaries.
+    pub fn from_working_space(self, color: ColorSample) -> ColorSample {
+        let m = match self {
+            OutputPrimaries::Rec709 => return color,
+            OutputPrimaries::DisplayP3 => [
+                [0.8224619687, 0.1775380313, 0.0000000000],
+                [0.0331941989, 0.9668058011, 0.0000000000],
+                [0.0170826307, 0.0723974407, 0.9105199286],
+            ],
+            OutputPrimaries::Rec2020 => [
+                [0.6274038959, 0.3292830384, 0.0433130657],
+                [0.0690972894, 0.9195403951, 0.0113623156],
+                [0.0163914389, 0.0880133079, 0.8955952532],
+            ],
+        };
+        ColorSample {
+            red: m[0][0] * color.red + m[0][1] * color.green + m[0][2] * color.blue,
+            green: m[1][0] * color.red + m[1][1] * color.green + m[1][2] * color.blue,
+            blue: m[2][0] * color.red + m[2][1] * color.green + m[2][2] * color.blue,
+        }
+    }
+
+    /// Encodes a linear value in [0, 1] with the transfer function of the
+    /// color space. Display P3 shares the sRGB curve.
+    pub fn oetf(self, value: SamplePrecision) -> SamplePrecision {
+        match self {
+            OutputPrimaries::Rec709 | OutputPrimaries::DisplayP3 => srgb_oetf(value),
+            OutputPrimaries::Rec2020 => rec2020_oetf(value),
+        }
+    }
+
+    /// Power the transfer function approximates, for the PNG gAMA chunk.
+    pub fn gamma(self) -> f64 {
+        match self {
+            OutputPrimaries::Rec709 | OutputPrimaries::DisplayP3 => 1.0 / 2.2,
+            OutputPrimaries::Rec2020 => 0.45,
+        }
+    }
+
+    /// Color primaries and transfer characteristics codes of ITU-T H.273,
+    /// for the PNG cICP chunk.
+    pub fn coding_points(self) -> (u8, u8) {
+        match self {
+            OutputPrimaries::Rec709 => (1, 13),
+            OutputPrimaries::DisplayP3 => (12, 13),
+            OutputPrimaries::Rec2020 => (9, 15),
+        }
+    }
+
+    /// White point followed by the red, green and blue primaries, as CIE xy
+    /// chromaticity coordinates.
+    pub fn chromaticities(&self) -> [(f64, f64); 4] {
+        match *self {
+            OutputPrimaries::Rec709 => [D65, (0.640, 0.330), (0.300, 0.600), (0.150, 0.060)],
+            OutputPrimaries::DisplayP3 => [D65, (0.680, 0.320), (0.265, 0.690), (0.150, 0.060)],
+            OutputPrimaries::Rec2020 => [D65, (0.708, 0.292), (0.170, 0.797), (0.131, 0.046)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_output_primaries {
+    use super::*;
+
+    #[test]
+    fn white_stays_white() {
+        for primaries in [
+            OutputPrimaries::Rec709,
+            OutputPrimaries::DisplayP3,
+            OutputPrimaries::Rec2020,
+        ]
+        .iter()
+        {
+            let white = primaries.from_working_space(ColorSample::WHITE);
+            for c in [white.red, white.green, white.blue].iter() {
+                assert!((c - 1.0).abs() < 1e-6, "{:?} {:?}", primaries, white);
+            }
+        }
+    }
+}
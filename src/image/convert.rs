@@ -22,9 +22,71 @@ pub fn to_sixteen_bits(color: SamplePrecision) -> u16 {
     (SIXTEEN_BIT_MAX * color).trunc() as u16
 }
 
+/// Converts to the bit pattern of the nearest IEEE 754 half precision float,
+/// rounding ties to even. Values too large for a half become infinity.
+pub fn to_half(color: f32) -> u16 {
+    let bits = color.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    let (half, remainder, halfway) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        (
+            mantissa >> shift,
+            mantissa & ((1 << shift) - 1),
+            1 << (shift - 1),
+        )
+    } else {
+        (
+            ((half_exponent as u32) << 10) | (mantissa >> 13),
+            mantissa & 0x1fff,
+            0x1000,
+        )
+    };
+    // a carry out of the mantissa correctly bumps the exponent
+    let half = if remainder > halfway || (remainder == halfway && half & 1 == 1) {
+        half + 1
+    } else {
+        half
+    };
+    sign | half as u16
+}
+
+/// The sRGB opto-electronic transfer function, mapping linear values in
+/// [0, 1] to encoded values in [0, 1].
 #[inline(always)]
-pub fn gamma_2(color: SamplePrecision) -> SamplePrecision {
-    color.sqrt()
+pub fn srgb_oetf(color: SamplePrecision) -> SamplePrecision {
+    if color <= 0.0031308 {
+        12.92 * color
+    } else {
+        1.055 * color.powf(2.4f64.recip()) - 0.055
+    }
+}
+
+/// The Rec.2020 opto-electronic transfer function, at the precision of its
+/// 12 bit variant, mapping linear values in [0, 1] to encoded values in
+/// [0, 1].
+#[inline(always)]
+pub fn rec2020_oetf(color: SamplePrecision) -> SamplePrecision {
+    const ALPHA: SamplePrecision = 1.099_296_826_809_44;
+    const BETA: SamplePrecision = 0.018_053_968_510_807;
+    if color < BETA {
+        4.5 * color
+    } else {
+        ALPHA * color.powf(0.45) - (ALPHA - 1.0)
+    }
 }
 
 #[cfg(test)]
@@ -104,3 +166,91 @@ mod test_conversion_to_sixteen_bits {
         }
     }
 }
+
+#[cfg(test)]
+mod test_conversion_to_half {
+    use super::*;
+    use std::f32;
+
+    #[test]
+    fn exactly_representable_values() {
+        for &(v, bits) in [
+            (0.0, 0x0000),
+            (-0.0, 0x8000),
+            (0.5, 0x3800),
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (65504.0, 0x7bff),
+        ]
+        .iter()
+        {
+            assert_eq!(bits, to_half(v), "{}", v);
+        }
+    }
+
+    #[test]
+    fn subnormals() {
+        assert_eq!(0x0001, to_half(2f32.powi(-24)));
+        assert_eq!(0x0200, to_half(2f32.powi(-15)));
+        assert_eq!(0x0000, to_half(2f32.powi(-26)));
+    }
+
+    #[test]
+    fn rounds_to_nearest_even() {
+        let ulp = 2f32.powi(-10);
+        assert_eq!(0x3c00, to_half(1.0 + ulp * 0.5));
+        assert_eq!(0x3c02, to_half(1.0 + ulp * 1.5));
+        assert_eq!(0x3c01, to_half(1.0 + ulp * 0.75));
+    }
+
+    #[test]
+    fn overflow_to_infinity() {
+        for v in vec![65520.0, 1e10, f32::INFINITY] {
+            assert_eq!(0x7c00, to_half(v), "{}", v);
+        }
+        assert_eq!(0xfc00, to_half(f32::NEG_INFINITY));
+        assert_eq!(0x7e00, to_half(f32::NAN));
+    }
+}
+
+#[cfg(test)]
+mod test_srgb_oetf {
+    use super::*;
+
+    #[test]
+    fn end_points() {
+        assert_eq!(0.0, srgb_oetf(0.0));
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn linear_segment_meets_power_segment() {
+        let knee = 0.0031308;
+        let below = 12.92 * knee;
+        let above = 1.055 * (knee + 1e-12 as SamplePrecision).powf(1.0 / 2.4) - 0.055;
+        assert!((below - above).abs() < 1e-6, "{} {}", below, above);
+    }
+
+    #[test]
+    fn middle_grey() {
+        assert!((srgb_oetf(0.18) - 0.4613561).abs() < 1e-6);
+    }
+}
+
+#[cfg(test)]
+mod test_rec2020_oetf {
+    use super::*;
+
+    #[test]
+    fn end_points() {
+        assert_eq!(0.0, rec2020_oetf(0.0));
+        assert!((rec2020_oetf(1.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn linear_segment_meets_power_segment() {
+        let knee = 0.018_053_968_510_807;
+        let below = 4.5 * knee;
+        assert!((below - rec2020_oetf(knee)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,324 @@
+use color::buffer::*;
+use deflate;
+use image::convert::*;
+use image::write::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const VERSION: u32 = 2;
+const CHANNEL_NAMES: [&str; 3] = ["B", "G", "R"];
+
+/// Channel storage written to the file. Channels keep their linear,
+/// unclamped values either way.
+#[derive(Copy, Clone)]
+pub enum ExrPixelType {
+    Half = 1,
+    Float = 2,
+}
+
+#[derive(Copy, Clone)]
+pub enum ExrCompression {
+    None = 0,
+    Rle = 1,
+    Zip = 3,
+}
+
+/// How EXR outputs are stored: half floats with ZIP compression unless set
+/// otherwise.
+#[derive(Copy, Clone)]
+pub struct ExrFormat {
+    pub pixel_type: ExrPixelType,
+    pub compression: ExrCompression,
+}
+
+impl ExrFormat {
+    pub fn new() -> ExrFormat {
+        ExrFormat {
+            pixel_type: ExrPixelType::Half,
+            compression: ExrCompression::Zip,
+        }
+    }
+}
+
+impl ExrCompression {
+    pub fn by_name(name: &str) -> Option<ExrCompression> {
+        match name {
+            "none" => Some(ExrCompression::None),
+            "rle" => Some(ExrCompression::Rle),
+            "zip" => Some(ExrCompression::Zip),
+            _ => None,
+        }
+    }
+}
+
+impl ExrPixelType {
+    fn bytes(self) -> usize {
+        match self {
+            ExrPixelType::Half => 2,
+            ExrPixelType::Float => 4,
+        }
+    }
+}
+
+impl ExrCompression {
+    fn lines_per_block(self) -> usize {
+        match self {
+            ExrCompression::None | ExrCompression::Rle => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// Writes the averaged `ColorBuffer` as a single part, scanline OpenEXR file.
+/// No gamma, clamping or quantization is applied.
+pub fn save_exr<'a>(
+    file_name: &'a str,
+    color_buffer: &ColorBuffer,
+    pixel_type: ExrPixelType,
+    compression: ExrCompression,
+) -> Result<(), WriteImageFileErr> {
+    let header = header(color_buffer, pixel_type, compression);
+    let lines_per_block = compression.lines_per_block();
+    let blocks: Vec<Vec<u8>> = (0..color_buffer.imgy)
+        .step_by(lines_per_block)
+        .map(|y| {
+            let last = (y + lines_per_block).min(color_buffer.imgy);
+            let pixels = block_pixels(color_buffer, y, last, pixel_type);
+            let data = compress(pixels, compression);
+            let mut block = Vec::with_capacity(data.len() + 8);
+            block.extend_from_slice(&(y as i32).to_le_bytes());
+            block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            block.extend(data);
+            block
+        })
+        .collect();
+
+    let path = Path::new(file_name);
+    let file = File::create(path)?;
+    let ref mut w = BufWriter::new(file);
+    w.write_all(&header)?;
+    let mut offset = (header.len() + 8 * blocks.len()) as u64;
+    for block in blocks.iter() {
+        w.write_all(&offset.to_le_bytes())?;
+        offset += block.len() as u64;
+    }
+    for block in blocks.iter() {
+        w.write_all(block)?;
+    }
+    w.flush()?;
+
+    Ok(())
+}
+
+fn header(
+    color_buffer: &ColorBuffer,
+    pixel_type: ExrPixelType,
+    compression: ExrCompression,
+) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut channels = Vec::new();
+    for name in CHANNEL_NAMES.iter() {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&(pixel_type as i32).to_le_bytes());
+        // pLinear and three reserved bytes
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        // x and y sampling
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+
+    attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression as u8],
+    );
+
+    let mut window = Vec::new();
+    for v in [
+        0,
+        0,
+        color_buffer.imgx as i32 - 1,
+        color_buffer.imgy as i32 - 1,
+    ]
+    .iter()
+    {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+
+    // increasing y
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_bits().to_le_bytes(),
+    );
+    let mut center = Vec::new();
+    center.extend_from_slice(&0f32.to_bits().to_le_bytes());
+    center.extend_from_slice(&0f32.to_bits().to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &center);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_bits().to_le_bytes(),
+    );
+    header.push(0);
+    header
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(attribute_type.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+/// Pixel data for scanlines `first..last`. Each scanline holds every channel
+/// in turn, in alphabetical channel order.
+fn block_pixels(
+    color_buffer: &ColorBuffer,
+    first: usize,
+    last: usize,
+    pixel_type: ExrPixelType,
+) -> Vec<u8> {
+    let mut pixels =
+        Vec::with_capacity((last - first) * color_buffer.imgx * 3 * pixel_type.bytes());
+    for y in first..last {
+        let row: Vec<_> = (0..color_buffer.imgx)
+            .map(|x| color_buffer.pixel(x, y))
+            .collect();
+        for channel in 0..3 {
+            for color in row.iter() {
+                let value = match channel {
+                    0 => color.blue,
+                    1 => color.green,
+                    _ => color.red,
+                } as f32;
+                match pixel_type {
+                    ExrPixelType::Half => pixels.extend_from_slice(&to_half(value).to_le_bytes()),
+                    ExrPixelType::Float => pixels.extend_from_slice(&value.to_bits().to_le_bytes()),
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// Compressed block data. Blocks that don't shrink are stored uncompressed,
+/// which readers detect from the block size.
+fn compress(pixels: Vec<u8>, compression: ExrCompression) -> Vec<u8> {
+    let compressed = match compression {
+        ExrCompression::None => return pixels,
+        ExrCompression::Rle => run_length_encode(&predict(&pixels)),
+        ExrCompression::Zip => deflate::deflate_bytes_zlib(&predict(&pixels)),
+    };
+    if compressed.len() < pixels.len() {
+        compressed
+    } else {
+        pixels
+    }
+}
+
+/// Splits even and odd bytes into two halves and delta encodes the result,
+/// as both RLE and ZIP compression expect.
+fn predict(pixels: &[u8]) -> Vec<u8> {
+    let half = (pixels.len() + 1) / 2;
+    let mut reordered = vec![0u8; pixels.len()];
+    for (i, byte) in pixels.iter().enumerate() {
+        let idx = if i % 2 == 0 { i / 2 } else { half + i / 2 };
+        reordered[idx] = *byte;
+    }
+    let mut previous = reordered.first().cloned().unwrap_or(0);
+    for byte in reordered.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    reordered
+}
+
+fn run_length_encode(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN_LENGTH: usize = 3;
+    const MAX_RUN_LENGTH: usize = 127;
+    let mut encoded = Vec::with_capacity(data.len());
+    let mut run_start = 0;
+    while run_start < data.len() {
+        let mut run_end = run_start + 1;
+        while run_end < data.len()
+            && data[run_start] == data[run_end]
+            && run_end - run_start <= MAX_RUN_LENGTH
+        {
+            run_end += 1;
+        }
+        if run_end - run_start >= MIN_RUN_LENGTH {
+            encoded.push((run_end - run_start - 1) as u8);
+            encoded.push(data[run_start]);
+        } else {
+            while run_end < data.len()
+                && !(run_end + 2 < data.len()
+                    && data[run_end] == data[run_end + 1]
+                    && data[run_end + 1] == data[run_end + 2])
+                && run_end - run_start < MAX_RUN_LENGTH
+            {
+                run_end += 1;
+            }
+            encoded.push((-((run_end - run_start) as i32)) as u8);
+            encoded.extend_from_slice(&data[run_start..run_end]);
+        }
+        run_start = run_end;
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test_run_length_encoding {
+    use super::*;
+
+    fn decode(data: &[u8]) -> Vec<u8> {
+        let mut decoded = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let count = data[i] as i8;
+            if count < 0 {
+                let n = -(count as i32) as usize;
+                decoded.extend_from_slice(&data[i + 1..i + 1 + n]);
+                i += n + 1;
+            } else {
+                for _ in 0..=count {
+                    decoded.push(data[i + 1]);
+                }
+                i += 2;
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut data = vec![7u8; 300];
+        data.extend((0..200).map(|v| v as u8));
+        data.extend_from_slice(&[1, 1, 2, 2, 2, 3]);
+        assert_eq!(data, decode(&run_length_encode(&data)));
+    }
+
+    #[test]
+    fn compresses_runs() {
+        let data = vec![0u8; 127];
+        assert_eq!(vec![126, 0], run_length_encode(&data));
+    }
+}
@@ -0,0 +1,270 @@
+use color::buffer::*;
+use color::sample::*;
+use image::read::*;
+use image::write::*;
+use std::fs::File;
+use std::io::Write;
+use std::io::{BufRead, BufReader, BufWriter, Read};
+use std::path::Path;
+
+const MIN_RUN_LENGTH: usize = 4;
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+
+/// Writes the averaged `ColorBuffer` as a run length encoded Radiance RGBE
+/// image.
+pub fn save_hdr<'a>(
+    file_name: &'a str,
+    color_buffer: &ColorBuffer,
+) -> Result<(), WriteImageFileErr> {
+    let path = Path::new(file_name);
+    let file = File::create(path)?;
+    let ref mut w = BufWriter::new(file);
+    write_hdr(w, color_buffer)?;
+    w.flush()?;
+
+    Ok(())
+}
+
+pub fn write_hdr<W: Write>(w: &mut W, color_buffer: &ColorBuffer) -> Result<(), WriteImageFileErr> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        color_buffer.imgy, color_buffer.imgx
+    )?;
+    for y in 0..color_buffer.imgy {
+        let scanline: Vec<[u8; 4]> = (0..color_buffer.imgx)
+            .map(|x| to_rgbe(color_buffer.pixel(x, y)))
+            .collect();
+        if scanline.len() < MIN_RLE_WIDTH || scanline.len() > MAX_RLE_WIDTH {
+            for rgbe in scanline.iter() {
+                w.write_all(rgbe)?;
+            }
+            continue;
+        }
+        let width = scanline.len();
+        w.write_all(&[2, 2, (width >> 8) as u8, (width & 0xff) as u8])?;
+        for component in 0..4 {
+            let bytes: Vec<u8> = scanline.iter().map(|rgbe| rgbe[component]).collect();
+            w.write_all(&run_length_encode(&bytes))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a Radiance RGBE image stored top to bottom, with or without run
+/// length encoded scanlines.
+pub fn load_hdr<'a>(file_name: &'a str) -> Result<ColorBuffer, ReadImageFileErr> {
+    let file = File::open(Path::new(file_name))?;
+    read_hdr(&mut BufReader::new(file))
+}
+
+pub fn read_hdr<R: BufRead>(r: &mut R) -> Result<ColorBuffer, ReadImageFileErr> {
+    let format_err = |msg: &str| ReadImageFileErr::Format(msg.to_string());
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    if !line.starts_with("#?") {
+        return Err(format_err("not a Radiance file"));
+    }
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 {
+            return Err(format_err("missing resolution"));
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(ReadImageFileErr::Format(line.to_string()));
+        }
+    }
+    line.clear();
+    r.read_line(&mut line)?;
+    let (imgx, imgy) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["-Y", height, "+X", width] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => (width, height),
+            _ => return Err(ReadImageFileErr::Format(line.trim().to_string())),
+        },
+        _ => return Err(ReadImageFileErr::Format(line.trim().to_string())),
+    };
+    if imgx * imgy > 1 << 28 {
+        return Err(format_err("image too large"));
+    }
+    let mut color_buffer = ColorBuffer::new(imgx, imgy);
+    let mut scanline = vec![[0u8; 4]; imgx];
+    for y in 0..imgy {
+        read_scanline(r, &mut scanline)?;
+        for (x, rgbe) in scanline.iter().enumerate() {
+            color_buffer.add_color(x, y, from_rgbe(*rgbe));
+        }
+    }
+    Ok(color_buffer)
+}
+
+fn read_scanline<R: Read>(r: &mut R, scanline: &mut [[u8; 4]]) -> Result<(), ReadImageFileErr> {
+    let width = scanline.len();
+    if width == 0 {
+        return Ok(());
+    }
+    r.read_exact(&mut scanline[0])?;
+    let first = scanline[0];
+    let rle = first[0] == 2 && first[1] == 2 && first[2] < 128;
+    if !rle || width < MIN_RLE_WIDTH || width > MAX_RLE_WIDTH {
+        for rgbe in scanline[1..].iter_mut() {
+            r.read_exact(rgbe)?;
+        }
+        return Ok(());
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != width {
+        return Err(ReadImageFileErr::Format(
+            "scanline width mismatch".to_string(),
+        ));
+    }
+    let mut byte = [0u8];
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            r.read_exact(&mut byte)?;
+            let (count, run) = match byte[0] {
+                count if count > 128 => (count as usize - 128, true),
+                count => (count as usize, false),
+            };
+            if count == 0 || x + count > width {
+                return Err(ReadImageFileErr::Format("bad scanline run".to_string()));
+            }
+            if run {
+                r.read_exact(&mut byte)?;
+            }
+            for rgbe in scanline[x..x + count].iter_mut() {
+                if !run {
+                    r.read_exact(&mut byte)?;
+                }
+                rgbe[component] = byte[0];
+            }
+            x += count;
+        }
+    }
+    Ok(())
+}
+
+fn from_rgbe(rgbe: [u8; 4]) -> ColorSample {
+    if rgbe[3] == 0 {
+        return ColorSample::BLACK;
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    ColorSample {
+        red: (rgbe[0] as f64 + 0.5) * scale,
+        green: (rgbe[1] as f64 + 0.5) * scale,
+        blue: (rgbe[2] as f64 + 0.5) * scale,
+    }
+}
+
+/// Shared exponent encoding. Channels are clamped to zero, since RGBE can't
+/// store negative values.
+pub fn to_rgbe(color: ColorSample) -> [u8; 4] {
+    let red = color.red.max(0.0);
+    let green = color.green.max(0.0);
+    let blue = color.blue.max(0.0);
+    let max = red.max(green).max(blue);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    let mut exponent = max.log2().floor() as i32 + 1;
+    // log2 may land one off for values close to a power of two
+    if max / 2f64.powi(exponent) >= 1.0 {
+        exponent += 1;
+    } else if max / 2f64.powi(exponent) < 0.5 {
+        exponent -= 1;
+    }
+    let scale = 256.0 / 2f64.powi(exponent);
+    [
+        (red * scale) as u8,
+        (green * scale) as u8,
+        (blue * scale) as u8,
+        (exponent + 128).max(0).min(255) as u8,
+    ]
+}
+
+fn run_length_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(bytes.len());
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut run_end = i + 1;
+        while run_end < bytes.len() && bytes[run_end] == bytes[i] && run_end - i < 127 {
+            run_end += 1;
+        }
+        if run_end - i >= MIN_RUN_LENGTH {
+            for literal in bytes[literal_start..i].chunks(128) {
+                encoded.push(literal.len() as u8);
+                encoded.extend_from_slice(literal);
+            }
+            encoded.push((128 + run_end - i) as u8);
+            encoded.push(bytes[i]);
+            i = run_end;
+            literal_start = i;
+        } else {
+            i += 1;
+        }
+    }
+    for literal in bytes[literal_start..].chunks(128) {
+        encoded.push(literal.len() as u8);
+        encoded.extend_from_slice(literal);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test_rgbe {
+    use super::*;
+
+    #[test]
+    fn black_has_zero_exponent() {
+        assert_eq!([0, 0, 0, 0], to_rgbe(ColorSample::BLACK));
+    }
+
+    #[test]
+    fn largest_channel_keeps_mantissa_precision() {
+        for &(v, exponent) in [(1.0, 129), (0.5, 128), (3.0, 130), (1000.0, 138)].iter() {
+            let rgbe = to_rgbe(ColorSample {
+                red: v,
+                green: v / 2.0,
+                blue: 0.0,
+            });
+            assert!(rgbe[0] >= 128, "{} {:?}", v, rgbe);
+            assert_eq!(exponent, rgbe[3], "{} {:?}", v, rgbe);
+            let decoded = (rgbe[0] as f64 + 0.5) * 2f64.powi(rgbe[3] as i32 - 136);
+            assert!((decoded - v).abs() / v < 1.0 / 128.0, "{} {}", v, decoded);
+        }
+    }
+
+    #[test]
+    fn reads_what_it_writes() {
+        let mut color_buffer = ColorBuffer::new(9, 2);
+        for x in 0..9 {
+            let v = if x < 5 { 2.0 } else { x as f64 };
+            color_buffer.add_color(
+                x,
+                1,
+                ColorSample {
+                    red: v,
+                    green: 0.25,
+                    blue: 0.0,
+                },
+            );
+        }
+        let mut bytes = Vec::new();
+        write_hdr(&mut bytes, &color_buffer).unwrap();
+        let loaded = read_hdr(&mut bytes.as_slice()).unwrap();
+        assert_eq!((9, 2), (loaded.imgx, loaded.imgy));
+        for x in 0..9 {
+            let (a, b) = (color_buffer.pixel(x, 1), loaded.pixel(x, 1));
+            // channels share the precision of the largest one
+            assert!((a.red - b.red).abs() <= a.red / 128.0);
+            assert!((a.green - b.green).abs() <= a.red / 128.0);
+            assert!(b.blue <= a.red / 128.0);
+        }
+    }
+}
@@ -1,3 +1,9 @@
 pub mod buffer;
+pub mod color_space;
 pub mod convert;
+pub mod exr;
+pub mod hdr;
+pub mod pfm;
+pub mod read;
+pub mod tone_map;
 pub mod write;
@@ -0,0 +1,30 @@
+use color::buffer::*;
+use image::write::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+/// Writes the averaged `ColorBuffer` as a little endian Portable FloatMap.
+/// PFM stores scanlines bottom to top.
+pub fn save_pfm<'a>(
+    file_name: &'a str,
+    color_buffer: &ColorBuffer,
+) -> Result<(), WriteImageFileErr> {
+    let path = Path::new(file_name);
+    let file = File::create(path)?;
+    let ref mut w = BufWriter::new(file);
+    // a negative scale marks little endian data
+    write!(w, "PF\n{} {}\n-1.0\n", color_buffer.imgx, color_buffer.imgy)?;
+    for y in (0..color_buffer.imgy).rev() {
+        for x in 0..color_buffer.imgx {
+            let color = color_buffer.pixel(x, y);
+            for c in [color.red, color.green, color.blue].iter() {
+                w.write_all(&(*c as f32).to_bits().to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()?;
+
+    Ok(())
+}
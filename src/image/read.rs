@@ -0,0 +1,116 @@
+use color::buffer::*;
+use color::sample::*;
+use image::hdr::*;
+use png;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ReadImageFileErr {
+    File(io::Error),
+    Format(String),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for ReadImageFileErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadImageFileErr::File(ref err) => write!(f, "File error: {}", err),
+            ReadImageFileErr::Format(ref msg) => write!(f, "Invalid image: {}", msg),
+            ReadImageFileErr::UnsupportedFormat(ref file_name) => {
+                write!(f, "Unsupported image format: {}", file_name)
+            }
+        }
+    }
+}
+
+impl error::Error for ReadImageFileErr {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            ReadImageFileErr::File(ref err) => Some(err),
+            ReadImageFileErr::Format(_) => None,
+            ReadImageFileErr::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadImageFileErr {
+    fn from(err: io::Error) -> ReadImageFileErr {
+        ReadImageFileErr::File(err)
+    }
+}
+
+impl From<png::DecodingError> for ReadImageFileErr {
+    fn from(err: png::DecodingError) -> ReadImageFileErr {
+        match err {
+            png::DecodingError::IoError(err) => ReadImageFileErr::File(err),
+            err => ReadImageFileErr::Format(err.to_string()),
+        }
+    }
+}
+
+/// Reads a linear image in the format matching the file name's extension.
+/// Only hdr is supported. Every pixel of the result holds one sample.
+pub fn load_color_buffer<'a>(file_name: &'a str) -> Result<ColorBuffer, ReadImageFileErr> {
+    match extension(file_name).as_ref().map(|e| e.as_str()) {
+        Some("hdr") => load_hdr(file_name),
+        _ => Err(ReadImageFileErr::UnsupportedFormat(file_name.to_string())),
+    }
+}
+
+/// Reads a data image such as a normal map, hdr or png. Png values are taken
+/// as stored rather than as sRGB colors.
+pub fn load_data_buffer<'a>(file_name: &'a str) -> Result<ColorBuffer, ReadImageFileErr> {
+    match extension(file_name).as_ref().map(|e| e.as_str()) {
+        Some("png") => load_png(file_name),
+        _ => load_color_buffer(file_name),
+    }
+}
+
+fn extension(file_name: &str) -> Option<String> {
+    Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+}
+
+/// Reads a png file as stored, with values from zero to one and without
+/// decoding its transfer function, for data such as normal maps.
+fn load_png<'a>(file_name: &'a str) -> Result<ColorBuffer, ReadImageFileErr> {
+    let decoder = png::Decoder::new(File::open(Path::new(file_name))?);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut bytes = vec![0; info.buffer_size()];
+    reader.next_frame(&mut bytes)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    // palettes and low bit depths are expanded to eight bits
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::RGB => 3,
+        png::ColorType::RGBA => 4,
+        png::ColorType::Indexed => {
+            return Err(ReadImageFileErr::Format("unexpanded palette".to_string()))
+        }
+    };
+    let mut buffer = ColorBuffer::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = &bytes[y * info.line_size + x * channels..];
+            let value = |i: usize| pixel[i] as SamplePrecision / 255.0;
+            let color = if channels < 3 {
+                ColorSample::WHITE * value(0)
+            } else {
+                ColorSample {
+                    red: value(0),
+                    green: value(1),
+                    blue: value(2),
+                }
+            };
+            buffer.add_color(x, y, color);
+        }
+    }
+    Ok(buffer)
+}
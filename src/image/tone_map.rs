@@ -0,0 +1,196 @@
+use color::sample::*;
+use image::color_space::*;
+
+/// Compresses linear radiance into the [0, 1] range that gets quantized for
+/// low dynamic range output.
+pub trait ToneMap {
+    fn tone_map(&self, color: ColorSample) -> ColorSample;
+}
+
+pub type ToneMapSS = ToneMap + Send + Sync;
+
+/// Leaves colors untouched; quantization clamps anything above one.
+pub struct Clamp;
+
+/// Reinhard's global operator applied to luminance, which keeps hues intact.
+pub struct Reinhard;
+
+/// Reinhard operator where luminance at or above the white point maps to one.
+pub struct ExtendedReinhard {
+    pub white_point: SamplePrecision,
+}
+
+/// John Hable's filmic curve from Uncharted 2.
+pub struct Hable {
+    pub white_point: SamplePrecision,
+}
+
+/// Stephen Hill's fit of the ACES reference rendering and sRGB output
+/// transforms.
+pub struct AcesFitted;
+
+/// Scene linear radiance is multiplied by 2^ev before tone mapping, then
+/// converted to the output primaries.
+pub struct DisplayTransform {
+    pub exposure: SamplePrecision,
+    pub primaries: OutputPrimaries,
+    pub tone_map: Box<ToneMapSS>,
+}
+
+impl DisplayTransform {
+    pub fn new() -> DisplayTransform {
+        DisplayTransform {
+            exposure: 0.0,
+            primaries: OutputPrimaries::Rec709,
+            tone_map: Box::new(Clamp),
+        }
+    }
+
+    pub fn apply(&self, color: ColorSample) -> ColorSample {
+        let color = self.tone_map.tone_map(color * self.exposure.exp2());
+        self.primaries.from_working_space(color)
+    }
+}
+
+/// Looks up a tone mapping operator by its command line name.
+pub fn tone_map_by_name(name: &str) -> Option<Box<ToneMapSS>> {
+    match name {
+        "clamp" => Some(Box::new(Clamp)),
+        "reinhard" => Some(Box::new(Reinhard)),
+        "extended-reinhard" => Some(Box::new(ExtendedReinhard { white_point: 4.0 })),
+        "hable" => Some(Box::new(Hable { white_point: 11.2 })),
+        "aces" => Some(Box::new(AcesFitted)),
+        _ => None,
+    }
+}
+
+fn scale_luminance(
+    color: ColorSample,
+    curve: &Fn(SamplePrecision) -> SamplePrecision,
+) -> ColorSample {
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return ColorSample::BLACK;
+    }
+    color * (curve(luminance) / luminance)
+}
+
+fn map_channels(color: ColorSample, curve: &Fn(SamplePrecision) -> SamplePrecision) -> ColorSample {
+    ColorSample {
+        red: curve(color.red),
+        green: curve(color.green),
+        blue: curve(color.blue),
+    }
+}
+
+impl ToneMap for Clamp {
+    fn tone_map(&self, color: ColorSample) -> ColorSample {
+        color
+    }
+}
+
+impl ToneMap for Reinhard {
+    fn tone_map(&self, color: ColorSample) -> ColorSample {
+        scale_luminance(color, &|l| l / (1.0 + l))
+    }
+}
+
+impl ToneMap for ExtendedReinhard {
+    fn tone_map(&self, color: ColorSample) -> ColorSample {
+        let white_squared = self.white_point * self.white_point;
+        scale_luminance(color, &|l| l * (1.0 + l / white_squared) / (1.0 + l))
+    }
+}
+
+impl Hable {
+    const EXPOSURE_BIAS: SamplePrecision = 2.0;
+
+    fn curve(x: SamplePrecision) -> SamplePrecision {
+        const A: SamplePrecision = 0.15;
+        const B: SamplePrecision = 0.50;
+        const C: SamplePrecision = 0.10;
+        const D: SamplePrecision = 0.20;
+        const E: SamplePrecision = 0.02;
+        const F: SamplePrecision = 0.30;
+        ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+    }
+}
+
+impl ToneMap for Hable {
+    fn tone_map(&self, color: ColorSample) -> ColorSample {
+        let white_scale = Hable::curve(self.white_point).recip();
+        map_channels(color, &|c| {
+            Hable::curve(Hable::EXPOSURE_BIAS * c.max(0.0)) * white_scale
+        })
+    }
+}
+
+impl ToneMap for AcesFitted {
+    fn tone_map(&self, color: ColorSample) -> ColorSample {
+        let input = ColorSample {
+            red: 0.59719 * color.red + 0.35458 * color.green + 0.04823 * color.blue,
+            green: 0.07600 * color.red + 0.90834 * color.green + 0.01566 * color.blue,
+            blue: 0.02840 * color.red + 0.13383 * color.green + 0.83777 * color.blue,
+        };
+        let fitted = map_channels(input, &|v| {
+            let v = v.max(0.0);
+            (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081)
+        });
+        ColorSample {
+            red: 1.60475 * fitted.red - 0.53108 * fitted.green - 0.07367 * fitted.blue,
+            green: -0.10208 * fitted.red + 1.10813 * fitted.green - 0.00605 * fitted.blue,
+            blue: -0.00327 * fitted.red - 0.07276 * fitted.green + 1.07602 * fitted.blue,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_tone_maps {
+    use super::*;
+
+    fn grey(v: SamplePrecision) -> ColorSample {
+        ColorSample {
+            red: v,
+            green: v,
+            blue: v,
+        }
+    }
+
+    #[test]
+    fn curves_are_monotonic_and_bounded_below_white() {
+        for name in ["reinhard", "extended-reinhard", "hable", "aces"].iter() {
+            let tone_map = tone_map_by_name(name).unwrap();
+            let mut previous = -1.0;
+            for i in 0..=80 {
+                let v = tone_map.tone_map(grey(i as SamplePrecision * 0.05)).green;
+                assert!(v >= previous, "{} {} {}", name, i, v);
+                assert!(v <= 1.0 + 1e-3, "{} {} {}", name, i, v);
+                previous = v;
+            }
+        }
+    }
+
+    #[test]
+    fn white_point_maps_to_one() {
+        let reinhard = ExtendedReinhard { white_point: 4.0 };
+        assert!((reinhard.tone_map(grey(4.0)).red - 1.0).abs() < 1e-9);
+        let hable = Hable { white_point: 11.2 };
+        let v = hable.tone_map(grey(11.2 / Hable::EXPOSURE_BIAS)).red;
+        assert!((v - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for name in ["clamp", "reinhard", "extended-reinhard", "hable", "aces"].iter() {
+            let v = tone_map_by_name(name).unwrap().tone_map(ColorSample::BLACK);
+            assert!(v.red.abs() < 1e-3, "{} {:?}", name, v);
+        }
+    }
+
+    #[test]
+    fn exposure_doubles_per_stop() {
+        let mut display = DisplayTransform::new();
+        display.exposure = 2.0;
+        assert_eq!(2.0, display.apply(grey(0.5)).blue);
+    }
+}
@@ -1,4 +1,10 @@
+use color::buffer::*;
 use image::buffer::*;
+use image::color_space::*;
+use image::exr::*;
+use image::hdr::*;
+use image::pfm::*;
+use image::tone_map::*;
 use png;
 use png::HasParameters;
 use std::error;
@@ -12,6 +18,7 @@ use std::path::Path;
 pub enum WriteImageFileErr {
     File(io::Error),
     Encoding(png::EncodingError),
+    UnsupportedFormat(String),
 }
 
 impl fmt::Display for WriteImageFileErr {
@@ -19,6 +26,9 @@ impl fmt::Display for WriteImageFileErr {
         match *self {
             WriteImageFileErr::File(ref err) => write!(f, "File error: {}", err),
             WriteImageFileErr::Encoding(ref err) => write!(f, "Encoding error: {}", err),
+            WriteImageFileErr::UnsupportedFormat(ref file_name) => {
+                write!(f, "Unsupported image format: {}", file_name)
+            }
         }
     }
 }
@@ -28,6 +38,7 @@ impl error::Error for WriteImageFileErr {
         match *self {
             WriteImageFileErr::File(ref err) => Some(err),
             WriteImageFileErr::Encoding(ref err) => Some(err),
+            WriteImageFileErr::UnsupportedFormat(_) => None,
         }
     }
 }
@@ -53,7 +64,59 @@ pub fn save_image<'a>(file_name: &'a str, buffer: &ImageBuffer) -> Result<(), Wr
     encoder.set(png::ColorType::RGB).set(buffer.bit_depth);
     let mut writer = encoder.write_header()?;
 
+    // color space chunks have to precede the image data
+    if buffer.primaries == OutputPrimaries::Rec709 {
+        // perceptual rendering intent
+        writer.write_chunk(*b"sRGB", &[0])?;
+    }
+    // coding points that readers supporting them prefer over the rest: full
+    // range RGB
+    let (primaries, transfer) = buffer.primaries.coding_points();
+    writer.write_chunk(*b"cICP", &[primaries, transfer, 0, 1])?;
+    writer.write_chunk(
+        *b"gAMA",
+        &png_fixed_point(buffer.primaries.gamma()).to_be_bytes(),
+    )?;
+    let mut chromaticities = Vec::with_capacity(32);
+    for &(x, y) in buffer.primaries.chromaticities().iter() {
+        chromaticities.extend_from_slice(&png_fixed_point(x).to_be_bytes());
+        chromaticities.extend_from_slice(&png_fixed_point(y).to_be_bytes());
+    }
+    writer.write_chunk(*b"cHRM", &chromaticities)?;
+
     writer.write_image_data(&buffer.buffer)?;
 
     Ok(())
 }
+
+/// PNG stores fractions as integers scaled by 100000.
+fn png_fixed_point(value: f64) -> u32 {
+    (value * 100_000.0).round() as u32
+}
+
+/// Writes the averaged `ColorBuffer` in the format matching the file name's
+/// extension: png, exr, hdr or pfm. Only png output goes through the display
+/// transform; the other formats keep linear radiance, EXR stored as `exr`
+/// says.
+pub fn save_color_buffer<'a>(
+    file_name: &'a str,
+    color_buffer: &ColorBuffer,
+    display: &DisplayTransform,
+    exr: ExrFormat,
+) -> Result<(), WriteImageFileErr> {
+    let extension = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match extension.as_ref().map(|e| e.as_str()) {
+        Some("png") => {
+            let image_buffer =
+                ImageBuffer::from_color_buffer(color_buffer, BytesPerColor::Two, display);
+            save_image(file_name, &image_buffer)
+        }
+        Some("exr") => save_exr(file_name, color_buffer, exr.pixel_type, exr.compression),
+        Some("hdr") => save_hdr(file_name, color_buffer),
+        Some("pfm") => save_pfm(file_name, color_buffer),
+        _ => Err(WriteImageFileErr::UnsupportedFormat(file_name.to_string())),
+    }
+}
@@ -0,0 +1,41 @@
+use color::sample::*;
+use geometry::vec3::*;
+use light::light::*;
+
+/// Parallel light from infinitely far away, like a sun of no size.
+pub struct DirectionalLight {
+    /// Unit vector pointing the way the light travels.
+    pub direction: Vec3,
+    /// Irradiance in W/m² on a surface facing the light.
+    pub irradiance: ColorSample,
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: MAX_DIMENSION,
+            radiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_directional_light {
+    use super::*;
+
+    #[test]
+    fn lights_every_point_from_the_same_direction() {
+        let light = DirectionalLight {
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            irradiance: ColorSample::WHITE * 3.0,
+        };
+        for point in [Vec3::ZERO, Vec3::new(100.0, -50.0, 7.0)].iter() {
+            let sample = light.sample(point).unwrap();
+            assert_eq!(Vec3::new(0.0, 1.0, 0.0), sample.direction);
+            // shadow rays reach past every object
+            assert_eq!(MAX_DIMENSION, sample.distance);
+            assert_eq!(3.0, sample.radiance.red);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use color::sample::*;
+use color::spectrum::*;
+use geometry::vec3::*;
+
+pub struct LightSample {
+    /// Unit vector from the shaded point towards the light.
+    pub direction: Vec3,
+    /// How far shadow rays have to reach to get to the light.
+    pub distance: Dimension,
+    /// Incoming radiance divided by the probability density of sampling
+    /// `direction`.
+    pub radiance: ColorSample,
+}
+
+/// Light that can be sampled directly from a shaded point instead of waiting
+/// for scattered rays to find it.
+pub trait Light {
+    fn sample(&self, point: &Vec3) -> Option<LightSample>;
+
+    /// Radiance seen by rays escaping the scene in `direction`. Only lights
+    /// at infinity can be seen this way.
+    fn emitted(&self, _direction: &Vec3) -> ColorSample {
+        ColorSample::BLACK
+    }
+
+    /// Spectrum of the emitted light in spectral mode. None for lights whose
+    /// color is upsampled as if lit by the working space white.
+    fn spectrum(&self) -> Option<&IlluminantSpectrum> {
+        None
+    }
+}
+
+pub type LightSS = Light + Send + Sync;
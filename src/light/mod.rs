@@ -0,0 +1,6 @@
+pub mod directional;
+pub mod light;
+pub mod point;
+pub mod spectral;
+pub mod spot;
+pub mod sun;
@@ -0,0 +1,46 @@
+use color::sample::*;
+use geometry::vec3::*;
+use light::light::*;
+
+/// Light leaving a single point equally in all directions.
+pub struct PointLight {
+    pub position: Vec3,
+    /// Radiant intensity in W/sr.
+    pub intensity: ColorSample,
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        if distance == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction: to_light / distance,
+            distance: distance,
+            radiance: self.intensity * (distance * distance).recip(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_point_light {
+    use super::*;
+
+    #[test]
+    fn falls_off_with_the_squared_distance() {
+        let light = PointLight {
+            position: Vec3::new(0.0, 2.0, 0.0),
+            intensity: ColorSample::WHITE,
+        };
+        let near = light.sample(&Vec3::ZERO).unwrap();
+        assert_eq!(Vec3::new(0.0, 1.0, 0.0), near.direction);
+        assert_eq!(2.0, near.distance);
+        assert_eq!(0.25, near.radiance.red);
+        let far = light.sample(&Vec3::new(0.0, -2.0, 0.0)).unwrap();
+        assert_eq!(4.0, far.distance);
+        assert_eq!(0.0625, far.radiance.red);
+        assert!(light.sample(&light.position).is_none());
+    }
+}
@@ -0,0 +1,29 @@
+use color::sample::*;
+use color::spectrum::*;
+use geometry::vec3::*;
+use light::light::*;
+use std::sync::Arc;
+
+/// A light emitting the spectrum of a standard illuminant. Its color is
+/// tinted by the illuminant's color when rendering in RGB.
+pub struct SpectralLight {
+    pub light: Arc<LightSS>,
+    pub spectrum: IlluminantSpectrum,
+}
+
+impl Light for SpectralLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        self.light.sample(point).map(|sample| LightSample {
+            radiance: sample.radiance * self.spectrum.color(),
+            ..sample
+        })
+    }
+
+    fn emitted(&self, direction: &Vec3) -> ColorSample {
+        self.light.emitted(direction) * self.spectrum.color()
+    }
+
+    fn spectrum(&self) -> Option<&IlluminantSpectrum> {
+        Some(&self.spectrum)
+    }
+}
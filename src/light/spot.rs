@@ -0,0 +1,90 @@
+use color::sample::*;
+use geometry::vec3::*;
+use light::light::*;
+
+/// A point light shining into a cone. The intensity is full inside
+/// `inner_angle` of the axis and falls off smoothly to zero at `outer_angle`.
+pub struct SpotLight {
+    position: Vec3,
+    direction: Vec3,
+    intensity: ColorSample,
+    cos_inner: Dimension,
+    cos_outer: Dimension,
+}
+
+impl SpotLight {
+    /// `intensity` is the radiant intensity along the axis in W/sr. Angles
+    /// are in radians from the axis.
+    pub fn new(
+        position: Vec3,
+        direction: Vec3,
+        intensity: ColorSample,
+        inner_angle: Dimension,
+        outer_angle: Dimension,
+    ) -> SpotLight {
+        let outer_angle = outer_angle.max(inner_angle);
+        SpotLight {
+            position: position,
+            direction: direction.unit(),
+            intensity: intensity,
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+        }
+    }
+
+    fn falloff(&self, cos_angle: Dimension) -> Dimension {
+        if cos_angle >= self.cos_inner {
+            1.0
+        } else if cos_angle <= self.cos_outer {
+            0.0
+        } else {
+            let t = (cos_angle - self.cos_outer) / (self.cos_inner - self.cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: &Vec3) -> Option<LightSample> {
+        let to_light = self.position - *point;
+        let distance = to_light.length();
+        if distance == 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff == 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction: direction,
+            distance: distance,
+            radiance: self.intensity * (falloff / (distance * distance)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_spot_light {
+    use super::*;
+
+    #[test]
+    fn falls_off_between_the_cone_angles() {
+        let light = SpotLight::new(
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            ColorSample::WHITE,
+            0.2,
+            0.4,
+        );
+        let irradiance = |x: Dimension| {
+            light
+                .sample(&Vec3::new(x, 0.0, 0.0))
+                .map_or(0.0, |sample| sample.radiance.red)
+        };
+        assert_eq!(0.25, irradiance(0.0));
+        let x = 2.0 * (0.3 as Dimension).tan();
+        assert!(irradiance(x) > 0.0 && irradiance(x) < 1.0 / (4.0 + x * x));
+        assert_eq!(0.0, irradiance(2.0));
+    }
+}
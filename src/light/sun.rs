@@ -0,0 +1,57 @@
+use color::sample::*;
+use geometry::frame::*;
+use geometry::vec3::*;
+use light::light::*;
+use rand::{thread_rng, Rng};
+
+/// Angular radius of the sun seen from earth, in radians.
+pub const SUN_ANGULAR_RADIUS: Dimension = 0.004_65;
+
+/// A distant disc of uniform radiance, like the sun.
+pub struct Sun {
+    direction: Vec3,
+    radiance: ColorSample,
+    cos_angular_radius: Dimension,
+}
+
+impl Sun {
+    /// A sun in `direction` giving `irradiance` on a surface facing it.
+    pub fn new(direction: Vec3, irradiance: ColorSample, angular_radius: Dimension) -> Sun {
+        let cos_angular_radius = angular_radius.cos();
+        let solid_angle = 2.0 * PI_DIMENSION * (1.0 - cos_angular_radius);
+        Sun {
+            direction: direction.unit(),
+            radiance: irradiance * solid_angle.recip(),
+            cos_angular_radius: cos_angular_radius,
+        }
+    }
+
+    fn solid_angle(&self) -> Dimension {
+        2.0 * PI_DIMENSION * (1.0 - self.cos_angular_radius)
+    }
+}
+
+impl Light for Sun {
+    /// Samples the disc uniformly by solid angle.
+    fn sample(&self, _point: &Vec3) -> Option<LightSample> {
+        let mut rng = thread_rng();
+        let cos_theta =
+            1.0 - rng.gen_range::<Dimension>(0.0, 1.0) * (1.0 - self.cos_angular_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI_DIMENSION * rng.gen_range::<Dimension>(0.0, 1.0);
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Some(LightSample {
+            direction: Frame::from_w(self.direction).to_world(local),
+            distance: MAX_DIMENSION,
+            radiance: self.radiance * self.solid_angle(),
+        })
+    }
+
+    fn emitted(&self, direction: &Vec3) -> ColorSample {
+        if direction.unit().dot(self.direction) >= self.cos_angular_radius {
+            self.radiance
+        } else {
+            ColorSample::BLACK
+        }
+    }
+}
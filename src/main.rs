@@ -1,279 +1,346 @@
 #![feature(int_to_from_bytes)]
+extern crate deflate;
 extern crate png;
 extern crate rand;
 
 mod camera;
 mod color;
+mod distributed;
+mod distribution;
+mod environment;
 mod float_cmp;
 mod geometry;
 mod hit_detection;
 mod image;
+mod light;
+mod medium;
+mod render;
+mod scene;
+mod settings;
 mod surface;
 mod world;
 
-use camera::*;
+use color::aov::*;
 use color::buffer::*;
-use color::sample::*;
-use geometry::ray::*;
-use geometry::vec3::*;
-use hit_detection::sphere::*;
-use image::buffer::*;
+use color::checkpoint::*;
+use color::denoise::*;
+use color::outliers::*;
+use distributed::coordinator::*;
+use distributed::protocol::*;
+use distributed::worker::*;
+use image::tone_map::*;
 use image::write::*;
 use rand::{thread_rng, Rng};
+use render::*;
+use scene::*;
+use settings::*;
+use std::error;
 use std::io::{self, BufRead};
+use std::net::TcpListener;
+use std::process;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::*;
 use std::sync::Arc;
-use std::time::Duration;
-use surface::dielectric::*;
-use surface::lambertian::*;
-use surface::material::*;
-use surface::metal::*;
-use world::bvh::*;
-use world::entity::*;
-use world::model::*;
+use std::time::{Duration, Instant};
 
-fn color(ray: Ray, scene: &Arc<ModelSS>) -> ColorSample {
-    let mut attenuation = ColorSample::WHITE;
-    let mut new_ray = ray;
-    for _depth in 0..50 {
-        if let Some(hit) = scene.hit_model(&new_ray, 1e-3, MAX_DIMENSION) {
-            if let Some(scatter_result) =
-                hit.material
-                    .scatter(&new_ray, &hit.hit_record.p, &hit.hit_record.normal)
-            {
-                attenuation *= scatter_result.attenuation;
-                new_ray = scatter_result.scattered;
-                continue;
-            } else {
+/// Spawns threads rendering `n_samples` full image passes, each sent back as
+/// its own buffer.
+fn spawn_render(
+    scene: &Arc<Scene>,
+    imgx: usize,
+    imgy: usize,
+    n_samples: usize,
+    n_threads: usize,
+    clamp: Option<SampleClamp>,
+) -> Receiver<ColorBuffer> {
+    let (tx, rx) = sync_channel::<ColorBuffer>(n_threads * 3);
+    let ct = Arc::new(AtomicUsize::new(0));
+    for _ in 0..n_threads {
+        let ct = ct.clone();
+        let tx = tx.clone();
+        let scene = scene.clone();
+        std::thread::spawn(move || loop {
+            let s = ct.fetch_add(1, Ordering::SeqCst);
+            if s >= n_samples {
                 break;
             }
-        } else {
-            const LIGHT_BLUE: ColorSample = ColorSample {
-                red: 0.5,
-                green: 0.7,
-                blue: 1.0,
+            let tile = Tile {
+                x: 0,
+                y: 0,
+                width: imgx,
+                height: imgy,
             };
-            let y = ray.direction.unit().y;
-            let t = 0.5 * (y + 1.0);
-            let col = (1.0 - t) * ColorSample::WHITE + t * LIGHT_BLUE;
-            return attenuation * col;
-        }
+            let color_buffer = render_tile(&scene, imgx, imgy, &tile, 1, clamp);
+            tx.send(color_buffer).unwrap();
+        });
     }
-    ColorSample::BLACK
+    rx
 }
 
-fn random_scene(imgx: usize, imgy: usize) -> (Arc<ModelSS>, Camera) {
-    let mut rng = thread_rng();
-    let look_from = Vec3::new(20.0, 1.9, 5.0);
-    let look_at = Vec3::new(0.0, 0.5, 0.0);
-    let up = Vec3::new(0.0, 1.0, 0.0);
-    let vert_fov_degrees = 10.0;
-    let aspect = imgx as Dimension / imgy as Dimension;
-    let aperture = 0.04;
-    let distance_to_focus = (look_from - look_at).length() - 2.0;
-    let camera = Camera::new(
-        look_from,
-        look_at,
-        up,
-        vert_fov_degrees,
-        aspect,
-        aperture,
-        distance_to_focus,
-    );
-    let mut spheres: Vec<Sphere> = Vec::new();
-    let mut center_spheres: Vec<Box<ModelSS>> = Vec::new();
-    // floor
-    let sphere = Sphere {
-        center: Vec3::new(0.0, -1e12, 0.0),
-        radius: 1e12,
-    };
-    spheres.push(sphere);
-    let floor = Box::new(WorldEntity {
-        shape: Box::new(sphere),
-        material: Arc::new(Lambertian {
-            albedo: ColorSample {
-                red: 0.5,
-                green: 0.5,
-                blue: 0.5,
-            },
-        }),
-    });
-    // dielectric
-    let sphere = Sphere {
-        center: Vec3::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-    };
-    spheres.push(sphere);
-    center_spheres.push(Box::new(WorldEntity {
-        shape: Box::new(sphere),
-        material: Arc::new(Dielectric { ref_idx: 1.5 }),
-    }));
-    // lambertian
-    let sphere = Sphere {
-        center: Vec3::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-    };
-    spheres.push(sphere);
-    center_spheres.push(Box::new(WorldEntity {
-        shape: Box::new(sphere),
-        material: Arc::new(Lambertian {
-            albedo: ColorSample {
-                red: 0.4,
-                green: 0.2,
-                blue: 0.1,
-            },
-        }),
-    }));
-    // metal
-    let sphere = Sphere {
-        center: Vec3::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-    };
-    spheres.push(sphere);
-    center_spheres.push(Box::new(WorldEntity {
-        shape: Box::new(sphere),
-        material: Arc::new(Metal::new(
-            ColorSample {
-                red: 0.7,
-                green: 0.6,
-                blue: 0.5,
-            },
-            0.0,
-        )),
-    }));
-    // random sphere field
-    let mut sphere_field: Vec<Box<ModelSS>> = Vec::new();
-    for a in -11..=11 {
-        for b in -11..=11 {
-            let material: Arc<MaterialSS> = match rng.gen_range::<Dimension>(0.0, 1.0) {
-                v if v < 0.8 => Arc::new(Lambertian {
-                    albedo: ColorSample {
-                        red: rng.gen_range::<Dimension>(0.0, 1.0)
-                            * rng.gen_range::<Dimension>(0.0, 1.0),
-                        green: rng.gen_range::<Dimension>(0.0, 1.0)
-                            * rng.gen_range::<Dimension>(0.0, 1.0),
-                        blue: rng.gen_range::<Dimension>(0.0, 1.0)
-                            * rng.gen_range::<Dimension>(0.0, 1.0),
-                    },
-                }),
-                v if v < 0.95 => Arc::new(Metal::new(
-                    ColorSample {
-                        red: rng.gen_range::<Dimension>(0.5, 1.0),
-                        green: rng.gen_range::<Dimension>(0.5, 1.0),
-                        blue: rng.gen_range::<Dimension>(0.5, 1.0),
-                    },
-                    rng.gen_range::<Dimension>(0.0, 0.5),
-                )),
-                _ => Arc::new(Dielectric { ref_idx: 1.5 }),
-            };
-            let mut new_sphere: Option<Sphere> = None;
-            for _ in 0..1000 {
-                let sphere = Sphere {
-                    center: Vec3 {
-                        x: a as Dimension + rng.gen_range::<Dimension>(0.0, 0.9),
-                        y: 0.2,
-                        z: b as Dimension + rng.gen_range::<Dimension>(0.0, 0.9),
-                    },
-                    radius: 0.2,
-                };
-                if !spheres.iter().any(|&s| s.intersects(&sphere)) {
-                    spheres.push(sphere);
-                    new_sphere = Some(sphere);
-                    break;
-                }
+/// Adds rendered passes to `color_buffer` until `max_wait` has passed or
+/// rendering finishes. Returns the number of passes added, or None once
+/// rendering has finished.
+fn receive_samples(
+    rx: &Receiver<ColorBuffer>,
+    color_buffer: &mut ColorBuffer,
+    max_wait: Duration,
+) -> Option<usize> {
+    let buffer = rx.recv().ok()?;
+    let start = Instant::now();
+    let mut samples = 1;
+    color_buffer.add_buffer(buffer);
+    while let Some(remaining) = max_wait.checked_sub(start.elapsed()) {
+        match rx.recv_timeout(remaining) {
+            Ok(buffer) => {
+                samples += 1;
+                color_buffer.add_buffer(buffer);
             }
-            sphere_field.push(Box::new(WorldEntity {
-                shape: Box::new(new_sphere.expect("unable to place sphere")),
-                material: material,
-            }));
+            Err(_) => break,
         }
     }
-    let sphere_field =
-        Tree::from_list_on_dimensions(&mut sphere_field, &[SplitDim::X, SplitDim::Z]);
-    let center_spheres = Tree::from_list_on_dimensions(&mut center_spheres, &[SplitDim::X]);
-    let mut scene: Vec<Box<ModelSS>> = vec![floor, sphere_field, center_spheres];
-    let scene = Arc::from(Tree::from_list_on_dimensions(&mut scene, &[SplitDim::Y]));
-    (scene, camera)
+    Some(samples)
 }
 
-fn render_scene() {
-    let imgx = 600;
-    let imgy = 400;
-    let n_samples = 1000;
-    'new_scene: loop {
-        let (scene, camera) = random_scene(imgx, imgy);
-        for (imgx, imgy, n_samples, n_threads) in vec![
-            (imgx / 4, imgy / 4, 1, 1),
-            (imgx, imgy, 1, 1),
-            (imgx, imgy, n_samples, 15),
-        ] {
-            let (tx, rx) = sync_channel::<ColorBuffer>(n_threads * 3);
-            let ct = Arc::new(AtomicUsize::new(0));
-            for _ in 0..n_threads {
-                let ct = ct.clone();
-                let tx = tx.clone();
-                let scene = scene.clone();
-                let camera = camera.clone();
-                std::thread::spawn(move || {
-                    let mut rng = thread_rng();
-                    loop {
-                        let s = ct.fetch_add(1, Ordering::SeqCst);
-                        if s >= n_samples {
-                            break;
-                        }
-                        let mut color_buffer = ColorBuffer::new(imgx, imgy);
-                        let ru = rng.gen_range::<Dimension>(0.0, 1.0);
-                        let rv = rng.gen_range::<Dimension>(0.0, 1.0);
-                        for j in (0..imgy).rev() {
-                            let v = (rv + j as Dimension) / imgy as Dimension;
-                            for i in 0..imgx {
-                                let u = (ru + i as Dimension) / imgx as Dimension;
-                                let ray = camera.get_ray(u, v);
-                                let color = color(ray, &scene);
-                                color_buffer.add_color(i, imgy - 1 - j, color);
-                            }
-                        }
-                        tx.send(color_buffer).unwrap();
-                    }
-                });
-            }
+/// Writes the image outputs, with fireflies rejected when requested and
+/// denoised when requested and the auxiliary outputs guiding the denoiser
+/// are available.
+fn save_outputs(
+    settings: &Settings,
+    color_buffer: &ColorBuffer,
+    aov_buffers: Option<&AovBuffers>,
+) -> Result<(), WriteImageFileErr> {
+    let filtered = settings
+        .outlier_threshold
+        .map(|threshold| reject_outliers(color_buffer, threshold));
+    let color_buffer = filtered.as_ref().unwrap_or(color_buffer);
+    let denoised = match aov_buffers {
+        Some(aov_buffers) if settings.denoise => {
+            Some(Denoiser::new().denoise(color_buffer, aov_buffers))
+        }
+        _ => None,
+    };
+    for output in settings.outputs.iter() {
+        save_color_buffer(
+            output,
+            denoised.as_ref().unwrap_or(color_buffer),
+            &settings.display,
+            settings.exr,
+        )?;
+    }
+    Ok(())
+}
 
-            drop(tx);
+/// Renders the auxiliary outputs when they are requested or needed for
+/// denoising, and writes the requested ones.
+fn save_aovs(
+    settings: &Settings,
+    scene: &Scene,
+    imgx: usize,
+    imgy: usize,
+) -> Result<Option<AovBuffers>, WriteImageFileErr> {
+    if settings.aovs.is_empty() && !settings.denoise {
+        return Ok(None);
+    }
+    let aov_buffers = render_aovs(scene, imgx, imgy, settings.aov_samples);
+    let display = DisplayTransform::new();
+    for &(aov, ref file_name) in settings.aovs.iter() {
+        save_color_buffer(file_name, aov_buffers.get(aov), &display, settings.exr)?;
+    }
+    Ok(Some(aov_buffers))
+}
 
-            let mut sample = 0usize;
+/// Renders quick previews of random scenes until one is accepted and returns
+/// its seed.
+fn choose_scene(settings: &Settings, imgx: usize, imgy: usize) -> Result<u64, Box<error::Error>> {
+    'new_scene: loop {
+        let seed = thread_rng().gen::<u64>();
+        println!("scene seed {}", seed);
+        for (imgx, imgy) in vec![(imgx / 4, imgy / 4), (imgx, imgy)] {
+            let scene = Arc::new(random_scene(seed, imgx, imgy, &settings.scene)?);
+            let rx = spawn_render(&scene, imgx, imgy, 1, 1, settings.clamp);
             let mut color_buffer = ColorBuffer::new(imgx, imgy);
-            loop {
-                let mut buffer: ColorBuffer;
-                match rx.recv() {
-                    Ok(data) => buffer = data,
-                    Err(_) => break,
-                };
-                loop {
-                    sample += 1;
-                    color_buffer.add_buffer(buffer);
-                    match rx.recv_timeout(Duration::from_millis(500)) {
-                        Ok(data) => buffer = data,
-                        Err(_) => break,
-                    }
-                }
-
-                println!("sample {}/{}", sample, n_samples);
-                let image_buffer =
-                    ImageBuffer::from_color_buffer(&color_buffer, BytesPerColor::Two);
-                save_image("images/012-random-scene.png", &image_buffer).unwrap();
-            }
+            while receive_samples(&rx, &mut color_buffer, settings.progress_interval).is_some() {}
+            save_outputs(settings, &color_buffer, None)?;
 
             println!("ok? ('yes' to use this world)");
             let stdin = io::stdin();
-            let line = stdin.lock().lines().next().unwrap().unwrap();
+            let line = stdin.lock().lines().next().unwrap_or(Ok(String::new()))?;
             if line != "yes" {
                 continue 'new_scene;
             }
         }
+        return Ok(seed);
+    }
+}
+
+fn resume(
+    settings: &Settings,
+    file_name: &str,
+    imgx: usize,
+    imgy: usize,
+) -> Result<Checkpoint, Box<error::Error>> {
+    let checkpoint = load_checkpoint(file_name)?;
+    let seed = settings.seed.unwrap_or(checkpoint.seed);
+    if checkpoint.scene_hash != scene_hash(seed, imgx, imgy, &settings.scene, settings.clamp) {
+        return Err(From::from(format!(
+            "{} was rendered with a different scene or settings",
+            file_name
+        )));
+    }
+    println!(
+        "resuming scene seed {} at sample {}",
+        seed, checkpoint.samples
+    );
+    Ok(checkpoint)
+}
+
+fn render_scene(settings: &Settings) -> Result<(), Box<error::Error>> {
+    let imgx = 600;
+    let imgy = 400;
+    let mut checkpoint = match settings.checkpoint {
+        Some(ref file_name) if settings.resume => resume(settings, file_name, imgx, imgy)?,
+        _ => {
+            let seed = match settings.seed {
+                Some(seed) => seed,
+                None => choose_scene(settings, imgx, imgy)?,
+            };
+            Checkpoint {
+                color_buffer: ColorBuffer::new(imgx, imgy),
+                samples: 0,
+                scene_hash: scene_hash(seed, imgx, imgy, &settings.scene, settings.clamp),
+                seed: seed,
+            }
+        }
+    };
+    let scene = Arc::new(random_scene(checkpoint.seed, imgx, imgy, &settings.scene)?);
+    let aov_buffers = save_aovs(settings, &scene, imgx, imgy)?;
+    let n_samples = settings.samples.saturating_sub(checkpoint.samples);
+    if n_samples == 0 {
+        // nothing left to render, but the outputs may not exist yet
+        save_outputs(settings, &checkpoint.color_buffer, aov_buffers.as_ref())?;
+        return Ok(());
+    }
+    let rx = spawn_render(
+        &scene,
+        imgx,
+        imgy,
+        n_samples,
+        settings.threads,
+        settings.clamp,
+    );
+    while let Some(samples) = receive_samples(
+        &rx,
+        &mut checkpoint.color_buffer,
+        settings.progress_interval,
+    ) {
+        checkpoint.samples += samples;
+        println!("sample {}/{}", checkpoint.samples, settings.samples);
+        save_outputs(settings, &checkpoint.color_buffer, aov_buffers.as_ref())?;
+        if let Some(ref file_name) = settings.checkpoint {
+            save_checkpoint(file_name, &checkpoint)?;
+        }
     }
+    Ok(())
+}
+
+fn merge_renders(settings: &Settings) -> Result<(), Box<error::Error>> {
+    let mut checkpoints = Vec::new();
+    for file_name in settings.inputs.iter() {
+        checkpoints.push(load_checkpoint(file_name)?);
+    }
+    let merged = merge_checkpoints(checkpoints)?;
+    println!(
+        "merged {} renders of scene seed {}: {} samples",
+        settings.inputs.len(),
+        merged.seed,
+        merged.samples
+    );
+    let (imgx, imgy) = (merged.color_buffer.imgx, merged.color_buffer.imgy);
+    let scene = random_scene(merged.seed, imgx, imgy, &settings.scene)?;
+    let aov_buffers = save_aovs(settings, &scene, imgx, imgy)?;
+    save_outputs(settings, &merged.color_buffer, aov_buffers.as_ref())?;
+    if let Some(ref file_name) = settings.checkpoint {
+        save_checkpoint(file_name, &merged)?;
+    }
+    Ok(())
+}
+
+/// Renders through workers, which must see the files the scene reads at the
+/// same paths as the coordinator.
+fn coordinate_render(settings: &Settings) -> Result<(), Box<error::Error>> {
+    let imgx = 600;
+    let imgy = 400;
+    let seed = settings.seed.unwrap_or_else(|| thread_rng().gen::<u64>());
+    let listener = TcpListener::bind(settings.address.as_str())?;
+    let address = listener.local_addr()?.to_string();
+    println!("coordinating scene seed {} on {}", seed, address);
+    let mut local_workers = Vec::new();
+    for _ in 0..settings.local_workers {
+        let worker = process::Command::new(std::env::current_exe()?)
+            .args(&["worker", address.as_str(), "--threads", "1"])
+            .spawn()?;
+        local_workers.push(worker);
+    }
+    let aov_buffers = {
+        let scene = random_scene(seed, imgx, imgy, &settings.scene)?;
+        save_aovs(settings, &scene, imgx, imgy)?
+    };
+    let scene = SceneDescription {
+        imgx: imgx,
+        imgy: imgy,
+        scene_hash: scene_hash(seed, imgx, imgy, &settings.scene, settings.clamp),
+        seed: seed,
+        clamp: settings.clamp,
+        options: settings.scene.clone(),
+    };
+    let assignments = assignments(
+        imgx,
+        imgy,
+        settings.samples,
+        TILE_SIZE,
+        SAMPLES_PER_ASSIGNMENT,
+    );
+    let scene_hash = scene.scene_hash;
+    let color_buffer = coordinate(
+        listener,
+        scene,
+        assignments,
+        settings.worker_timeout,
+        settings.progress_interval,
+        |color_buffer| {
+            save_outputs(settings, color_buffer, aov_buffers.as_ref()).map_err(From::from)
+        },
+    )?;
+    save_outputs(settings, &color_buffer, aov_buffers.as_ref())?;
+    if let Some(ref file_name) = settings.checkpoint {
+        let checkpoint = Checkpoint {
+            color_buffer: color_buffer,
+            samples: settings.samples,
+            scene_hash: scene_hash,
+            seed: seed,
+        };
+        save_checkpoint(file_name, &checkpoint)?;
+    }
+    for worker in local_workers.iter_mut() {
+        let status = worker.wait()?;
+        if !status.success() {
+            return Err(From::from(format!("local worker failed: {}", status)));
+        }
+    }
+    Ok(())
 }
 
 fn main() {
-    render_scene();
+    let result = Settings::from_args(std::env::args().skip(1))
+        .map_err(|err| Box::new(err) as Box<error::Error>)
+        .and_then(|settings| match settings.command {
+            Command::Render => render_scene(&settings),
+            Command::Merge => merge_renders(&settings),
+            Command::Coordinator => coordinate_render(&settings),
+            Command::Worker => run_worker(&settings.address, settings.threads),
+        });
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
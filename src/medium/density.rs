@@ -0,0 +1,13 @@
+use geometry::vec3::*;
+use world::bounds::*;
+
+/// Density varying through space, scaling the coefficients of a medium.
+pub trait DensityField {
+    /// Density at `point`, zero outside the bounds.
+    fn density(&self, point: &Vec3) -> Dimension;
+    /// Largest density anywhere, bounding the tracking steps.
+    fn max_density(&self) -> Dimension;
+    fn bounds(&self) -> Bounds;
+}
+
+pub type DensityFieldSS = DensityField + Send + Sync;
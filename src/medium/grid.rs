@@ -0,0 +1,207 @@
+use geometry::vec3::*;
+use medium::density::*;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use world::bounds::*;
+
+#[derive(Debug)]
+pub enum ReadVolumeFileErr {
+    File(io::Error),
+    Format(String),
+}
+
+impl fmt::Display for ReadVolumeFileErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadVolumeFileErr::File(ref err) => write!(f, "File error: {}", err),
+            ReadVolumeFileErr::Format(ref msg) => write!(f, "Invalid volume: {}", msg),
+        }
+    }
+}
+
+impl error::Error for ReadVolumeFileErr {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            ReadVolumeFileErr::File(ref err) => Some(err),
+            ReadVolumeFileErr::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadVolumeFileErr {
+    fn from(err: io::Error) -> ReadVolumeFileErr {
+        ReadVolumeFileErr::File(err)
+    }
+}
+
+/// Densities sampled at the centers of voxels filling a box, interpolated
+/// trilinearly in between.
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    /// x varies fastest, then y, then z.
+    values: Vec<Dimension>,
+    bounds: Bounds,
+    max_density: Dimension,
+}
+
+impl DensityGrid {
+    pub fn new(resolution: [usize; 3], values: Vec<Dimension>, bounds: Bounds) -> DensityGrid {
+        let max_density = values.iter().cloned().fold(0.0, Dimension::max);
+        DensityGrid {
+            resolution: resolution,
+            values: values,
+            bounds: bounds,
+            max_density: max_density,
+        }
+    }
+
+    /// Scales the grid's box about the origin and then moves it by `offset`.
+    pub fn place(&mut self, offset: Vec3, scale: Dimension) {
+        self.bounds = Bounds::new(
+            self.bounds.min * scale + offset,
+            self.bounds.max * scale + offset,
+        );
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> Dimension {
+        let [nx, ny, _] = self.resolution;
+        self.values[(z * ny + y) * nx + x]
+    }
+}
+
+impl DensityField for DensityGrid {
+    fn density(&self, point: &Vec3) -> Dimension {
+        let (min, max) = (self.bounds.min, self.bounds.max);
+        let relative = [
+            (point.x - min.x) / (max.x - min.x),
+            (point.y - min.y) / (max.y - min.y),
+            (point.z - min.z) / (max.z - min.z),
+        ];
+        if relative.iter().any(|r| !(*r >= 0.0 && *r <= 1.0)) {
+            return 0.0;
+        }
+        // lower voxel and weight of the upper one along each axis
+        let mut lower = [0; 3];
+        let mut weight = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.resolution[axis];
+            let position = (relative[axis] * n as Dimension - 0.5)
+                .max(0.0)
+                .min((n - 1) as Dimension);
+            lower[axis] = (position as usize).min(n.saturating_sub(2));
+            weight[axis] = (position - lower[axis] as Dimension).min(1.0);
+        }
+        let mut density = 0.0;
+        for corner in 0..8 {
+            let mut index = [0; 3];
+            let mut corner_weight = 1.0;
+            for axis in 0..3 {
+                let upper = (corner >> axis) & 1 == 1;
+                index[axis] = (lower[axis] + upper as usize).min(self.resolution[axis] - 1);
+                corner_weight *= if upper {
+                    weight[axis]
+                } else {
+                    1.0 - weight[axis]
+                };
+            }
+            if corner_weight > 0.0 {
+                density += corner_weight * self.value(index[0], index[1], index[2]);
+            }
+        }
+        density
+    }
+
+    fn max_density(&self) -> Dimension {
+        self.max_density
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+}
+
+/// Reads a single channel, 32 bit float volume in Mitsuba's vol format.
+pub fn load_vol<'a>(file_name: &'a str) -> Result<DensityGrid, ReadVolumeFileErr> {
+    let file = File::open(Path::new(file_name))?;
+    read_vol(&mut BufReader::new(file))
+}
+
+pub fn read_vol<R: Read>(r: &mut R) -> Result<DensityGrid, ReadVolumeFileErr> {
+    let format_err = |msg: &str| ReadVolumeFileErr::Format(msg.to_string());
+    let mut header = [0u8; 4];
+    r.read_exact(&mut header)?;
+    if &header[..3] != b"VOL" || header[3] != 3 {
+        return Err(format_err("not a version 3 vol file"));
+    }
+    let read_u32 = |r: &mut R| -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        r.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    };
+    if read_u32(r)? != 1 {
+        return Err(format_err("only 32 bit float volumes are supported"));
+    }
+    let mut resolution = [0; 3];
+    for n in resolution.iter_mut() {
+        *n = read_u32(r)? as usize;
+    }
+    if resolution.iter().any(|&n| n == 0) {
+        return Err(format_err("empty volume"));
+    }
+    if read_u32(r)? != 1 {
+        return Err(format_err("only single channel volumes are supported"));
+    }
+    let mut corners = [0.0; 6];
+    for c in corners.iter_mut() {
+        *c = f32::from_bits(read_u32(r)?) as Dimension;
+    }
+    let count = resolution[0] * resolution[1] * resolution[2];
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = f32::from_bits(read_u32(r)?) as Dimension;
+        if !(value >= 0.0) {
+            return Err(format_err("negative density"));
+        }
+        values.push(value);
+    }
+    let bounds = Bounds::new(
+        Vec3::new(corners[0], corners[1], corners[2]),
+        Vec3::new(corners[3], corners[4], corners[5]),
+    );
+    Ok(DensityGrid::new(resolution, values, bounds))
+}
+
+#[cfg(test)]
+mod test_grid {
+    use super::*;
+
+    fn vol(resolution: [u32; 3], values: &[f32]) -> Vec<u8> {
+        let mut bytes = b"VOL\x03".to_vec();
+        for word in [1, resolution[0], resolution[1], resolution[2], 1].iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for value in [0.0f32, 0.0, 0.0, 2.0, 1.0, 1.0]
+            .iter()
+            .chain(values.iter())
+        {
+            bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn interpolates_between_voxel_centers() {
+        let bytes = vol([2, 1, 1], &[1.0, 3.0]);
+        let grid = read_vol(&mut &bytes[..]).unwrap();
+        assert_eq!(3.0, grid.max_density());
+        assert_eq!(1.0, grid.density(&Vec3::new(0.2, 0.5, 0.5)));
+        assert_eq!(2.0, grid.density(&Vec3::new(1.0, 0.5, 0.5)));
+        assert_eq!(3.0, grid.density(&Vec3::new(1.9, 0.1, 0.9)));
+        assert_eq!(0.0, grid.density(&Vec3::new(1.0, 1.5, 0.5)));
+        assert!(read_vol(&mut &bytes[..bytes.len() - 1]).is_err());
+    }
+}
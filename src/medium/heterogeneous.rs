@@ -0,0 +1,34 @@
+use color::sample::*;
+use geometry::vec3::*;
+use medium::density::*;
+use medium::phase::*;
+use world::bounds::*;
+
+/// Participating medium whose coefficients follow a density field, such as
+/// a cloud or smoke.
+pub struct HeterogeneousMedium {
+    pub density: Box<DensityFieldSS>,
+    /// Absorption per unit distance at density one.
+    pub absorption: ColorSample,
+    /// Scattering per unit distance at density one.
+    pub scattering: ColorSample,
+    pub phase_function: HenyeyGreenstein,
+}
+
+impl HeterogeneousMedium {
+    /// Absorption and scattering coefficients at `point`.
+    pub fn coefficients(&self, point: &Vec3) -> (ColorSample, ColorSample) {
+        let density = self.density.density(point);
+        (self.absorption * density, self.scattering * density)
+    }
+
+    /// Bound on the extinction of every channel everywhere in the medium.
+    pub fn majorant(&self) -> SamplePrecision {
+        let extinction = self.absorption + self.scattering;
+        extinction.red.max(extinction.green).max(extinction.blue) * self.density.max_density()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.density.bounds()
+    }
+}
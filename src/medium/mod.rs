@@ -0,0 +1,6 @@
+pub mod density;
+pub mod grid;
+pub mod heterogeneous;
+pub mod noise;
+pub mod phase;
+pub mod tracking;
@@ -0,0 +1,85 @@
+use geometry::vec3::*;
+use medium::density::*;
+use world::bounds::*;
+
+/// Octaves of value noise summed into the cloud's detail.
+const OCTAVES: usize = 5;
+
+/// Puffy cloud filling a sphere: dense in the middle and eroded by fractal
+/// noise towards the edge. Densities are between zero and one.
+pub struct NoiseCloud {
+    pub center: Vec3,
+    pub radius: Dimension,
+}
+
+impl DensityField for NoiseCloud {
+    fn density(&self, point: &Vec3) -> Dimension {
+        let offset = (*point - self.center) / self.radius;
+        let distance = offset.length();
+        if distance >= 1.0 {
+            return 0.0;
+        }
+        let noise = fractal_noise(offset * 4.0);
+        (2.0 * (1.0 - distance) + noise - 0.5).max(0.0).min(1.0)
+    }
+
+    fn max_density(&self) -> Dimension {
+        1.0
+    }
+
+    fn bounds(&self) -> Bounds {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        Bounds::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// Sum of value noise octaves with halving amplitudes, in [-1, 1].
+pub fn fractal_noise(point: Vec3) -> Dimension {
+    let mut sum = 0.0;
+    let mut amplitude = 0.5;
+    let mut frequency = 1.0;
+    for _ in 0..OCTAVES {
+        sum += amplitude * value_noise(point * frequency);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / (1.0 - amplitude * 2.0)
+}
+
+/// Smoothly interpolated random values at the integer lattice, in [-1, 1].
+fn value_noise(point: Vec3) -> Dimension {
+    let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+    let smooth = |t: Dimension| t * t * (3.0 - 2.0 * t);
+    let (fx, fy, fz) = (
+        smooth(point.x - x0),
+        smooth(point.y - y0),
+        smooth(point.z - z0),
+    );
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+    let lerp = |a: Dimension, b: Dimension, t: Dimension| a + (b - a) * t;
+    let corner = |dx: i64, dy: i64, dz: i64| lattice_value(x0 + dx, y0 + dy, z0 + dz);
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), fx),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), fx),
+            fy,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), fx),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), fx),
+            fy,
+        ),
+        fz,
+    )
+}
+
+fn lattice_value(x: i64, y: i64, z: i64) -> Dimension {
+    let mut hash = (x as u64)
+        .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add((y as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f))
+        .wrapping_add((z as u64).wrapping_mul(0x1656_67b1_9e37_79f9));
+    hash ^= hash >> 31;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 29;
+    (hash >> 11) as Dimension / (1u64 << 53) as Dimension * 2.0 - 1.0
+}
@@ -0,0 +1,78 @@
+use geometry::frame::*;
+use geometry::vec3::*;
+use rand::{thread_rng, Rng};
+
+/// Henyey-Greenstein phase function. Positive asymmetry `g` favours forward
+/// scattering, as in clouds, negative favours back scattering and zero
+/// scatters equally in all directions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HenyeyGreenstein {
+    /// Mean cosine of the scattering angle, in (-1, 1).
+    pub g: Dimension,
+}
+
+impl HenyeyGreenstein {
+    /// Density per steradian of scattering from travelling in unit direction
+    /// `incoming` to travelling in unit direction `outgoing`.
+    pub fn eval(&self, incoming: &Vec3, outgoing: &Vec3) -> Dimension {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * incoming.dot(*outgoing);
+        (1.0 - g * g) / (4.0 * PI_DIMENSION * denominator * denominator.max(1e-12).sqrt())
+    }
+
+    /// Samples a unit direction to travel in after scattering, distributed
+    /// as `eval`.
+    pub fn sample(&self, incoming: &Vec3) -> Vec3 {
+        let mut rng = thread_rng();
+        let u = rng.gen_range::<Dimension>(0.0, 1.0);
+        let phi = rng.gen_range::<Dimension>(0.0, 2.0 * PI_DIMENSION);
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 + g - 2.0 * g * u);
+            ((1.0 + g * g - s * s) / (2.0 * g)).max(-1.0).min(1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        Frame::from_w(incoming.unit()).to_world(Vec3::new(
+            sin_theta * phi.cos(),
+            sin_theta * phi.sin(),
+            cos_theta,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test_phase {
+    use super::*;
+
+    #[test]
+    fn samples_have_the_mean_cosine_g() {
+        let incoming = Vec3::new(0.0, 1.0, 0.0);
+        for &g in [-0.5, 0.0, 0.8].iter() {
+            let phase = HenyeyGreenstein { g: g };
+            let n = 20000;
+            let mean = (0..n)
+                .map(|_| phase.sample(&incoming).dot(incoming))
+                .sum::<Dimension>()
+                / n as Dimension;
+            assert!((mean - g).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn integrates_to_one() {
+        let phase = HenyeyGreenstein { g: 0.7 };
+        let incoming = Vec3::new(0.0, 0.0, 1.0);
+        let steps = 2000;
+        let d_theta = PI_DIMENSION / steps as Dimension;
+        let total: Dimension = (0..steps)
+            .map(|i| {
+                let theta = (i as Dimension + 0.5) * d_theta;
+                let outgoing = Vec3::new(theta.sin(), 0.0, theta.cos());
+                phase.eval(&incoming, &outgoing) * 2.0 * PI_DIMENSION * theta.sin() * d_theta
+            })
+            .sum();
+        assert!((total - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,319 @@
+use color::sample::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use medium::heterogeneous::*;
+use medium::phase::*;
+use rand::{thread_rng, Rng};
+
+/// What happened to a ray travelling through the media.
+pub enum MediumEvent<'a> {
+    /// The ray got through. Radiance arriving along it is to be multiplied
+    /// by the weight.
+    Passed(ColorSample),
+    /// The ray scattered at `point`. The weight includes the scattering
+    /// albedo.
+    Scattered {
+        point: Vec3,
+        weight: ColorSample,
+        phase_function: &'a HenyeyGreenstein,
+    },
+    Absorbed,
+}
+
+/// Samples where a ray travelling between `t_min` and `t_max` first interacts
+/// with the media, by spectral tracking (Kutz et al., "Spectral and
+/// Decomposition Tracking for Rendering Heterogeneous Volumes", 2017). Steps
+/// are taken against the summed majorants of the media the ray crosses and
+/// each tentative collision is absorption, scattering or a null collision
+/// with probabilities following the mean coefficients over the channels.
+/// The weights keep the estimate unbiased for every channel.
+pub fn sample_media<'a>(
+    media: &'a [HeterogeneousMedium],
+    ray: &Ray,
+    t_min: Dimension,
+    t_max: Dimension,
+) -> MediumEvent<'a> {
+    let (t_start, t_end, majorant) = match overlap(media, ray, t_min, t_max) {
+        Some(overlap) => overlap,
+        None => return MediumEvent::Passed(ColorSample::WHITE),
+    };
+    let mut rng = thread_rng();
+    let step_scale = (majorant * ray.direction.length()).recip();
+    let mut weight = ColorSample::WHITE;
+    let mut t = t_start;
+    loop {
+        t -= (1.0 - rng.gen_range::<Dimension>(0.0, 1.0)).ln() * step_scale;
+        if t >= t_end {
+            return MediumEvent::Passed(weight);
+        }
+        let point = ray.point_at_parameter(t);
+        let mut absorption = ColorSample::BLACK;
+        let mut scattering = ColorSample::BLACK;
+        for medium in media.iter() {
+            let (a, s) = medium.coefficients(&point);
+            absorption += a;
+            scattering += s;
+        }
+        let null = ColorSample {
+            red: (majorant - absorption.red - scattering.red).max(0.0),
+            green: (majorant - absorption.green - scattering.green).max(0.0),
+            blue: (majorant - absorption.blue - scattering.blue).max(0.0),
+        };
+        let (p_absorb, p_scatter, p_null) = (mean(absorption), mean(scattering), mean(null));
+        let u = rng.gen_range::<Dimension>(0.0, p_absorb + p_scatter + p_null);
+        if u < p_absorb {
+            return MediumEvent::Absorbed;
+        } else if u < p_absorb + p_scatter {
+            // pick the medium to scatter in by its share of the scattering
+            let mut v = rng.gen_range::<Dimension>(0.0, p_scatter);
+            for medium in media.iter() {
+                let (_, s) = medium.coefficients(&point);
+                v -= mean(s);
+                if v < 0.0 {
+                    return MediumEvent::Scattered {
+                        point: point,
+                        weight: weight * s * mean(s).recip(),
+                        phase_function: &medium.phase_function,
+                    };
+                }
+            }
+            return MediumEvent::Absorbed;
+        } else {
+            weight *= null * p_null.recip();
+        }
+    }
+}
+
+/// Samples where a ray travelling between `t_min` and `t_max` first interacts
+/// with a homogeneous medium filling all of space, such as the inside of a
+/// translucent object. The distance is sampled by the extinction of a random
+/// channel, and weighted by the mean density over the channels, which keeps
+/// the weights small along the long walks of strongly colored media.
+pub fn sample_homogeneous<'a>(
+    absorption: ColorSample,
+    scattering: ColorSample,
+    phase_function: &'a HenyeyGreenstein,
+    ray: &Ray,
+    t_min: Dimension,
+    t_max: Dimension,
+) -> MediumEvent<'a> {
+    let extinction = absorption + scattering;
+    let mut rng = thread_rng();
+    let channel = match rng.gen_range::<Dimension>(0.0, 3.0) {
+        u if u < 1.0 => extinction.red,
+        u if u < 2.0 => extinction.green,
+        _ => extinction.blue,
+    };
+    let length = ray.direction.length();
+    let t = if channel > 0.0 {
+        t_min - (1.0 - rng.gen_range::<Dimension>(0.0, 1.0)).ln() / (channel * length)
+    } else {
+        MAX_DIMENSION
+    };
+    let passed = t >= t_max;
+    let distance = (t.min(t_max) - t_min) * length;
+    let transmittance = ColorSample {
+        red: (-extinction.red * distance).exp(),
+        green: (-extinction.green * distance).exp(),
+        blue: (-extinction.blue * distance).exp(),
+    };
+    if passed {
+        // probability of getting this far
+        return MediumEvent::Passed(transmittance * mean(transmittance).recip());
+    }
+    let density = mean(extinction * transmittance);
+    if !(density > 0.0) {
+        return MediumEvent::Absorbed;
+    }
+    MediumEvent::Scattered {
+        point: ray.point_at_parameter(t),
+        weight: transmittance * scattering * density.recip(),
+        phase_function: phase_function,
+    }
+}
+
+/// Fraction of light getting through the media between `t_min` and `t_max`,
+/// estimated by ratio tracking.
+pub fn transmittance(
+    media: &[HeterogeneousMedium],
+    ray: &Ray,
+    t_min: Dimension,
+    t_max: Dimension,
+) -> ColorSample {
+    let (t_start, t_end, majorant) = match overlap(media, ray, t_min, t_max) {
+        Some(overlap) => overlap,
+        None => return ColorSample::WHITE,
+    };
+    let mut rng = thread_rng();
+    let step_scale = (majorant * ray.direction.length()).recip();
+    let mut transmittance = ColorSample::WHITE;
+    let mut t = t_start;
+    loop {
+        t -= (1.0 - rng.gen_range::<Dimension>(0.0, 1.0)).ln() * step_scale;
+        if t >= t_end {
+            return transmittance;
+        }
+        let point = ray.point_at_parameter(t);
+        let mut extinction = ColorSample::BLACK;
+        for medium in media.iter() {
+            let (a, s) = medium.coefficients(&point);
+            extinction += a + s;
+        }
+        transmittance.red *= (1.0 - extinction.red / majorant).max(0.0);
+        transmittance.green *= (1.0 - extinction.green / majorant).max(0.0);
+        transmittance.blue *= (1.0 - extinction.blue / majorant).max(0.0);
+        // Russian roulette once little light is left
+        let largest = transmittance
+            .red
+            .max(transmittance.green)
+            .max(transmittance.blue);
+        if largest < 0.1 {
+            if rng.gen_range::<SamplePrecision>(0.0, 1.0) < 0.5 {
+                return ColorSample::BLACK;
+            }
+            transmittance *= 2.0;
+        }
+    }
+}
+
+/// Part of the ray between `t_min` and `t_max` crossing the bounds of any of
+/// the media, with the sum of their majorants.
+fn overlap(
+    media: &[HeterogeneousMedium],
+    ray: &Ray,
+    t_min: Dimension,
+    t_max: Dimension,
+) -> Option<(Dimension, Dimension, SamplePrecision)> {
+    let mut range: Option<(Dimension, Dimension)> = None;
+    let mut majorant = 0.0;
+    for medium in media.iter() {
+        if let Some((t0, t1)) = medium.bounds().clip(ray, t_min, t_max) {
+            majorant += medium.majorant();
+            range = Some(match range {
+                Some((r0, r1)) => (r0.min(t0), r1.max(t1)),
+                None => (t0, t1),
+            });
+        }
+    }
+    match range {
+        Some((t0, t1)) if majorant > 0.0 => Some((t0, t1, majorant)),
+        _ => None,
+    }
+}
+
+fn mean(color: ColorSample) -> SamplePrecision {
+    (color.red + color.green + color.blue) / 3.0
+}
+
+#[cfg(test)]
+mod test_tracking {
+    use super::*;
+    use medium::density::*;
+    use world::bounds::*;
+
+    /// Unit density in the unit cube.
+    struct Cube;
+
+    impl DensityField for Cube {
+        fn density(&self, point: &Vec3) -> Dimension {
+            let inside = |x: Dimension| x >= 0.0 && x <= 1.0;
+            if inside(point.x) && inside(point.y) && inside(point.z) {
+                1.0
+            } else {
+                0.0
+            }
+        }
+
+        fn max_density(&self) -> Dimension {
+            1.0
+        }
+
+        fn bounds(&self) -> Bounds {
+            Bounds::new(Vec3::ZERO, Vec3::new(1.0, 1.0, 1.0))
+        }
+    }
+
+    fn colored_medium() -> Vec<HeterogeneousMedium> {
+        vec![HeterogeneousMedium {
+            density: Box::new(Cube),
+            absorption: ColorSample {
+                red: 0.2,
+                green: 0.5,
+                blue: 1.0,
+            },
+            scattering: ColorSample {
+                red: 0.5,
+                green: 0.5,
+                blue: 0.5,
+            },
+            phase_function: HenyeyGreenstein { g: 0.0 },
+        }]
+    }
+
+    fn ray() -> Ray {
+        Ray {
+            origin: Vec3::new(0.5, 0.5, -1.0),
+            direction: Vec3::new(0.0, 0.0, 2.0),
+            wavelength: None,
+        }
+    }
+
+    #[test]
+    fn transmittance_follows_beer_lambert_per_channel() {
+        let media = colored_medium();
+        let n = 20000;
+        let mut total = ColorSample::BLACK;
+        for _ in 0..n {
+            total += transmittance(&media, &ray(), 0.0, 10.0);
+        }
+        let mean = total / n;
+        assert!((mean.red - (-0.7 as SamplePrecision).exp()).abs() < 0.02);
+        assert!((mean.blue - (-1.5 as SamplePrecision).exp()).abs() < 0.02);
+    }
+
+    #[test]
+    fn passing_weights_match_transmittance() {
+        let media = colored_medium();
+        let n = 20000;
+        let mut total = ColorSample::BLACK;
+        for _ in 0..n {
+            if let MediumEvent::Passed(weight) = sample_media(&media, &ray(), 0.0, 10.0) {
+                total += weight;
+            }
+        }
+        let mean = total / n;
+        assert!((mean.red - (-0.7 as SamplePrecision).exp()).abs() < 0.02);
+        assert!((mean.green - (-1.0 as SamplePrecision).exp()).abs() < 0.02);
+        assert!((mean.blue - (-1.5 as SamplePrecision).exp()).abs() < 0.02);
+    }
+
+    #[test]
+    fn homogeneous_weights_match_transmittance() {
+        // the coefficients of the colored medium over the same distance
+        let absorption = ColorSample {
+            red: 0.2,
+            green: 0.5,
+            blue: 1.0,
+        };
+        let phase_function = HenyeyGreenstein { g: 0.0 };
+        let n = 20000;
+        let mut total = ColorSample::BLACK;
+        for _ in 0..n {
+            let event = sample_homogeneous(
+                absorption,
+                ColorSample::WHITE * 0.5,
+                &phase_function,
+                &ray(),
+                0.0,
+                0.5,
+            );
+            if let MediumEvent::Passed(weight) = event {
+                total += weight;
+            }
+        }
+        let mean = total / n;
+        assert!((mean.red - (-0.7 as SamplePrecision).exp()).abs() < 0.02);
+        assert!((mean.green - (-1.0 as SamplePrecision).exp()).abs() < 0.02);
+        assert!((mean.blue - (-1.5 as SamplePrecision).exp()).abs() < 0.02);
+    }
+}
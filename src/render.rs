@@ -0,0 +1,530 @@
+use color::aov::*;
+use color::buffer::*;
+use color::sample::*;
+use color::spectrum::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use hit_detection::hitable::*;
+use light::light::*;
+use medium::phase::*;
+use medium::tracking::*;
+use rand::{thread_rng, Rng};
+use scene::*;
+use world::model::*;
+
+/// Interactions a random walk through a translucent object takes before
+/// Russian roulette may end it.
+const WALK_ROULETTE_STEPS: usize = 8;
+
+/// Most interactions a random walk takes before it is given up. Roulette ends
+/// walks long before, unless the interior hardly absorbs at all.
+const MAX_WALK_STEPS: usize = 100_000;
+
+/// A rectangle of pixels, with y increasing downwards.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Limits the radiance a single sample contributes, trading bias for fewer
+/// fireflies.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SampleClamp {
+    /// Largest channel value a sample may have. Brighter samples are scaled
+    /// down, keeping their hue.
+    pub max: SamplePrecision,
+    /// Only clamp light that bounced more than once, leaving directly seen
+    /// and directly lit surfaces exact.
+    pub indirect_only: bool,
+}
+
+impl SampleClamp {
+    fn apply(&self, color: ColorSample, bounces: usize) -> ColorSample {
+        if self.indirect_only && bounces < 2 {
+            return color;
+        }
+        let brightest = color.red.max(color.green).max(color.blue);
+        if brightest > self.max {
+            color * (self.max / brightest)
+        } else {
+            color
+        }
+    }
+}
+
+/// Radiance arriving along `ray`. At surfaces that can be lit directly the
+/// scene's lights are sampled with shadow rays; rays scattered from those
+/// surfaces then no longer see the lights, which would count them twice.
+/// Media between a ray's origin and the surface it hits may scatter or absorb
+/// it first; points where it scatters are lit like surfaces. Inside objects
+/// with an absorbing interior, such as coloured glass, light fades with the
+/// distance travelled, and inside translucent ones paths take a random walk
+/// until they reach the surface again.
+///
+/// In spectral mode the path follows a hero wavelength and two companions,
+/// its colors holding their values at those wavelengths. Colors of materials
+/// and lights are upsampled to spectra as they are met, and the result is
+/// converted back through the film's response.
+pub fn color(ray: Ray, scene: &Scene, clamp: Option<SampleClamp>) -> ColorSample {
+    let clamped = |color: ColorSample, bounces: usize| match clamp {
+        Some(clamp) => clamp.apply(color, bounces),
+        None => color,
+    };
+    let wavelengths = if scene.spectral {
+        Some(Wavelengths::sample(thread_rng().gen_range(0.0, 1.0)))
+    } else {
+        None
+    };
+    // colors of materials and media
+    let reflectance = |color: ColorSample| match wavelengths {
+        Some(ref wavelengths) => wavelengths.reflectance(color),
+        None => color,
+    };
+    // colors of light sources
+    let emission =
+        |color: ColorSample, spectrum: Option<&IlluminantSpectrum>| match (wavelengths, spectrum) {
+            (Some(ref wavelengths), Some(spectrum)) => spectrum.values(color, wavelengths),
+            (Some(ref wavelengths), None) => wavelengths.illuminant(color),
+            (None, _) => color,
+        };
+    let mut attenuation = ColorSample::WHITE;
+    let mut radiance = ColorSample::BLACK;
+    let mut new_ray = Ray {
+        wavelength: wavelengths.map(|wavelengths| wavelengths.hero()),
+        ..ray
+    };
+    let mut hero_only = false;
+    let mut lights_sampled = false;
+    // objects the path is inside, innermost last
+    let mut interiors: Vec<Interior> = Vec::new();
+    for depth in 0..50 {
+        let mut hit = scene.model.hit_model(&new_ray, 1e-3, MAX_DIMENSION);
+        let t_hit = hit.as_ref().map_or(MAX_DIMENSION, |hit| hit.hit_record.t);
+        if let Some(interior) = interiors.last().filter(|i| i.scattering.is_some()) {
+            // the scene's media don't reach inside
+            match random_walk(scene, new_ray, hit, interior) {
+                Some((ray, walk_hit, weight)) => {
+                    attenuation *= reflectance(weight);
+                    new_ray = ray;
+                    hit = walk_hit;
+                }
+                None => break,
+            }
+        } else {
+            match sample_media(&scene.media, &new_ray, 1e-3, t_hit) {
+                MediumEvent::Absorbed => break,
+                MediumEvent::Scattered {
+                    point,
+                    weight,
+                    phase_function,
+                } => {
+                    attenuation *= reflectance(weight);
+                    attenuation *= reflectance(interior_transmittance(
+                        &interiors,
+                        (point - new_ray.origin).length(),
+                    ));
+                    let incoming = new_ray.direction.unit();
+                    lights_sampled = false;
+                    for light in scene.lights.iter() {
+                        if let Some(sample) = light.sample(&point) {
+                            lights_sampled = true;
+                            let f = phase_function.eval(&incoming, &sample.direction);
+                            let shadow = shadow_transmittance(scene, &point, &sample);
+                            let shadow = reflectance(shadow);
+                            let incident = emission(sample.radiance, light.spectrum());
+                            radiance += clamped(attenuation * shadow * incident * f, depth + 1);
+                        }
+                    }
+                    new_ray = Ray {
+                        origin: point,
+                        direction: phase_function.sample(&incoming),
+                        wavelength: new_ray.wavelength,
+                    };
+                    continue;
+                }
+                MediumEvent::Passed(weight) => attenuation *= reflectance(weight),
+            }
+        }
+        if let Some(hit) = hit {
+            let (p, normal) = (hit.hit_record.p, shading_normal(&hit.hit_record, &new_ray));
+            let tangent = hit.hit_record.dpdu;
+            attenuation *= reflectance(interior_transmittance(
+                &interiors,
+                (p - new_ray.origin).length(),
+            ));
+            if wavelengths.is_some() && !hero_only && hit.material.is_wavelength_dependent() {
+                attenuation *= Wavelengths::HERO_ONLY;
+                hero_only = true;
+            }
+            lights_sampled = false;
+            for light in scene.lights.iter() {
+                let sample = match light.sample(&p) {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                if let Some(f) =
+                    hit.material
+                        .eval(&new_ray, &p, &normal, &tangent, &sample.direction)
+                {
+                    lights_sampled = true;
+                    let shadow = reflectance(shadow_transmittance(scene, &p, &sample));
+                    let incident = emission(sample.radiance, light.spectrum());
+                    radiance +=
+                        clamped(attenuation * shadow * reflectance(f) * incident, depth + 1);
+                }
+            }
+            if let Some(scatter_result) = hit.material.scatter(&new_ray, &p, &normal, &tangent) {
+                if scatter_result.specular {
+                    // the lights were only sampled for the other lobes
+                    lights_sampled = false;
+                }
+                attenuation *= reflectance(scatter_result.attenuation);
+                new_ray = scatter_result.scattered;
+                if let Some(absorption) = hit.material.interior_absorption() {
+                    let inside = new_ray.direction.dot(hit.hit_record.normal) < 0.0;
+                    let position = interiors.iter().position(|i| i.object_id == hit.object_id);
+                    match (inside, position) {
+                        (true, None) => interiors.push(Interior {
+                            object_id: hit.object_id,
+                            absorption: absorption,
+                            scattering: hit.material.interior_scattering(),
+                        }),
+                        (false, Some(i)) => {
+                            interiors.remove(i);
+                        }
+                        _ => (),
+                    }
+                }
+                continue;
+            } else {
+                break;
+            }
+        } else {
+            if !interiors.is_empty() {
+                // escaped from inside an object that isn't closed
+                attenuation *= reflectance(interior_transmittance(&interiors, MAX_DIMENSION));
+            }
+            let mut col = emission(scene.environment.radiance(&new_ray.direction), None);
+            if !lights_sampled {
+                for light in scene.lights.iter() {
+                    col += emission(light.emitted(&new_ray.direction), light.spectrum());
+                }
+            }
+            radiance += clamped(attenuation * col, depth);
+            break;
+        }
+    }
+    match wavelengths {
+        Some(wavelengths) => wavelengths.to_rgb(radiance),
+        None => radiance,
+    }
+}
+
+/// Normal to shade `hit` with. Normal and bump maps may tilt the shading
+/// normal past the ray, which would confuse materials about the side it
+/// arrives from; the geometric normal is used then.
+fn shading_normal(hit: &HitRecord, ray: &Ray) -> Vec3 {
+    let (shading, geometric) = (
+        ray.direction.dot(hit.shading_normal),
+        ray.direction.dot(hit.normal),
+    );
+    if (shading > 0.0) == (geometric > 0.0) {
+        hit.shading_normal
+    } else {
+        hit.normal
+    }
+}
+
+/// Medium filling an object a path is inside.
+#[derive(Clone, Copy, Debug)]
+struct Interior {
+    object_id: usize,
+    absorption: ColorSample,
+    /// Scattering per unit distance and phase function of translucent
+    /// objects.
+    scattering: Option<(ColorSample, HenyeyGreenstein)>,
+}
+
+/// Beer-Lambert transmittance over `distance` inside the innermost of
+/// `interiors`. Random walks account for absorption inside translucent
+/// objects themselves.
+fn interior_transmittance(interiors: &[Interior], distance: Dimension) -> ColorSample {
+    match interiors.last() {
+        Some(&Interior {
+            absorption,
+            scattering: None,
+            ..
+        }) => ColorSample {
+            red: (-absorption.red * distance).exp(),
+            green: (-absorption.green * distance).exp(),
+            blue: (-absorption.blue * distance).exp(),
+        },
+        _ => ColorSample::WHITE,
+    }
+}
+
+/// Follows `ray`, which first reaches the surface at `hit`, on a random walk
+/// through the translucent `interior` it is in. Returns the ray reaching the
+/// surface, where it hits and the weight of the walk; None when the light is
+/// absorbed on the way or the walk ends by Russian roulette.
+fn random_walk(
+    scene: &Scene,
+    mut ray: Ray,
+    mut hit: Option<ModelHitRecord>,
+    interior: &Interior,
+) -> Option<(Ray, Option<ModelHitRecord>, ColorSample)> {
+    let (scattering, phase_function) = interior.scattering?;
+    let mut weight = ColorSample::WHITE;
+    for step in 0..MAX_WALK_STEPS {
+        let t_hit = hit.as_ref().map_or(MAX_DIMENSION, |hit| hit.hit_record.t);
+        match sample_homogeneous(
+            interior.absorption,
+            scattering,
+            &phase_function,
+            &ray,
+            1e-3,
+            t_hit,
+        ) {
+            MediumEvent::Passed(passed) => return Some((ray, hit, weight * passed)),
+            MediumEvent::Absorbed => return None,
+            MediumEvent::Scattered {
+                point,
+                weight: scattered,
+                phase_function,
+            } => {
+                weight *= scattered;
+                if step >= WALK_ROULETTE_STEPS {
+                    let survival = weight.red.max(weight.green).max(weight.blue).min(1.0);
+                    if !(thread_rng().gen_range::<SamplePrecision>(0.0, 1.0) < survival) {
+                        return None;
+                    }
+                    weight *= survival.recip();
+                }
+                ray = Ray {
+                    origin: point,
+                    direction: phase_function.sample(&ray.direction.unit()),
+                    wavelength: ray.wavelength,
+                };
+                hit = scene.model.hit_model(&ray, 1e-3, MAX_DIMENSION);
+            }
+        }
+    }
+    None
+}
+
+/// Fraction of the light in `sample` reaching `point`: black when a surface is
+/// in the way, otherwise the transmittance of the media.
+fn shadow_transmittance(scene: &Scene, point: &Vec3, sample: &LightSample) -> ColorSample {
+    let shadow_ray = Ray {
+        origin: *point,
+        direction: sample.direction,
+        wavelength: None,
+    };
+    if scene
+        .model
+        .hit_model(&shadow_ray, 1e-3, sample.distance)
+        .is_some()
+    {
+        return ColorSample::BLACK;
+    }
+    transmittance(&scene.media, &shadow_ray, 1e-3, sample.distance)
+}
+
+/// Renders `n_samples` passes over the pixels of `tile`. The returned buffer
+/// is the size of the tile.
+pub fn render_tile(
+    scene: &Scene,
+    imgx: usize,
+    imgy: usize,
+    tile: &Tile,
+    n_samples: usize,
+    clamp: Option<SampleClamp>,
+) -> ColorBuffer {
+    let mut rng = thread_rng();
+    let mut color_buffer = ColorBuffer::new(tile.width, tile.height);
+    for _ in 0..n_samples {
+        let ru = rng.gen_range::<Dimension>(0.0, 1.0);
+        let rv = rng.gen_range::<Dimension>(0.0, 1.0);
+        for y in 0..tile.height {
+            let j = imgy - 1 - (tile.y + y);
+            let v = (rv + j as Dimension) / imgy as Dimension;
+            for x in 0..tile.width {
+                let i = tile.x + x;
+                let u = (ru + i as Dimension) / imgx as Dimension;
+                let ray = scene.camera.get_ray(u, v);
+                let color = color(ray, scene, clamp);
+                color_buffer.add_color(x, y, color);
+            }
+        }
+    }
+    color_buffer
+}
+
+/// What the camera ray hits first, for the auxiliary outputs.
+pub fn first_hit(ray: Ray, scene: &Scene) -> Option<AovSample> {
+    scene
+        .model
+        .hit_model(&ray, 1e-3, MAX_DIMENSION)
+        .map(|hit| AovSample {
+            albedo: hit.material.albedo(&hit.hit_record.p),
+            normal: hit.hit_record.shading_normal,
+            depth: hit.hit_record.t * ray.direction.length(),
+            position: hit.hit_record.p,
+            material_id: hit.material_id,
+            object_id: hit.object_id,
+        })
+}
+
+/// Renders the auxiliary outputs with `n_samples` jittered rays per pixel.
+/// Ids come from a single ray through the pixel center so object edges don't
+/// blend them.
+pub fn render_aovs(scene: &Scene, imgx: usize, imgy: usize, n_samples: usize) -> AovBuffers {
+    let mut rng = thread_rng();
+    let mut aov_buffers = AovBuffers::new(imgx, imgy);
+    for s in 0..n_samples {
+        let (ru, rv) = if s == 0 {
+            (0.5, 0.5)
+        } else {
+            (
+                rng.gen_range::<Dimension>(0.0, 1.0),
+                rng.gen_range::<Dimension>(0.0, 1.0),
+            )
+        };
+        for y in 0..imgy {
+            let v = (rv + (imgy - 1 - y) as Dimension) / imgy as Dimension;
+            for x in 0..imgx {
+                let u = (ru + x as Dimension) / imgx as Dimension;
+                let sample = first_hit(scene.camera.get_ray(u, v), scene);
+                aov_buffers.add_sample(x, y, sample.as_ref());
+                if s == 0 {
+                    aov_buffers.set_ids(x, y, sample.as_ref());
+                }
+            }
+        }
+    }
+    aov_buffers
+}
+
+#[cfg(test)]
+mod test_sample_clamp {
+    use super::*;
+
+    const BRIGHT: ColorSample = ColorSample {
+        red: 8.0,
+        green: 4.0,
+        blue: 2.0,
+    };
+
+    #[test]
+    fn scales_bright_samples_keeping_hue() {
+        let clamp = SampleClamp {
+            max: 2.0,
+            indirect_only: false,
+        };
+        let clamped = clamp.apply(BRIGHT, 0);
+        assert_eq!((2.0, 1.0, 0.5), (clamped.red, clamped.green, clamped.blue));
+    }
+
+    #[test]
+    fn indirect_only_leaves_direct_light() {
+        let clamp = SampleClamp {
+            max: 2.0,
+            indirect_only: true,
+        };
+        assert_eq!(8.0, clamp.apply(BRIGHT, 1).red);
+        assert_eq!(2.0, clamp.apply(BRIGHT, 2).red);
+    }
+}
+
+#[cfg(test)]
+mod test_interior_transmittance {
+    use super::*;
+
+    #[test]
+    fn attenuates_by_the_innermost_interior() {
+        assert_eq!(ColorSample::WHITE, interior_transmittance(&[], 10.0));
+        let tinted = ColorSample {
+            red: 0.0,
+            green: 1.0,
+            blue: 2.0,
+        };
+        let interior = |id: usize, absorption: ColorSample| Interior {
+            object_id: id,
+            absorption: absorption,
+            scattering: None,
+        };
+        let interiors = [interior(1, ColorSample::WHITE), interior(2, tinted)];
+        let t = interior_transmittance(&interiors, 0.5);
+        assert_eq!(1.0, t.red);
+        assert!((t.blue - (-1.0 as SamplePrecision).exp()).abs() < 1e-12);
+        assert_eq!(0.0, interior_transmittance(&interiors, MAX_DIMENSION).blue);
+    }
+}
+
+#[cfg(test)]
+mod test_random_walk {
+    use super::*;
+    use camera::*;
+    use environment::uniform::*;
+    use hit_detection::sphere::*;
+    use std::sync::Arc;
+    use surface::subsurface::*;
+    use world::entity::*;
+
+    #[test]
+    fn long_walks_without_absorption_keep_their_light() {
+        // a few thousand interactions to cross, far more than roulette
+        // starts after
+        let material = Subsurface {
+            albedo: ColorSample::WHITE,
+            mean_free_path: ColorSample::WHITE * 0.02,
+            ior: 1.3,
+        };
+        let scene = Scene {
+            model: Arc::new(WorldEntity {
+                shape: Box::new(Sphere {
+                    center: Vec3::ZERO,
+                    radius: 1.0,
+                }),
+                material: Arc::new(material),
+                object_id: 1,
+                material_id: 1,
+                shading: None,
+            }),
+            camera: Camera::new(
+                Vec3::new(0.0, 0.0, 5.0),
+                Vec3::ZERO,
+                Vec3::new(0.0, 1.0, 0.0),
+                40.0,
+                1.0,
+                0.0,
+                5.0,
+            ),
+            environment: Arc::new(Uniform {
+                radiance: ColorSample::BLACK,
+            }),
+            lights: Vec::new(),
+            media: Vec::new(),
+            spectral: false,
+        };
+        let interior = Interior {
+            object_id: 1,
+            absorption: ColorSample::BLACK,
+            scattering: Some((ColorSample::WHITE * 50.0, HenyeyGreenstein { g: 0.0 })),
+        };
+        let ray = Ray {
+            origin: Vec3::new(0.0, 0.0, 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            wavelength: None,
+        };
+        for _ in 0..200 {
+            let hit = scene.model.hit_model(&ray, 1e-3, MAX_DIMENSION);
+            let (_, hit, weight) = random_walk(&scene, ray, hit, &interior).unwrap();
+            assert!(hit.is_some());
+            assert!((weight.red - 1.0).abs() < 1e-9);
+        }
+    }
+}
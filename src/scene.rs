@@ -0,0 +1,1498 @@
+use camera::*;
+use color::sample::*;
+use color::spectrum::*;
+use environment::environment::*;
+use environment::gradient::*;
+use environment::map::*;
+use environment::preetham::*;
+use environment::uniform::*;
+use geometry::vec3::*;
+use hit_detection::sphere::*;
+use image::read::*;
+use light::directional::*;
+use light::light::*;
+use light::point::*;
+use light::spectral::*;
+use light::spot::*;
+use medium::grid::*;
+use medium::heterogeneous::*;
+use medium::noise::*;
+use medium::phase::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use render::*;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+use surface::conductor::*;
+use surface::dielectric::*;
+use surface::dispersion::*;
+use surface::isotropic::*;
+use surface::lambertian::*;
+use surface::layered::*;
+use surface::material::*;
+use surface::metal::*;
+use surface::microfacet::*;
+use surface::principled::*;
+use surface::rough_dielectric::*;
+use surface::shading_normal::*;
+use surface::subsurface::*;
+use surface::texture::*;
+use surface::thin_film::*;
+use world::bvh::*;
+use world::constant_medium::*;
+use world::entity::*;
+use world::model::*;
+
+/// Bumped whenever `random_scene` changes the scene it builds for a seed, or
+/// rendering changes the image, so stale checkpoints are rejected.
+const SCENE_VERSION: u64 = 2;
+
+/// Everything rays are traced against.
+pub struct Scene {
+    pub model: Arc<ModelSS>,
+    pub camera: Camera,
+    pub environment: Arc<EnvironmentSS>,
+    /// Lights sampled directly at diffuse surfaces.
+    pub lights: Vec<Arc<LightSS>>,
+    /// Volumes tracked along every ray instead of being hit like surfaces.
+    pub media: Vec<HeterogeneousMedium>,
+    /// Trace wavelengths instead of RGB.
+    pub spectral: bool,
+}
+
+#[derive(Debug)]
+pub enum SceneErr {
+    Image(ReadImageFileErr),
+    Volume(ReadVolumeFileErr),
+}
+
+impl fmt::Display for SceneErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneErr::Image(ref err) => write!(f, "Image error: {}", err),
+            SceneErr::Volume(ref err) => write!(f, "Volume error: {}", err),
+        }
+    }
+}
+
+impl error::Error for SceneErr {
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            SceneErr::Image(ref err) => Some(err),
+            SceneErr::Volume(ref err) => Some(err),
+        }
+    }
+}
+
+impl From<ReadImageFileErr> for SceneErr {
+    fn from(err: ReadImageFileErr) -> SceneErr {
+        SceneErr::Image(err)
+    }
+}
+
+impl From<ReadVolumeFileErr> for SceneErr {
+    fn from(err: ReadVolumeFileErr) -> SceneErr {
+        SceneErr::Volume(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sky {
+    /// White at the horizon blending into light blue overhead, without a sun.
+    Gradient,
+    /// Preetham daylight with a matching sun. Angles are in degrees; the
+    /// azimuth turns from +x towards +z.
+    Preetham {
+        sun_elevation: Dimension,
+        sun_azimuth: Dimension,
+        turbidity: Dimension,
+        ground_albedo: Dimension,
+    },
+    /// Equirectangular HDR image, turned by `rotation` degrees around the y
+    /// axis and scaled by `intensity`. Workers load the same file name.
+    Map {
+        file_name: String,
+        rotation: Dimension,
+        intensity: Dimension,
+    },
+}
+
+/// Analytic lights added to the scene. Angles are in degrees.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneLight {
+    /// Intensity in W/sr.
+    Point {
+        position: Vec3,
+        intensity: ColorSample,
+    },
+    /// Intensity along the axis in W/sr, fading out between the inner and
+    /// outer angles.
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        intensity: ColorSample,
+        inner_angle: Dimension,
+        outer_angle: Dimension,
+    },
+    /// Irradiance in W/m² for light travelling in `direction`.
+    Directional {
+        direction: Vec3,
+        irradiance: ColorSample,
+    },
+}
+
+impl SceneLight {
+    fn light(&self) -> Arc<LightSS> {
+        match *self {
+            SceneLight::Point {
+                position,
+                intensity,
+            } => Arc::new(PointLight {
+                position: position,
+                intensity: intensity,
+            }),
+            SceneLight::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => Arc::new(SpotLight::new(
+                position,
+                direction,
+                intensity,
+                inner_angle.to_radians(),
+                outer_angle.to_radians(),
+            )),
+            SceneLight::Directional {
+                direction,
+                irradiance,
+            } => Arc::new(DirectionalLight {
+                direction: direction.unit(),
+                irradiance: irradiance,
+            }),
+        }
+    }
+}
+
+/// Participating media added to the scene.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneMedium {
+    /// Fog of constant density, in scattering events per unit distance,
+    /// filling a sphere.
+    Sphere {
+        center: Vec3,
+        radius: Dimension,
+        density: Dimension,
+        albedo: ColorSample,
+    },
+    /// Procedural cloud filling a sphere, with coefficients per unit
+    /// distance at full density and the Henyey-Greenstein asymmetry.
+    Cloud {
+        center: Vec3,
+        radius: Dimension,
+        absorption: ColorSample,
+        scattering: ColorSample,
+        anisotropy: Dimension,
+    },
+    /// Voxel densities from a vol file, with the file's box scaled and then
+    /// moved to `position`. Workers load the same file name.
+    Grid {
+        file_name: String,
+        position: Vec3,
+        scale: Dimension,
+        absorption: ColorSample,
+        scattering: ColorSample,
+        anisotropy: Dimension,
+    },
+}
+
+/// Microfacet metal for the large metal sphere, with roughnesses along and
+/// across the horizontal direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneConductor {
+    pub eta: ColorSample,
+    pub k: ColorSample,
+    pub roughness_u: Dimension,
+    pub roughness_v: Dimension,
+}
+
+/// Dielectric coat over the large diffuse and metal spheres.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneCoat {
+    pub ior: Dimension,
+    pub roughness: SceneTexture,
+    /// Absorption per unit distance inside the coat.
+    pub absorption: ColorSample,
+    pub thickness: Dimension,
+}
+
+impl SceneCoat {
+    fn over(&self, base: Arc<MaterialSS>) -> Arc<MaterialSS> {
+        Arc::new(Layered {
+            base: base,
+            ior: self.ior,
+            roughness: self.roughness.texture(),
+            absorption: self.absorption,
+            thickness: self.thickness,
+        })
+    }
+}
+
+/// Thin film on the smooth glass and the large metal sphere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneThinFilm {
+    /// Thickness in nanometers.
+    pub thickness: SceneTexture,
+    pub ior: Dimension,
+}
+
+impl SceneThinFilm {
+    fn film(&self) -> ThinFilm {
+        ThinFilm {
+            thickness: self.thickness.texture(),
+            ior: self.ior,
+        }
+    }
+}
+
+/// Translucent material for the large diffuse sphere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SceneSubsurface {
+    pub albedo: ColorSample,
+    pub mean_free_path: ColorSample,
+    pub ior: Dimension,
+}
+
+/// Detail added to the large diffuse and metal spheres by changing the
+/// normals they are shaded with.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneShading {
+    /// Tangent space normal map image. Workers load the same file name.
+    NormalMap { file_name: String },
+    /// Bumps of fractal noise with features `size` wide, up to `height`
+    /// high.
+    Bump { size: Dimension, height: Dimension },
+}
+
+/// Material parameter or color varying over a surface.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SceneTexture<T = Dimension> {
+    Constant(T),
+    /// Solid checkerboard of cubes `size` wide.
+    Checker {
+        even: T,
+        odd: T,
+        size: Dimension,
+    },
+}
+
+impl SceneTexture<Dimension> {
+    fn texture(&self) -> Box<ScalarTextureSS> {
+        match *self {
+            SceneTexture::Constant(value) => Box::new(value),
+            SceneTexture::Checker { even, odd, size } => Box::new(Checker {
+                size: size,
+                even: even,
+                odd: odd,
+            }),
+        }
+    }
+}
+
+impl SceneTexture<ColorSample> {
+    fn color_texture(&self) -> Box<ColorTextureSS> {
+        match *self {
+            SceneTexture::Constant(color) => Box::new(color),
+            SceneTexture::Checker { even, odd, size } => Box::new(Checker {
+                size: size,
+                even: even,
+                odd: odd,
+            }),
+        }
+    }
+}
+
+/// Principled material for the large diffuse sphere. See `Principled` for
+/// the parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScenePrincipled {
+    pub base_color: SceneTexture<ColorSample>,
+    pub metallic: SceneTexture,
+    pub roughness: SceneTexture,
+    pub specular: SceneTexture,
+    pub specular_tint: SceneTexture,
+    pub sheen: SceneTexture,
+    pub sheen_tint: SceneTexture,
+    pub clearcoat: SceneTexture,
+    pub clearcoat_gloss: SceneTexture,
+    pub transmission: SceneTexture,
+    pub anisotropic: SceneTexture,
+    pub ior: Dimension,
+}
+
+impl ScenePrincipled {
+    /// `base_color` with the defaults of common principled shaders for
+    /// everything else.
+    pub fn new(base_color: SceneTexture<ColorSample>) -> ScenePrincipled {
+        ScenePrincipled {
+            base_color: base_color,
+            metallic: SceneTexture::Constant(0.0),
+            roughness: SceneTexture::Constant(0.5),
+            specular: SceneTexture::Constant(0.5),
+            specular_tint: SceneTexture::Constant(0.0),
+            sheen: SceneTexture::Constant(0.0),
+            sheen_tint: SceneTexture::Constant(0.5),
+            clearcoat: SceneTexture::Constant(0.0),
+            clearcoat_gloss: SceneTexture::Constant(1.0),
+            transmission: SceneTexture::Constant(0.0),
+            anisotropic: SceneTexture::Constant(0.0),
+            ior: 1.45,
+        }
+    }
+
+    /// The scalar parameters, in the order they are serialized.
+    fn scalars(&self) -> [SceneTexture; 10] {
+        [
+            self.metallic,
+            self.roughness,
+            self.specular,
+            self.specular_tint,
+            self.sheen,
+            self.sheen_tint,
+            self.clearcoat,
+            self.clearcoat_gloss,
+            self.transmission,
+            self.anisotropic,
+        ]
+    }
+
+    fn material(&self) -> Principled {
+        Principled {
+            base_color: self.base_color.color_texture(),
+            metallic: self.metallic.texture(),
+            roughness: self.roughness.texture(),
+            specular: self.specular.texture(),
+            specular_tint: self.specular_tint.texture(),
+            sheen: self.sheen.texture(),
+            sheen_tint: self.sheen_tint.texture(),
+            clearcoat: self.clearcoat.texture(),
+            clearcoat_gloss: self.clearcoat_gloss.texture(),
+            transmission: self.transmission.texture(),
+            anisotropic: self.anisotropic.texture(),
+            ior: self.ior,
+        }
+    }
+}
+
+/// Scene choices that don't depend on the seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneOptions {
+    pub sky: Sky,
+    pub lights: Vec<SceneLight>,
+    pub media: Vec<SceneMedium>,
+    pub conductor: Option<SceneConductor>,
+    /// Roughness frosting the large glass sphere.
+    pub glass_roughness: Option<SceneTexture>,
+    /// Absorption per unit distance inside all glass.
+    pub glass_absorption: ColorSample,
+    /// Wavelength dependent refractive index of the smooth glass.
+    pub glass_dispersion: Option<Dispersion>,
+    /// Replaces the large diffuse sphere's material.
+    pub principled: Option<ScenePrincipled>,
+    pub coat: Option<SceneCoat>,
+    pub thin_film: Option<SceneThinFilm>,
+    /// Replaces the large diffuse sphere's material, uncoated.
+    pub subsurface: Option<SceneSubsurface>,
+    pub shading: Option<SceneShading>,
+    /// Render with hero wavelength sampling instead of RGB.
+    pub spectral: bool,
+    /// Spectrum emitted by the analytic lights.
+    pub light_spectrum: Illuminant,
+}
+
+impl SceneOptions {
+    pub fn new() -> SceneOptions {
+        SceneOptions {
+            sky: Sky::Gradient,
+            lights: Vec::new(),
+            media: Vec::new(),
+            conductor: None,
+            glass_roughness: None,
+            glass_absorption: ColorSample::BLACK,
+            glass_dispersion: None,
+            principled: None,
+            coat: None,
+            thin_film: None,
+            subsurface: None,
+            shading: None,
+            spectral: false,
+            light_spectrum: Illuminant::D65,
+        }
+    }
+
+    /// Files the scene is built from, read at the same paths by every
+    /// process rendering it.
+    pub fn file_names(&self) -> Vec<&str> {
+        let mut file_names = Vec::new();
+        if let Sky::Map { ref file_name, .. } = self.sky {
+            file_names.push(file_name.as_str());
+        }
+        for medium in self.media.iter() {
+            if let SceneMedium::Grid { ref file_name, .. } = *medium {
+                file_names.push(file_name.as_str());
+            }
+        }
+        if let Some(SceneShading::NormalMap { ref file_name }) = self.shading {
+            file_names.push(file_name.as_str());
+        }
+        file_names
+    }
+
+    /// Serializes the options for hashing and for sending them to workers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self.sky {
+            Sky::Gradient => bytes.push(0),
+            Sky::Preetham {
+                sun_elevation,
+                sun_azimuth,
+                turbidity,
+                ground_albedo,
+            } => {
+                bytes.push(1);
+                put_values(
+                    &mut bytes,
+                    &[sun_elevation, sun_azimuth, turbidity, ground_albedo],
+                );
+            }
+            Sky::Map {
+                ref file_name,
+                rotation,
+                intensity,
+            } => {
+                bytes.push(2);
+                put_values(&mut bytes, &[rotation, intensity]);
+                put_values(&mut bytes, &[file_name.len() as Dimension]);
+                bytes.extend_from_slice(file_name.as_bytes());
+            }
+        }
+        put_values(&mut bytes, &[self.lights.len() as Dimension]);
+        for light in self.lights.iter() {
+            match *light {
+                SceneLight::Point {
+                    position,
+                    intensity,
+                } => {
+                    bytes.push(0);
+                    put_values(&mut bytes, &[position.x, position.y, position.z]);
+                    put_values(
+                        &mut bytes,
+                        &[intensity.red, intensity.green, intensity.blue],
+                    );
+                }
+                SceneLight::Spot {
+                    position,
+                    direction,
+                    intensity,
+                    inner_angle,
+                    outer_angle,
+                } => {
+                    bytes.push(1);
+                    put_values(&mut bytes, &[position.x, position.y, position.z]);
+                    put_values(&mut bytes, &[direction.x, direction.y, direction.z]);
+                    put_values(
+                        &mut bytes,
+                        &[intensity.red, intensity.green, intensity.blue],
+                    );
+                    put_values(&mut bytes, &[inner_angle, outer_angle]);
+                }
+                SceneLight::Directional {
+                    direction,
+                    irradiance,
+                } => {
+                    bytes.push(2);
+                    put_values(&mut bytes, &[direction.x, direction.y, direction.z]);
+                    put_values(
+                        &mut bytes,
+                        &[irradiance.red, irradiance.green, irradiance.blue],
+                    );
+                }
+            }
+        }
+        put_values(&mut bytes, &[self.media.len() as Dimension]);
+        for medium in self.media.iter() {
+            match *medium {
+                SceneMedium::Sphere {
+                    center,
+                    radius,
+                    density,
+                    albedo,
+                } => {
+                    bytes.push(0);
+                    put_values(&mut bytes, &[center.x, center.y, center.z]);
+                    put_values(&mut bytes, &[radius, density]);
+                    put_values(&mut bytes, &[albedo.red, albedo.green, albedo.blue]);
+                }
+                SceneMedium::Cloud {
+                    center,
+                    radius,
+                    absorption,
+                    scattering,
+                    anisotropy,
+                } => {
+                    bytes.push(1);
+                    put_values(&mut bytes, &[center.x, center.y, center.z, radius]);
+                    put_coefficients(&mut bytes, absorption, scattering, anisotropy);
+                }
+                SceneMedium::Grid {
+                    ref file_name,
+                    position,
+                    scale,
+                    absorption,
+                    scattering,
+                    anisotropy,
+                } => {
+                    bytes.push(2);
+                    put_values(&mut bytes, &[file_name.len() as Dimension]);
+                    bytes.extend_from_slice(file_name.as_bytes());
+                    put_values(&mut bytes, &[position.x, position.y, position.z, scale]);
+                    put_coefficients(&mut bytes, absorption, scattering, anisotropy);
+                }
+            }
+        }
+        match self.conductor {
+            None => bytes.push(0),
+            Some(conductor) => {
+                bytes.push(1);
+                let (eta, k) = (conductor.eta, conductor.k);
+                put_values(&mut bytes, &[eta.red, eta.green, eta.blue]);
+                put_values(&mut bytes, &[k.red, k.green, k.blue]);
+                put_values(&mut bytes, &[conductor.roughness_u, conductor.roughness_v]);
+            }
+        }
+        match self.glass_roughness {
+            None => bytes.push(0),
+            Some(roughness) => put_texture(&mut bytes, &roughness, |value| vec![value]),
+        }
+        let absorption = self.glass_absorption;
+        put_values(
+            &mut bytes,
+            &[absorption.red, absorption.green, absorption.blue],
+        );
+        match self.glass_dispersion {
+            None => bytes.push(0),
+            Some(Dispersion::Cauchy { a, b }) => {
+                bytes.push(1);
+                put_values(&mut bytes, &[a, b]);
+            }
+            Some(Dispersion::Sellmeier { b, c }) => {
+                bytes.push(2);
+                put_values(&mut bytes, &b);
+                put_values(&mut bytes, &c);
+            }
+        }
+        match self.principled {
+            None => bytes.push(0),
+            Some(principled) => {
+                bytes.push(1);
+                put_texture(&mut bytes, &principled.base_color, |c| {
+                    vec![c.red, c.green, c.blue]
+                });
+                for scalar in principled.scalars().iter() {
+                    put_texture(&mut bytes, scalar, |value| vec![value]);
+                }
+                put_values(&mut bytes, &[principled.ior]);
+            }
+        }
+        match self.coat {
+            None => bytes.push(0),
+            Some(coat) => {
+                bytes.push(1);
+                put_values(&mut bytes, &[coat.ior]);
+                put_texture(&mut bytes, &coat.roughness, |value| vec![value]);
+                let absorption = coat.absorption;
+                put_values(
+                    &mut bytes,
+                    &[absorption.red, absorption.green, absorption.blue],
+                );
+                put_values(&mut bytes, &[coat.thickness]);
+            }
+        }
+        match self.thin_film {
+            None => bytes.push(0),
+            Some(film) => {
+                bytes.push(1);
+                put_texture(&mut bytes, &film.thickness, |value| vec![value]);
+                put_values(&mut bytes, &[film.ior]);
+            }
+        }
+        match self.subsurface {
+            None => bytes.push(0),
+            Some(subsurface) => {
+                bytes.push(1);
+                let (albedo, path) = (subsurface.albedo, subsurface.mean_free_path);
+                put_values(&mut bytes, &[albedo.red, albedo.green, albedo.blue]);
+                put_values(&mut bytes, &[path.red, path.green, path.blue]);
+                put_values(&mut bytes, &[subsurface.ior]);
+            }
+        }
+        match self.shading {
+            None => bytes.push(0),
+            Some(SceneShading::NormalMap { ref file_name }) => {
+                bytes.push(1);
+                put_values(&mut bytes, &[file_name.len() as Dimension]);
+                bytes.extend_from_slice(file_name.as_bytes());
+            }
+            Some(SceneShading::Bump { size, height }) => {
+                bytes.push(2);
+                put_values(&mut bytes, &[size, height]);
+            }
+        }
+        bytes.push(self.spectral as u8);
+        match self.light_spectrum {
+            Illuminant::E => bytes.push(0),
+            Illuminant::A => bytes.push(1),
+            Illuminant::D65 => bytes.push(2),
+            Illuminant::Blackbody(kelvin) => {
+                bytes.push(3);
+                put_values(&mut bytes, &[kelvin]);
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<SceneOptions> {
+        let mut r = ByteReader { bytes: bytes };
+        let sky = match r.byte()? {
+            0 => Sky::Gradient,
+            1 => Sky::Preetham {
+                sun_elevation: r.value()?,
+                sun_azimuth: r.value()?,
+                turbidity: r.value()?,
+                ground_albedo: r.value()?,
+            },
+            2 => {
+                let rotation = r.value()?;
+                let intensity = r.value()?;
+                let length = r.value()? as usize;
+                Sky::Map {
+                    file_name: String::from_utf8(r.take(length)?.to_vec()).ok()?,
+                    rotation: rotation,
+                    intensity: intensity,
+                }
+            }
+            _ => return None,
+        };
+        let n_lights = r.value()? as usize;
+        let mut lights = Vec::new();
+        for _ in 0..n_lights {
+            lights.push(match r.byte()? {
+                0 => SceneLight::Point {
+                    position: r.vec3()?,
+                    intensity: r.color()?,
+                },
+                1 => SceneLight::Spot {
+                    position: r.vec3()?,
+                    direction: r.vec3()?,
+                    intensity: r.color()?,
+                    inner_angle: r.value()?,
+                    outer_angle: r.value()?,
+                },
+                2 => SceneLight::Directional {
+                    direction: r.vec3()?,
+                    irradiance: r.color()?,
+                },
+                _ => return None,
+            });
+        }
+        let n_media = r.value()? as usize;
+        let mut media = Vec::new();
+        for _ in 0..n_media {
+            media.push(match r.byte()? {
+                0 => SceneMedium::Sphere {
+                    center: r.vec3()?,
+                    radius: r.value()?,
+                    density: r.value()?,
+                    albedo: r.color()?,
+                },
+                1 => SceneMedium::Cloud {
+                    center: r.vec3()?,
+                    radius: r.value()?,
+                    absorption: r.color()?,
+                    scattering: r.color()?,
+                    anisotropy: r.value()?,
+                },
+                2 => {
+                    let length = r.value()? as usize;
+                    SceneMedium::Grid {
+                        file_name: String::from_utf8(r.take(length)?.to_vec()).ok()?,
+                        position: r.vec3()?,
+                        scale: r.value()?,
+                        absorption: r.color()?,
+                        scattering: r.color()?,
+                        anisotropy: r.value()?,
+                    }
+                }
+                _ => return None,
+            });
+        }
+        let conductor = match r.byte()? {
+            0 => None,
+            1 => Some(SceneConductor {
+                eta: r.color()?,
+                k: r.color()?,
+                roughness_u: r.value()?,
+                roughness_v: r.value()?,
+            }),
+            _ => return None,
+        };
+        let glass_roughness = match r.byte()? {
+            0 => None,
+            tag => Some(r.texture(tag, ByteReader::value)?),
+        };
+        let glass_absorption = r.color()?;
+        let glass_dispersion = match r.byte()? {
+            0 => None,
+            1 => Some(Dispersion::Cauchy {
+                a: r.value()?,
+                b: r.value()?,
+            }),
+            2 => Some(Dispersion::Sellmeier {
+                b: [r.value()?, r.value()?, r.value()?],
+                c: [r.value()?, r.value()?, r.value()?],
+            }),
+            _ => return None,
+        };
+        let principled = match r.byte()? {
+            0 => None,
+            1 => {
+                let tag = r.byte()?;
+                let base_color = r.texture(tag, ByteReader::color)?;
+                let mut scalars = Vec::new();
+                for _ in 0..10 {
+                    let tag = r.byte()?;
+                    scalars.push(r.texture(tag, ByteReader::value)?);
+                }
+                Some(ScenePrincipled {
+                    base_color: base_color,
+                    metallic: scalars[0],
+                    roughness: scalars[1],
+                    specular: scalars[2],
+                    specular_tint: scalars[3],
+                    sheen: scalars[4],
+                    sheen_tint: scalars[5],
+                    clearcoat: scalars[6],
+                    clearcoat_gloss: scalars[7],
+                    transmission: scalars[8],
+                    anisotropic: scalars[9],
+                    ior: r.value()?,
+                })
+            }
+            _ => return None,
+        };
+        let coat = match r.byte()? {
+            0 => None,
+            1 => {
+                let ior = r.value()?;
+                let tag = r.byte()?;
+                Some(SceneCoat {
+                    ior: ior,
+                    roughness: r.texture(tag, ByteReader::value)?,
+                    absorption: r.color()?,
+                    thickness: r.value()?,
+                })
+            }
+            _ => return None,
+        };
+        let thin_film = match r.byte()? {
+            0 => None,
+            1 => {
+                let tag = r.byte()?;
+                Some(SceneThinFilm {
+                    thickness: r.texture(tag, ByteReader::value)?,
+                    ior: r.value()?,
+                })
+            }
+            _ => return None,
+        };
+        let subsurface = match r.byte()? {
+            0 => None,
+            1 => Some(SceneSubsurface {
+                albedo: r.color()?,
+                mean_free_path: r.color()?,
+                ior: r.value()?,
+            }),
+            _ => return None,
+        };
+        let shading = match r.byte()? {
+            0 => None,
+            1 => {
+                let length = r.value()? as usize;
+                Some(SceneShading::NormalMap {
+                    file_name: String::from_utf8(r.take(length)?.to_vec()).ok()?,
+                })
+            }
+            2 => Some(SceneShading::Bump {
+                size: r.value()?,
+                height: r.value()?,
+            }),
+            _ => return None,
+        };
+        let spectral = match r.byte()? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let light_spectrum = match r.byte()? {
+            0 => Illuminant::E,
+            1 => Illuminant::A,
+            2 => Illuminant::D65,
+            3 => Illuminant::Blackbody(r.value()?),
+            _ => return None,
+        };
+        if !r.bytes.is_empty() {
+            return None;
+        }
+        Some(SceneOptions {
+            sky: sky,
+            lights: lights,
+            media: media,
+            conductor: conductor,
+            glass_roughness: glass_roughness,
+            glass_absorption: glass_absorption,
+            glass_dispersion: glass_dispersion,
+            principled: principled,
+            coat: coat,
+            thin_film: thin_film,
+            subsurface: subsurface,
+            shading: shading,
+            spectral: spectral,
+            light_spectrum: light_spectrum,
+        })
+    }
+}
+
+fn put_values(bytes: &mut Vec<u8>, values: &[Dimension]) {
+    for value in values.iter() {
+        bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+}
+
+/// Writes a texture tagged 1 when constant and 2 for a checkerboard, with
+/// `values` turning its values into numbers.
+fn put_texture<T: Copy, F: Fn(T) -> Vec<Dimension>>(
+    bytes: &mut Vec<u8>,
+    texture: &SceneTexture<T>,
+    values: F,
+) {
+    match *texture {
+        SceneTexture::Constant(value) => {
+            bytes.push(1);
+            put_values(bytes, &values(value));
+        }
+        SceneTexture::Checker { even, odd, size } => {
+            bytes.push(2);
+            put_values(bytes, &values(even));
+            put_values(bytes, &values(odd));
+            put_values(bytes, &[size]);
+        }
+    }
+}
+
+/// Writes the coefficients of a medium.
+fn put_coefficients(
+    bytes: &mut Vec<u8>,
+    absorption: ColorSample,
+    scattering: ColorSample,
+    anisotropy: Dimension,
+) {
+    put_values(bytes, &[absorption.red, absorption.green, absorption.blue]);
+    put_values(bytes, &[scattering.red, scattering.green, scattering.blue]);
+    put_values(bytes, &[anisotropy]);
+}
+
+/// Reads back what `SceneOptions::to_bytes` wrote.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if n > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn value(&mut self) -> Option<Dimension> {
+        let mut word = [0u8; 8];
+        word.copy_from_slice(self.take(8)?);
+        Some(f64::from_bits(u64::from_le_bytes(word)))
+    }
+
+    fn vec3(&mut self) -> Option<Vec3> {
+        Some(Vec3::new(self.value()?, self.value()?, self.value()?))
+    }
+
+    fn color(&mut self) -> Option<ColorSample> {
+        Some(ColorSample {
+            red: self.value()?,
+            green: self.value()?,
+            blue: self.value()?,
+        })
+    }
+
+    /// Reads a texture written by `put_texture` after its `tag`.
+    fn texture<T, F: Fn(&mut Self) -> Option<T>>(
+        &mut self,
+        tag: u8,
+        read: F,
+    ) -> Option<SceneTexture<T>> {
+        match tag {
+            1 => Some(SceneTexture::Constant(read(self)?)),
+            2 => Some(SceneTexture::Checker {
+                even: read(self)?,
+                odd: read(self)?,
+                size: self.value()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+fn environment(sky: &Sky) -> Result<(Arc<EnvironmentSS>, Vec<Arc<LightSS>>), ReadImageFileErr> {
+    Ok(match *sky {
+        Sky::Gradient => (Arc::new(Gradient), Vec::new()),
+        Sky::Preetham {
+            sun_elevation,
+            sun_azimuth,
+            turbidity,
+            ground_albedo,
+        } => {
+            let (elevation, azimuth) = (sun_elevation.to_radians(), sun_azimuth.to_radians());
+            let sun_direction = Vec3::new(
+                elevation.cos() * azimuth.cos(),
+                elevation.sin(),
+                elevation.cos() * azimuth.sin(),
+            );
+            let sky = PreethamSky::new(sun_direction, turbidity, ground_albedo);
+            let sun: Arc<LightSS> = Arc::new(sky.sun());
+            (Arc::new(sky), vec![sun])
+        }
+        Sky::Map {
+            ref file_name,
+            rotation,
+            intensity,
+        } => {
+            let image = load_color_buffer(file_name)?;
+            let map: Arc<LightSS> = Arc::new(EnvironmentMap::new(
+                &image,
+                rotation.to_radians(),
+                intensity,
+            ));
+            // escaping rays see the map as a light
+            let environment = Uniform {
+                radiance: ColorSample::BLACK,
+            };
+            (Arc::new(environment), vec![map])
+        }
+    })
+}
+
+/// Builds the random sphere field scene. The same seed always produces the
+/// same scene. Fails when an environment map, normal map or volume can't be
+/// read.
+pub fn random_scene(
+    seed: u64,
+    imgx: usize,
+    imgy: usize,
+    options: &SceneOptions,
+) -> Result<Scene, SceneErr> {
+    let mut rng = seeded_rng(seed);
+    let look_from = Vec3::new(20.0, 1.9, 5.0);
+    let look_at = Vec3::new(0.0, 0.5, 0.0);
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let vert_fov_degrees = 10.0;
+    let aspect = imgx as Dimension / imgy as Dimension;
+    let aperture = 0.04;
+    let distance_to_focus = (look_from - look_at).length() - 2.0;
+    let camera = Camera::new(
+        look_from,
+        look_at,
+        up,
+        vert_fov_degrees,
+        aspect,
+        aperture,
+        distance_to_focus,
+    );
+    let shading: Option<Arc<ShadingNormalSS>> = match options.shading {
+        None => None,
+        Some(SceneShading::NormalMap { ref file_name }) => {
+            Some(Arc::new(NormalMap::new(&load_data_buffer(file_name)?)))
+        }
+        Some(SceneShading::Bump { size, height }) => Some(Arc::new(BumpMap {
+            height: Box::new(Noise { size: size }),
+            scale: height,
+        })),
+    };
+    // ids start at one; zero is the background in id outputs
+    let mut object_ids = 1..;
+    let mut material_ids = 1..;
+    let glass: Arc<MaterialSS> = Arc::new(Dielectric {
+        ref_idx: 1.5,
+        absorption: options.glass_absorption,
+        dispersion: options.glass_dispersion,
+        thin_film: options.thin_film.map(|film| film.film()),
+    });
+    let glass_id = material_ids.next().unwrap();
+    let mut spheres: Vec<Sphere> = Vec::new();
+    let mut center_spheres: Vec<Box<ModelSS>> = Vec::new();
+    // floor
+    let sphere = Sphere {
+        center: Vec3::new(0.0, -1e12, 0.0),
+        radius: 1e12,
+    };
+    spheres.push(sphere);
+    let floor = Box::new(WorldEntity {
+        shape: Box::new(sphere),
+        material: Arc::new(Lambertian {
+            albedo: ColorSample {
+                red: 0.5,
+                green: 0.5,
+                blue: 0.5,
+            },
+        }),
+        object_id: object_ids.next().unwrap(),
+        material_id: material_ids.next().unwrap(),
+        shading: None,
+    });
+    // dielectric
+    let sphere = Sphere {
+        center: Vec3::new(0.0, 1.0, 0.0),
+        radius: 1.0,
+    };
+    spheres.push(sphere);
+    let (material, material_id): (Arc<MaterialSS>, usize) = match options.glass_roughness {
+        Some(roughness) => (
+            Arc::new(RoughDielectric {
+                ref_idx: 1.5,
+                roughness: roughness.texture(),
+                absorption: options.glass_absorption,
+            }),
+            material_ids.next().unwrap(),
+        ),
+        None => (glass.clone(), glass_id),
+    };
+    center_spheres.push(Box::new(WorldEntity {
+        shape: Box::new(sphere),
+        material: material,
+        object_id: object_ids.next().unwrap(),
+        material_id: material_id,
+        shading: None,
+    }));
+    let coated = |base: Arc<MaterialSS>| match options.coat {
+        Some(coat) => coat.over(base),
+        None => base,
+    };
+    // lambertian
+    let sphere = Sphere {
+        center: Vec3::new(-4.0, 1.0, 0.0),
+        radius: 1.0,
+    };
+    spheres.push(sphere);
+    center_spheres.push(Box::new(WorldEntity {
+        shape: Box::new(sphere),
+        material: match options.subsurface {
+            // a coat would keep paths from entering
+            Some(subsurface) => Arc::new(Subsurface {
+                albedo: subsurface.albedo,
+                mean_free_path: subsurface.mean_free_path,
+                ior: subsurface.ior,
+            }),
+            None => coated(match options.principled {
+                Some(principled) => Arc::new(principled.material()),
+                None => Arc::new(Lambertian {
+                    albedo: ColorSample {
+                        red: 0.4,
+                        green: 0.2,
+                        blue: 0.1,
+                    },
+                }),
+            }),
+        },
+        object_id: object_ids.next().unwrap(),
+        material_id: material_ids.next().unwrap(),
+        shading: shading.clone(),
+    }));
+    // metal
+    let sphere = Sphere {
+        center: Vec3::new(4.0, 1.0, 0.0),
+        radius: 1.0,
+    };
+    spheres.push(sphere);
+    center_spheres.push(Box::new(WorldEntity {
+        shape: Box::new(sphere),
+        material: coated(match options.conductor {
+            Some(conductor) => Arc::new(Conductor {
+                eta: conductor.eta,
+                k: conductor.k,
+                distribution: Ggx::from_roughness(conductor.roughness_u, conductor.roughness_v),
+                thin_film: options.thin_film.map(|film| film.film()),
+            }),
+            None => Arc::new(Metal::new(
+                ColorSample {
+                    red: 0.7,
+                    green: 0.6,
+                    blue: 0.5,
+                },
+                0.0,
+            )),
+        }),
+        object_id: object_ids.next().unwrap(),
+        material_id: material_ids.next().unwrap(),
+        shading: shading.clone(),
+    }));
+    // random sphere field
+    let mut sphere_field: Vec<Box<ModelSS>> = Vec::new();
+    for a in -11..=11 {
+        for b in -11..=11 {
+            let (material, material_id): (Arc<MaterialSS>, usize) =
+                match rng.gen_range::<Dimension>(0.0, 1.0) {
+                    v if v < 0.8 => (
+                        Arc::new(Lambertian {
+                            albedo: ColorSample {
+                                red: rng.gen_range::<Dimension>(0.0, 1.0)
+                                    * rng.gen_range::<Dimension>(0.0, 1.0),
+                                green: rng.gen_range::<Dimension>(0.0, 1.0)
+                                    * rng.gen_range::<Dimension>(0.0, 1.0),
+                                blue: rng.gen_range::<Dimension>(0.0, 1.0)
+                                    * rng.gen_range::<Dimension>(0.0, 1.0),
+                            },
+                        }),
+                        material_ids.next().unwrap(),
+                    ),
+                    v if v < 0.95 => (
+                        Arc::new(Metal::new(
+                            ColorSample {
+                                red: rng.gen_range::<Dimension>(0.5, 1.0),
+                                green: rng.gen_range::<Dimension>(0.5, 1.0),
+                                blue: rng.gen_range::<Dimension>(0.5, 1.0),
+                            },
+                            rng.gen_range::<Dimension>(0.0, 0.5),
+                        )),
+                        material_ids.next().unwrap(),
+                    ),
+                    _ => (glass.clone(), glass_id),
+                };
+            let mut new_sphere: Option<Sphere> = None;
+            for _ in 0..1000 {
+                let sphere = Sphere {
+                    center: Vec3 {
+                        x: a as Dimension + rng.gen_range::<Dimension>(0.0, 0.9),
+                        y: 0.2,
+                        z: b as Dimension + rng.gen_range::<Dimension>(0.0, 0.9),
+                    },
+                    radius: 0.2,
+                };
+                if !spheres.iter().any(|&s| s.intersects(&sphere)) {
+                    spheres.push(sphere);
+                    new_sphere = Some(sphere);
+                    break;
+                }
+            }
+            sphere_field.push(Box::new(WorldEntity {
+                shape: Box::new(new_sphere.expect("unable to place sphere")),
+                material: material,
+                object_id: object_ids.next().unwrap(),
+                material_id: material_id,
+                shading: None,
+            }));
+        }
+    }
+    let sphere_field =
+        Tree::from_list_on_dimensions(&mut sphere_field, &[SplitDim::X, SplitDim::Z]);
+    let center_spheres = Tree::from_list_on_dimensions(&mut center_spheres, &[SplitDim::X]);
+    let mut scene: Vec<Box<ModelSS>> = vec![floor, sphere_field, center_spheres];
+    let mut media = Vec::new();
+    for medium in options.media.iter() {
+        match *medium {
+            SceneMedium::Sphere {
+                center,
+                radius,
+                density,
+                albedo,
+            } => scene.push(Box::new(ConstantMedium {
+                boundary: Box::new(Sphere {
+                    center: center,
+                    radius: radius,
+                }),
+                density: density,
+                phase_function: Arc::new(Isotropic { albedo: albedo }),
+                object_id: object_ids.next().unwrap(),
+                material_id: material_ids.next().unwrap(),
+            })),
+            SceneMedium::Cloud {
+                center,
+                radius,
+                absorption,
+                scattering,
+                anisotropy,
+            } => media.push(HeterogeneousMedium {
+                density: Box::new(NoiseCloud {
+                    center: center,
+                    radius: radius,
+                }),
+                absorption: absorption,
+                scattering: scattering,
+                phase_function: HenyeyGreenstein { g: anisotropy },
+            }),
+            SceneMedium::Grid {
+                ref file_name,
+                position,
+                scale,
+                absorption,
+                scattering,
+                anisotropy,
+            } => {
+                let mut grid = load_vol(file_name)?;
+                grid.place(position, scale);
+                media.push(HeterogeneousMedium {
+                    density: Box::new(grid),
+                    absorption: absorption,
+                    scattering: scattering,
+                    phase_function: HenyeyGreenstein { g: anisotropy },
+                })
+            }
+        }
+    }
+    let model = Arc::from(Tree::from_list_on_dimensions(&mut scene, &[SplitDim::Y]));
+    let (environment, mut lights) = environment(&options.sky)?;
+    let light_spectrum = match options.light_spectrum {
+        // the working space white, which lights already are
+        Illuminant::D65 => None,
+        illuminant => Some(IlluminantSpectrum::new(illuminant)),
+    };
+    lights.extend(options.lights.iter().map(|light| match light_spectrum {
+        Some(spectrum) => Arc::new(SpectralLight {
+            light: light.light(),
+            spectrum: spectrum,
+        }),
+        None => light.light(),
+    }));
+    Ok(Scene {
+        model: model,
+        camera: camera,
+        environment: environment,
+        lights: lights,
+        media: media,
+        spectral: options.spectral,
+    })
+}
+
+/// Identifies the rendered image: the scene seed, its version, the image
+/// dimensions, the scene options, the contents of the files they name and the
+/// sample clamp, since clamped and unclamped samples must not be mixed. Uses
+/// FNV-1a so the value is stable across builds.
+pub fn scene_hash(
+    seed: u64,
+    imgx: usize,
+    imgy: usize,
+    options: &SceneOptions,
+    clamp: Option<SampleClamp>,
+) -> u64 {
+    let mut bytes = Vec::new();
+    for value in [SCENE_VERSION, seed, imgx as u64, imgy as u64].iter() {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend(options.to_bytes());
+    match clamp {
+        None => bytes.push(0),
+        Some(clamp) => {
+            bytes.push(if clamp.indirect_only { 2 } else { 1 });
+            bytes.extend_from_slice(&clamp.max.to_bits().to_le_bytes());
+        }
+    }
+    let mut hash = fnv1a(0xcbf2_9ce4_8422_2325, &bytes);
+    for file_name in options.file_names() {
+        // unreadable files fail the render itself
+        let contents = fs::read(file_name).unwrap_or_default();
+        hash = fnv1a(hash, &(contents.len() as u64).to_le_bytes());
+        hash = fnv1a(hash, &contents);
+    }
+    hash
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn seeded_rng(seed: u64) -> StdRng {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    StdRng::from_seed(bytes)
+}
+
+#[cfg(test)]
+mod test_scene_options {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn clamping_changes_the_hash() {
+        let options = SceneOptions::new();
+        let clamp = |max, indirect_only| {
+            Some(SampleClamp {
+                max: max,
+                indirect_only: indirect_only,
+            })
+        };
+        let hashes = [
+            scene_hash(1, 4, 4, &options, None),
+            scene_hash(1, 4, 4, &options, clamp(10.0, false)),
+            scene_hash(1, 4, 4, &options, clamp(10.0, true)),
+            scene_hash(1, 4, 4, &options, clamp(20.0, false)),
+        ];
+        for (i, a) in hashes.iter().enumerate() {
+            for b in hashes[i + 1..].iter() {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn file_contents_change_the_hash() {
+        let file_name = env::temp_dir()
+            .join(format!("scene-hash-{}.png", process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut options = SceneOptions::new();
+        options.shading = Some(SceneShading::NormalMap {
+            file_name: file_name.clone(),
+        });
+        fs::write(&file_name, b"first").unwrap();
+        let first = scene_hash(1, 4, 4, &options, None);
+        assert_eq!(first, scene_hash(1, 4, 4, &options, None));
+        fs::write(&file_name, b"second").unwrap();
+        assert_ne!(first, scene_hash(1, 4, 4, &options, None));
+        fs::remove_file(&file_name).unwrap();
+    }
+
+    #[test]
+    fn round_trip_through_bytes() {
+        let options = SceneOptions {
+            sky: Sky::Map {
+                file_name: "sky.hdr".to_string(),
+                rotation: 90.0,
+                intensity: 2.0,
+            },
+            lights: vec![
+                SceneLight::Point {
+                    position: Vec3::new(1.0, 2.0, 3.0),
+                    intensity: ColorSample::WHITE,
+                },
+                SceneLight::Spot {
+                    position: Vec3::new(1.0, 2.0, 3.0),
+                    direction: Vec3::new(0.0, -1.0, 0.0),
+                    intensity: ColorSample::WHITE,
+                    inner_angle: 10.0,
+                    outer_angle: 20.0,
+                },
+                SceneLight::Directional {
+                    direction: Vec3::new(0.0, -1.0, 0.0),
+                    irradiance: ColorSample::WHITE,
+                },
+            ],
+            media: vec![
+                SceneMedium::Sphere {
+                    center: Vec3::new(0.0, 1.0, 0.0),
+                    radius: 2.0,
+                    density: 0.5,
+                    albedo: ColorSample::WHITE,
+                },
+                SceneMedium::Cloud {
+                    center: Vec3::new(0.0, 3.0, 0.0),
+                    radius: 2.0,
+                    absorption: ColorSample::BLACK,
+                    scattering: ColorSample::WHITE,
+                    anisotropy: 0.8,
+                },
+                SceneMedium::Grid {
+                    file_name: "smoke.vol".to_string(),
+                    position: Vec3::new(1.0, 0.0, 0.0),
+                    scale: 0.5,
+                    absorption: ColorSample::WHITE,
+                    scattering: ColorSample::WHITE,
+                    anisotropy: -0.2,
+                },
+            ],
+            conductor: Some(SceneConductor {
+                eta: ColorSample::WHITE,
+                k: ColorSample::BLACK,
+                roughness_u: 0.2,
+                roughness_v: 0.4,
+            }),
+            glass_roughness: Some(SceneTexture::Checker {
+                even: 0.1,
+                odd: 0.6,
+                size: 0.25,
+            }),
+            glass_absorption: ColorSample {
+                red: 0.1,
+                green: 0.2,
+                blue: 0.3,
+            },
+            glass_dispersion: Dispersion::by_name("bk7"),
+            principled: Some(ScenePrincipled {
+                base_color: SceneTexture::Checker {
+                    even: ColorSample::WHITE,
+                    odd: ColorSample::BLACK,
+                    size: 0.5,
+                },
+                clearcoat: SceneTexture::Constant(1.0),
+                ..ScenePrincipled::new(SceneTexture::Constant(ColorSample::WHITE))
+            }),
+            coat: Some(SceneCoat {
+                ior: 1.5,
+                roughness: SceneTexture::Constant(0.1),
+                absorption: ColorSample::BLACK,
+                thickness: 0.01,
+            }),
+            thin_film: Some(SceneThinFilm {
+                thickness: SceneTexture::Checker {
+                    even: 300.0,
+                    odd: 500.0,
+                    size: 0.5,
+                },
+                ior: 1.33,
+            }),
+            subsurface: Some(SceneSubsurface {
+                albedo: ColorSample::WHITE * 0.9,
+                mean_free_path: ColorSample::WHITE * 0.1,
+                ior: 1.3,
+            }),
+            shading: Some(SceneShading::NormalMap {
+                file_name: "bricks.png".to_string(),
+            }),
+            spectral: true,
+            light_spectrum: Illuminant::Blackbody(3200.0),
+        };
+        let bytes = options.to_bytes();
+        assert_eq!(Some(options), SceneOptions::from_bytes(&bytes));
+        assert_eq!(None, SceneOptions::from_bytes(&bytes[..bytes.len() - 1]));
+    }
+}
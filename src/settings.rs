@@ -0,0 +1,638 @@
+use color::aov::*;
+use color::sample::*;
+use color::spectrum::*;
+use geometry::vec3::*;
+use image::color_space::*;
+use image::exr::*;
+use image::tone_map::*;
+use render::*;
+use scene::*;
+use std::error;
+use std::fmt;
+use std::time::Duration;
+use surface::conductor::*;
+use surface::dispersion::*;
+use surface::material::*;
+use surface::subsurface::*;
+
+const DEFAULT_OUTPUTS: [&str; 2] = ["images/012-random-scene.png", "images/012-random-scene.exr"];
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render,
+    /// Combines partial renders saved as checkpoints into one image.
+    Merge,
+    /// Hands out tiles to worker processes connecting over TCP.
+    Coordinator,
+    /// Renders tiles for the coordinator at `address`.
+    Worker,
+}
+
+pub struct Settings {
+    /// Address the coordinator listens on, or the worker connects to.
+    pub address: String,
+    /// Auxiliary outputs and the files they are written to.
+    pub aovs: Vec<(Aov, String)>,
+    /// Rays per pixel for the auxiliary outputs.
+    pub aov_samples: usize,
+    pub checkpoint: Option<String>,
+    /// Per-sample radiance limit, off by default to keep renders unbiased.
+    pub clamp: Option<SampleClamp>,
+    pub command: Command,
+    /// Denoise image outputs, guided by the auxiliary outputs.
+    pub denoise: bool,
+    pub display: DisplayTransform,
+    pub exr: ExrFormat,
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    /// Standard deviations above its neighbours at which a pixel is replaced
+    /// as a firefly.
+    pub outlier_threshold: Option<SamplePrecision>,
+    /// How often outputs and the checkpoint are written while rendering.
+    pub progress_interval: Duration,
+    pub resume: bool,
+    pub samples: usize,
+    pub scene: SceneOptions,
+    pub seed: Option<u64>,
+    pub threads: usize,
+    /// Worker processes the coordinator starts on this machine.
+    pub local_workers: usize,
+    pub worker_timeout: Duration,
+}
+
+#[derive(Debug)]
+pub enum SettingsErr {
+    MissingValue(String),
+    InvalidValue(String, String),
+    UnknownOption(String),
+}
+
+impl fmt::Display for SettingsErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingsErr::MissingValue(ref option) => write!(f, "Missing value for {}", option),
+            SettingsErr::InvalidValue(ref option, ref value) => {
+                write!(f, "Invalid value for {}: {}", option, value)
+            }
+            SettingsErr::UnknownOption(ref option) => write!(f, "Unknown option: {}", option),
+        }
+    }
+}
+
+impl error::Error for SettingsErr {}
+
+impl Settings {
+    /// Parses command line arguments, excluding the program name. An optional
+    /// leading command is followed by options. Other arguments are output file
+    /// names when rendering and input checkpoints when merging.
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Settings, SettingsErr> {
+        let mut settings = Settings {
+            address: "127.0.0.1:7878".to_string(),
+            aovs: Vec::new(),
+            aov_samples: 16,
+            checkpoint: None,
+            clamp: None,
+            command: Command::Render,
+            denoise: false,
+            display: DisplayTransform::new(),
+            exr: ExrFormat::new(),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            outlier_threshold: None,
+            progress_interval: Duration::from_secs(30),
+            resume: false,
+            samples: 1000,
+            scene: SceneOptions::new(),
+            seed: None,
+            threads: 15,
+            local_workers: 0,
+            worker_timeout: Duration::from_secs(300),
+        };
+        let mut indirect_only = false;
+        let mut sky = "gradient".to_string();
+        let mut sun_elevation = 40.0;
+        let mut sun_azimuth = 150.0;
+        let mut turbidity = 3.0;
+        let mut ground_albedo = 0.3;
+        let mut environment_map = None;
+        let mut environment_rotation = 0.0;
+        let mut environment_intensity = 1.0;
+        let mut conductor_ior = None;
+        let mut conductor_roughness = None;
+        let mut args = args.peekable();
+        let command = match args.peek().map(|arg| arg.as_str()) {
+            Some("merge") => Some(Command::Merge),
+            Some("coordinator") => Some(Command::Coordinator),
+            Some("worker") => Some(Command::Worker),
+            _ => None,
+        };
+        if let Some(command) = command {
+            args.next();
+            settings.command = command;
+        }
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--aov" => {
+                    let v = value(&arg, args.next())?;
+                    let aov = {
+                        let mut parts = v.splitn(2, '=');
+                        match (parts.next().and_then(Aov::by_name), parts.next()) {
+                            (Some(aov), Some(file_name)) if !file_name.is_empty() => {
+                                Some((aov, file_name.to_string()))
+                            }
+                            _ => None,
+                        }
+                    };
+                    settings
+                        .aovs
+                        .push(aov.ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), v))?);
+                }
+                "--aov-samples" => settings.aov_samples = parse_value(&arg, args.next())?,
+                "--bump" => {
+                    let v = parse_values(&arg, args.next(), 2)?;
+                    if !(v[0] > 0.0) {
+                        return Err(SettingsErr::InvalidValue(arg, v[0].to_string()));
+                    }
+                    settings.scene.shading = Some(SceneShading::Bump {
+                        size: v[0],
+                        height: v[1],
+                    });
+                }
+                "--checkpoint" => settings.checkpoint = Some(value(&arg, args.next())?),
+                "--checkpoint-interval" => {
+                    settings.progress_interval =
+                        Duration::from_secs(parse_value(&arg, args.next())?)
+                }
+                "--clamp-max" => {
+                    settings.clamp = Some(SampleClamp {
+                        max: parse_value(&arg, args.next())?,
+                        indirect_only: false,
+                    });
+                }
+                "--clamp-indirect" => indirect_only = true,
+                "--coat" => {
+                    // optionally the transmittance straight through the coat
+                    let v = value(&arg, args.next())?;
+                    let c = match v.split(',').count() {
+                        2 => parse_values(&arg, Some(v.clone()), 2)?,
+                        _ => parse_values(&arg, Some(v.clone()), 6)?,
+                    };
+                    let absorption = match c.len() {
+                        6 if c[2..5].iter().all(|t| *t > 0.0 && *t <= 1.0) && c[5] > 0.0 => {
+                            absorption_from_transmittance(color(&c[2..5]), c[5])
+                        }
+                        2 => ColorSample::BLACK,
+                        _ => return Err(SettingsErr::InvalidValue(arg, v)),
+                    };
+                    if !(c[0] > 0.0 && c[1] >= 0.0 && c[1] <= 1.0) {
+                        return Err(SettingsErr::InvalidValue(arg, v));
+                    }
+                    settings.scene.coat = Some(SceneCoat {
+                        ior: c[0],
+                        roughness: SceneTexture::Constant(c[1]),
+                        absorption: absorption,
+                        thickness: c.get(5).cloned().unwrap_or(0.0),
+                    });
+                }
+                "--conductor" => {
+                    let name = value(&arg, args.next())?;
+                    conductor_ior = Some(
+                        Conductor::ior_by_name(&name)
+                            .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?,
+                    );
+                }
+                "--conductor-ior" => {
+                    let v = parse_values(&arg, args.next(), 6)?;
+                    conductor_ior = Some((color(&v[..3]), color(&v[3..])));
+                }
+                "--conductor-roughness" => {
+                    let v = value(&arg, args.next())?;
+                    let roughness = match v.find(',') {
+                        Some(_) => parse_values(&arg, Some(v.clone()), 2)?,
+                        None => vec![parse_value(&arg, Some(v.clone()))?; 2],
+                    };
+                    if roughness.iter().any(|r| !(*r >= 0.0 && *r <= 1.0)) {
+                        return Err(SettingsErr::InvalidValue(arg, v));
+                    }
+                    conductor_roughness = Some((roughness[0], roughness[1]));
+                }
+                "--denoise" => settings.denoise = true,
+                "--environment-intensity" => {
+                    environment_intensity = parse_value(&arg, args.next())?
+                }
+                "--environment-map" => {
+                    environment_map = Some(value(&arg, args.next())?);
+                    sky = "map".to_string();
+                }
+                "--environment-rotation" => environment_rotation = parse_value(&arg, args.next())?,
+                "--directional-light" => {
+                    let v = parse_values(&arg, args.next(), 6)?;
+                    settings.scene.lights.push(SceneLight::Directional {
+                        direction: Vec3::new(v[0], v[1], v[2]),
+                        irradiance: color(&v[3..]),
+                    });
+                }
+                "--exr-compression" => {
+                    let name = value(&arg, args.next())?;
+                    settings.exr.compression = ExrCompression::by_name(&name)
+                        .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?;
+                }
+                "--exr-float" => settings.exr.pixel_type = ExrPixelType::Float,
+                "--exposure" => settings.display.exposure = parse_value(&arg, args.next())?,
+                "--listen" => settings.address = value(&arg, args.next())?,
+                "--glass-cauchy" => {
+                    let v = parse_values(&arg, args.next(), 2)?;
+                    settings.scene.glass_dispersion = Some(Dispersion::Cauchy { a: v[0], b: v[1] });
+                }
+                "--glass-dispersion" => {
+                    let name = value(&arg, args.next())?;
+                    settings.scene.glass_dispersion = Some(
+                        Dispersion::by_name(&name)
+                            .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?,
+                    );
+                }
+                "--glass-color" => {
+                    // transmittance after the given distance inside
+                    let v = parse_values(&arg, args.next(), 4)?;
+                    if v[..3].iter().any(|c| !(*c > 0.0 && *c <= 1.0)) || !(v[3] > 0.0) {
+                        let values = v.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                        return Err(SettingsErr::InvalidValue(arg, values.join(",")));
+                    }
+                    settings.scene.glass_absorption =
+                        absorption_from_transmittance(color(&v[..3]), v[3]);
+                }
+                "--glass-roughness" => {
+                    let v = value(&arg, args.next())?;
+                    let texture = match v.find(',') {
+                        Some(_) => {
+                            let t = parse_values(&arg, Some(v.clone()), 3)?;
+                            SceneTexture::Checker {
+                                even: t[0],
+                                odd: t[1],
+                                size: t[2],
+                            }
+                        }
+                        None => SceneTexture::Constant(parse_value(&arg, Some(v.clone()))?),
+                    };
+                    let in_range = |r: Dimension| r >= 0.0 && r <= 1.0;
+                    let valid = match texture {
+                        SceneTexture::Constant(r) => in_range(r),
+                        SceneTexture::Checker { even, odd, size } => {
+                            in_range(even) && in_range(odd) && size > 0.0
+                        }
+                    };
+                    if !valid {
+                        return Err(SettingsErr::InvalidValue(arg, v));
+                    }
+                    settings.scene.glass_roughness = Some(texture);
+                }
+                "--ground-albedo" => ground_albedo = parse_value(&arg, args.next())?,
+                "--light-spectrum" => {
+                    let name = value(&arg, args.next())?;
+                    settings.scene.light_spectrum = Illuminant::by_name(&name)
+                        .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?;
+                }
+                "--local-workers" => settings.local_workers = parse_value(&arg, args.next())?,
+                "--medium-cloud" => {
+                    let values = value(&arg, args.next())?;
+                    let v = parse_values(&arg, Some(values.clone()), 11)?;
+                    if !(v[3] > 0.0 && v[4..10].iter().all(|&c| c >= 0.0)) {
+                        return Err(SettingsErr::InvalidValue(arg, values));
+                    }
+                    settings.scene.media.push(SceneMedium::Cloud {
+                        center: Vec3::new(v[0], v[1], v[2]),
+                        radius: v[3],
+                        absorption: color(&v[4..7]),
+                        scattering: color(&v[7..10]),
+                        anisotropy: anisotropy(&arg, v[10])?,
+                    });
+                }
+                "--medium-grid" => {
+                    // the file name comes first and may itself contain commas
+                    let values = value(&arg, args.next())?;
+                    let mut parts = values.rsplitn(12, ',').collect::<Vec<_>>();
+                    parts.reverse();
+                    let file_name = parts[0].to_string();
+                    let v = parse_values(&arg, Some(parts[1..].join(",")), 11)?;
+                    if !(v[3] > 0.0 && v[4..10].iter().all(|&c| c >= 0.0)) {
+                        return Err(SettingsErr::InvalidValue(arg, values));
+                    }
+                    settings.scene.media.push(SceneMedium::Grid {
+                        file_name: file_name,
+                        position: Vec3::new(v[0], v[1], v[2]),
+                        scale: v[3],
+                        absorption: color(&v[4..7]),
+                        scattering: color(&v[7..10]),
+                        anisotropy: anisotropy(&arg, v[10])?,
+                    });
+                }
+                "--medium-sphere" => {
+                    let values = value(&arg, args.next())?;
+                    let v = parse_values(&arg, Some(values.clone()), 8)?;
+                    let valid =
+                        v[3] > 0.0 && v[4] >= 0.0 && v[5..].iter().all(|&c| c >= 0.0 && c <= 1.0);
+                    if !valid {
+                        return Err(SettingsErr::InvalidValue(arg, values));
+                    }
+                    settings.scene.media.push(SceneMedium::Sphere {
+                        center: Vec3::new(v[0], v[1], v[2]),
+                        radius: v[3],
+                        density: v[4],
+                        albedo: color(&v[5..]),
+                    });
+                }
+                "--normal-map" => {
+                    settings.scene.shading = Some(SceneShading::NormalMap {
+                        file_name: value(&arg, args.next())?,
+                    })
+                }
+                "--output" => settings.outputs.push(value(&arg, args.next())?),
+                "--point-light" => {
+                    let v = parse_values(&arg, args.next(), 6)?;
+                    settings.scene.lights.push(SceneLight::Point {
+                        position: Vec3::new(v[0], v[1], v[2]),
+                        intensity: color(&v[3..]),
+                    });
+                }
+                "--reject-outliers" => {
+                    settings.outlier_threshold = Some(parse_value(&arg, args.next())?)
+                }
+                "--principled" => {
+                    let v = value(&arg, args.next())?;
+                    settings.scene.principled = Some(
+                        principled(&v).ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), v))?,
+                    );
+                }
+                "--primaries" => {
+                    let name = value(&arg, args.next())?;
+                    settings.display.primaries = OutputPrimaries::by_name(&name)
+                        .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?;
+                }
+                "--resume" => settings.resume = true,
+                "--samples" => settings.samples = parse_value(&arg, args.next())?,
+                "--sky" => sky = value(&arg, args.next())?,
+                "--spectral" => settings.scene.spectral = true,
+                "--spot-light" => {
+                    let values = value(&arg, args.next())?;
+                    let v = parse_values(&arg, Some(values.clone()), 11)?;
+                    // angles from the axis in degrees, the outer one widest
+                    let (inner, outer) = (v[9], v[10]);
+                    if !(inner >= 0.0 && inner <= outer && outer <= 180.0) {
+                        return Err(SettingsErr::InvalidValue(arg, values));
+                    }
+                    settings.scene.lights.push(SceneLight::Spot {
+                        position: Vec3::new(v[0], v[1], v[2]),
+                        direction: Vec3::new(v[3], v[4], v[5]),
+                        intensity: color(&v[6..9]),
+                        inner_angle: v[9],
+                        outer_angle: v[10],
+                    });
+                }
+                "--subsurface" => {
+                    // a measured material or R,G,B,MFP_R,MFP_G,MFP_B[,IOR]
+                    let v = value(&arg, args.next())?;
+                    let subsurface = match Subsurface::by_name(&v) {
+                        Some((albedo, mean_free_path)) => SceneSubsurface {
+                            albedo: albedo,
+                            mean_free_path: mean_free_path,
+                            ior: 1.3,
+                        },
+                        None => {
+                            let c = match v.split(',').count() {
+                                6 => parse_values(&arg, Some(v.clone()), 6)?,
+                                _ => parse_values(&arg, Some(v.clone()), 7)?,
+                            };
+                            let albedo_valid = c[..3].iter().all(|a| *a >= 0.0 && *a <= 1.0);
+                            let path_valid = c[3..6].iter().all(|l| *l > 0.0);
+                            let ior = c.get(6).cloned().unwrap_or(1.3);
+                            if !(albedo_valid && path_valid && ior > 0.0) {
+                                return Err(SettingsErr::InvalidValue(arg, v));
+                            }
+                            SceneSubsurface {
+                                albedo: color(&c[..3]),
+                                mean_free_path: color(&c[3..6]),
+                                ior: ior,
+                            }
+                        }
+                    };
+                    settings.scene.subsurface = Some(subsurface);
+                }
+                "--sun-azimuth" => sun_azimuth = parse_value(&arg, args.next())?,
+                "--sun-elevation" => {
+                    sun_elevation = parse_value(&arg, args.next())?;
+                    if !(sun_elevation > 0.0 && sun_elevation <= 90.0) {
+                        return Err(SettingsErr::InvalidValue(arg, sun_elevation.to_string()));
+                    }
+                }
+                "--thin-film" => {
+                    // a thickness in nanometers or a checkerboard of two
+                    let v = value(&arg, args.next())?;
+                    let t = match v.split(',').count() {
+                        2 => parse_values(&arg, Some(v.clone()), 2)?,
+                        _ => parse_values(&arg, Some(v.clone()), 4)?,
+                    };
+                    let (thickness, ior) = match t[..] {
+                        [thickness, ior] if thickness >= 0.0 => {
+                            (SceneTexture::Constant(thickness), ior)
+                        }
+                        [even, odd, size, ior] if even >= 0.0 && odd >= 0.0 && size > 0.0 => (
+                            SceneTexture::Checker {
+                                even: even,
+                                odd: odd,
+                                size: size,
+                            },
+                            ior,
+                        ),
+                        _ => return Err(SettingsErr::InvalidValue(arg, v)),
+                    };
+                    if !(ior > 0.0) {
+                        return Err(SettingsErr::InvalidValue(arg, v));
+                    }
+                    settings.scene.thin_film = Some(SceneThinFilm {
+                        thickness: thickness,
+                        ior: ior,
+                    });
+                }
+                "--turbidity" => {
+                    turbidity = parse_value(&arg, args.next())?;
+                    if !(turbidity >= 1.7 && turbidity <= 10.0) {
+                        return Err(SettingsErr::InvalidValue(arg, turbidity.to_string()));
+                    }
+                }
+                "--seed" => settings.seed = Some(parse_value(&arg, args.next())?),
+                "--threads" => {
+                    settings.threads = parse_value(&arg, args.next())?;
+                    if settings.threads == 0 {
+                        return Err(SettingsErr::InvalidValue(arg, "0".to_string()));
+                    }
+                }
+                "--worker-timeout" => {
+                    settings.worker_timeout = Duration::from_secs(parse_value(&arg, args.next())?)
+                }
+                "--tone-map" => {
+                    let name = value(&arg, args.next())?;
+                    settings.display.tone_map = tone_map_by_name(&name)
+                        .ok_or_else(|| SettingsErr::InvalidValue(arg.clone(), name))?;
+                }
+                option if option.starts_with("--") => {
+                    return Err(SettingsErr::UnknownOption(arg.clone()))
+                }
+                _ => match settings.command {
+                    Command::Render | Command::Coordinator => settings.outputs.push(arg),
+                    Command::Merge => settings.inputs.push(arg),
+                    Command::Worker => settings.address = arg,
+                },
+            }
+        }
+        settings.scene.sky = match sky.as_str() {
+            "gradient" => Sky::Gradient,
+            "preetham" => Sky::Preetham {
+                sun_elevation: sun_elevation,
+                sun_azimuth: sun_azimuth,
+                turbidity: turbidity,
+                ground_albedo: ground_albedo,
+            },
+            "map" => Sky::Map {
+                file_name: environment_map
+                    .ok_or_else(|| SettingsErr::MissingValue("--environment-map".to_string()))?,
+                rotation: environment_rotation,
+                intensity: environment_intensity,
+            },
+            _ => return Err(SettingsErr::InvalidValue("--sky".to_string(), sky)),
+        };
+        settings.scene.conductor = match (conductor_ior, conductor_roughness) {
+            (Some((eta, k)), roughness) => {
+                let (roughness_u, roughness_v) = roughness.unwrap_or((0.0, 0.0));
+                Some(SceneConductor {
+                    eta: eta,
+                    k: k,
+                    roughness_u: roughness_u,
+                    roughness_v: roughness_v,
+                })
+            }
+            (None, Some(_)) => return Err(SettingsErr::MissingValue("--conductor".to_string())),
+            (None, None) => None,
+        };
+        match settings.clamp.as_mut() {
+            Some(clamp) => clamp.indirect_only = indirect_only,
+            None if indirect_only => {
+                return Err(SettingsErr::MissingValue("--clamp-max".to_string()))
+            }
+            None => (),
+        }
+        if settings.command == Command::Merge && settings.inputs.is_empty() {
+            return Err(SettingsErr::MissingValue("merge".to_string()));
+        }
+        if settings.resume && settings.checkpoint.is_none() {
+            return Err(SettingsErr::MissingValue("--checkpoint".to_string()));
+        }
+        if settings.outputs.is_empty() {
+            settings.outputs = DEFAULT_OUTPUTS.iter().map(|o| o.to_string()).collect();
+        }
+        Ok(settings)
+    }
+}
+
+/// Parses `n` comma separated numbers.
+fn parse_values(option: &str, v: Option<String>, n: usize) -> Result<Vec<Dimension>, SettingsErr> {
+    let v = value(option, v)?;
+    let values: Result<Vec<Dimension>, _> = v.split(',').map(|x| x.trim().parse()).collect();
+    match values {
+        Ok(ref values) if values.len() == n => Ok(values.clone()),
+        _ => Err(SettingsErr::InvalidValue(option.to_string(), v)),
+    }
+}
+
+fn color(values: &[Dimension]) -> ColorSample {
+    ColorSample {
+        red: values[0],
+        green: values[1],
+        blue: values[2],
+    }
+}
+
+/// Parses comma separated `NAME=VALUE` settings of a principled material,
+/// such as `base=0.8:0.1:0.1,roughness=0.2`. Scalars are a number in [0, 1]
+/// or a checkerboard `EVEN:ODD:SIZE`; the base color is `R:G:B` or
+/// `R:G:B:R:G:B:SIZE`.
+fn principled(v: &str) -> Option<ScenePrincipled> {
+    let mut principled = ScenePrincipled::new(SceneTexture::Constant(ColorSample {
+        red: 0.8,
+        green: 0.8,
+        blue: 0.8,
+    }));
+    for setting in v.split(',') {
+        let mut parts = setting.splitn(2, '=');
+        let name = parts.next()?.trim();
+        let values: Vec<Dimension> = parts
+            .next()?
+            .split(':')
+            .map(|x| x.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        if name == "ior" {
+            match values[..] {
+                [ior] if ior > 0.0 => principled.ior = ior,
+                _ => return None,
+            }
+            continue;
+        }
+        if values.iter().any(|x| !(*x >= 0.0)) {
+            return None;
+        }
+        if name == "base" {
+            principled.base_color = match values.len() {
+                3 => SceneTexture::Constant(color(&values)),
+                7 if values[6] > 0.0 => SceneTexture::Checker {
+                    even: color(&values[..3]),
+                    odd: color(&values[3..6]),
+                    size: values[6],
+                },
+                _ => return None,
+            };
+            continue;
+        }
+        let texture = match values[..] {
+            [value] if value <= 1.0 => SceneTexture::Constant(value),
+            [even, odd, size] if even <= 1.0 && odd <= 1.0 && size > 0.0 => SceneTexture::Checker {
+                even: even,
+                odd: odd,
+                size: size,
+            },
+            _ => return None,
+        };
+        *match name {
+            "metallic" => &mut principled.metallic,
+            "roughness" => &mut principled.roughness,
+            "specular" => &mut principled.specular,
+            "specular-tint" => &mut principled.specular_tint,
+            "sheen" => &mut principled.sheen,
+            "sheen-tint" => &mut principled.sheen_tint,
+            "clearcoat" => &mut principled.clearcoat,
+            "clearcoat-gloss" => &mut principled.clearcoat_gloss,
+            "transmission" => &mut principled.transmission,
+            "anisotropic" => &mut principled.anisotropic,
+            _ => return None,
+        } = texture;
+    }
+    Some(principled)
+}
+
+/// Checks a Henyey-Greenstein asymmetry, which must lie strictly between -1
+/// and 1.
+fn anisotropy(option: &str, g: Dimension) -> Result<Dimension, SettingsErr> {
+    if g > -1.0 && g < 1.0 {
+        Ok(g)
+    } else {
+        Err(SettingsErr::InvalidValue(option.to_string(), g.to_string()))
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, SettingsErr> {
+    value.ok_or_else(|| SettingsErr::MissingValue(option.to_string()))
+}
+
+fn parse_value<T: ::std::str::FromStr>(option: &str, v: Option<String>) -> Result<T, SettingsErr> {
+    let v = value(option, v)?;
+    v.parse()
+        .map_err(|_| SettingsErr::InvalidValue(option.to_string(), v))
+}
@@ -0,0 +1,208 @@
+use color::sample::*;
+use geometry::frame::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use rand::{thread_rng, Rng};
+use surface::material::*;
+use surface::microfacet::*;
+use surface::thin_film::*;
+
+/// Complex refractive indices, real part n and extinction k, at wavelengths
+/// standing in for the red, green and blue channels (about 650, 550 and
+/// 450 nm).
+const PRESETS: [(&str, [SamplePrecision; 3], [SamplePrecision; 3]); 9] = [
+    ("aluminium", [1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+    ("chromium", [3.176, 3.101, 2.209], [3.330, 3.329, 3.031]),
+    ("copper", [0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+    ("gold", [0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+    ("iron", [2.912, 2.950, 2.585], [3.089, 2.932, 2.767]),
+    ("nickel", [1.990, 1.700, 1.570], [3.740, 3.020, 2.560]),
+    ("platinum", [2.375, 2.085, 1.845], [4.265, 3.715, 3.137]),
+    ("silver", [0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+    ("titanium", [2.745, 2.541, 2.267], [3.814, 3.435, 3.039]),
+];
+
+/// Rough metal: GGX microfacets reflecting with the Fresnel factor of a
+/// complex refractive index. Anisotropic roughness is oriented with the
+/// distribution's u axis along the surface's u tangent.
+pub struct Conductor {
+    pub eta: ColorSample,
+    pub k: ColorSample,
+    pub distribution: Ggx,
+    /// Film on the metal, replacing the Fresnel factor.
+    pub thin_film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    /// Looks up a metal's refractive index by name.
+    pub fn ior_by_name(name: &str) -> Option<(ColorSample, ColorSample)> {
+        let color = |c: [SamplePrecision; 3]| ColorSample {
+            red: c[0],
+            green: c[1],
+            blue: c[2],
+        };
+        PRESETS
+            .iter()
+            .find(|preset| preset.0 == name)
+            .map(|&(_, eta, k)| (color(eta), color(k)))
+    }
+
+    fn frame(normal: &Vec3, tangent: &Vec3) -> Frame {
+        Frame::from_w_and_tangent(*normal, *tangent)
+    }
+
+    /// Refractive index at `lambda`, interpolated between the wavelengths
+    /// the channels stand for.
+    fn ior_at(&self, lambda: Dimension) -> (SamplePrecision, SamplePrecision) {
+        let channel = |c: ColorSample| {
+            if lambda >= 550.0 {
+                let t = ((lambda - 550.0) / 100.0).min(1.0);
+                c.green * (1.0 - t) + c.red * t
+            } else {
+                let t = ((550.0 - lambda) / 100.0).min(1.0);
+                c.green * (1.0 - t) + c.blue * t
+            }
+        };
+        (channel(self.eta), channel(self.k))
+    }
+
+    fn fresnel(
+        &self,
+        point: &Vec3,
+        cosine: Dimension,
+        wavelength: Option<Dimension>,
+    ) -> ColorSample {
+        match self.thin_film {
+            Some(ref film) => {
+                film.reflectance(point, cosine, 1.0, wavelength, |lambda| self.ior_at(lambda))
+            }
+            None => ColorSample {
+                red: fresnel_conductor(cosine, self.eta.red, self.k.red),
+                green: fresnel_conductor(cosine, self.eta.green, self.k.green),
+                blue: fresnel_conductor(cosine, self.eta.blue, self.k.blue),
+            },
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        let frame = Conductor::frame(hit_normal, hit_tangent);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let mut rng = thread_rng();
+        let h = self.distribution.sample_visible_normal(
+            &wo,
+            rng.gen_range::<Dimension>(0.0, 1.0),
+            rng.gen_range::<Dimension>(0.0, 1.0),
+        );
+        let wi = reflect(-wo, h);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        // sampling visible normals leaves only the shadowing of the
+        // reflected direction in the weight
+        let shadowing = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+        Some(HitResult {
+            attenuation: self.fresnel(hit_point, wo.dot(h), ray.wavelength) * shadowing,
+            scattered: Ray {
+                origin: *hit_point,
+                direction: frame.to_world(wi),
+                wavelength: ray.wavelength,
+            },
+            specular: self.distribution.is_nearly_smooth(),
+        })
+    }
+
+    fn eval(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        if self.distribution.is_nearly_smooth() {
+            return None;
+        }
+        let frame = Conductor::frame(hit_normal, hit_tangent);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(*direction);
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Some(ColorSample::BLACK);
+        }
+        let h = (wo + wi).unit();
+        let specular = self.distribution.d(&h) * self.distribution.g2(&wo, &wi) / (4.0 * wo.z);
+        Some(self.fresnel(hit_point, wi.dot(h), ray.wavelength) * specular)
+    }
+
+    fn albedo(&self, hit_point: &Vec3) -> ColorSample {
+        self.fresnel(hit_point, 1.0, None)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        self.thin_film.is_some()
+    }
+}
+
+/// Unpolarized reflectance of a conductor with complex refractive index
+/// `eta + ik` for light arriving at `cosine` to the normal.
+pub fn fresnel_conductor(
+    cosine: Dimension,
+    eta: SamplePrecision,
+    k: SamplePrecision,
+) -> SamplePrecision {
+    let cos2 = cosine.max(0.0).min(1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cosine * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rs + rp)
+}
+
+#[cfg(test)]
+mod test_conductor {
+    use super::*;
+    use surface::material::test_util::*;
+
+    #[test]
+    fn gold_reflects_red_more_than_blue() {
+        let (eta, k) = Conductor::ior_by_name("gold").unwrap();
+        let red = fresnel_conductor(1.0, eta.red, k.red);
+        let blue = fresnel_conductor(1.0, eta.blue, k.blue);
+        assert!(red > 0.9 && blue < 0.5);
+        // every metal turns into a mirror at grazing angles
+        assert!(fresnel_conductor(1e-6, eta.blue, k.blue) > 0.99);
+    }
+
+    #[test]
+    fn sampling_agrees_with_eval() {
+        let (eta, k) = Conductor::ior_by_name("silver").unwrap();
+        let conductor = Conductor {
+            eta: eta,
+            k: k,
+            distribution: Ggx::from_roughness(0.6, 0.4),
+            thin_film: None,
+        };
+        let ray = Ray {
+            origin: Vec3::new(-1.0, 0.3, 1.0),
+            direction: Vec3::new(1.0, -0.3, -1.0),
+            wavelength: None,
+        };
+        assert_sampling_matches_eval(&conductor, &ray, 0.02);
+    }
+}
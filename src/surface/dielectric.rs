@@ -1,51 +1,114 @@
 use color::sample::*;
+use color::spectrum::*;
 use geometry::ray::*;
 use geometry::vec3::*;
 use rand::{thread_rng, Rng};
+use surface::dispersion::*;
 use surface::material::*;
+use surface::thin_film::*;
 
+/// Smooth glass. Light inside is absorbed with the distance travelled.
 pub struct Dielectric {
     pub ref_idx: Dimension,
+    /// Absorption per unit distance, black for clear glass.
+    pub absorption: ColorSample,
+    /// Replaces `ref_idx` with an index depending on the wavelength. Paths
+    /// reaching dispersive glass without a wavelength get one here.
+    pub dispersion: Option<Dispersion>,
+    /// Film on the outside, replacing the Fresnel factor.
+    pub thin_film: Option<ThinFilm>,
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit_point: &Vec3, hit_normal: &Vec3) -> Option<HitResult> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        let (ref_idx, wavelength, weight) = match (self.dispersion, ray.wavelength) {
+            (None, wavelength) => (self.ref_idx, wavelength, ColorSample::WHITE),
+            (Some(dispersion), Some(lambda)) => {
+                (dispersion.ref_idx(lambda), Some(lambda), ColorSample::WHITE)
+            }
+            (Some(dispersion), None) => {
+                let lambda = sample_wavelength(thread_rng().gen_range(0.0, 1.0));
+                (
+                    dispersion.ref_idx(lambda),
+                    Some(lambda),
+                    wavelength_weight(lambda),
+                )
+            }
+        };
         let perpendicular = ray.direction.dot(*hit_normal);
         let (outward_normal, ni_over_nt, cosine) = if perpendicular > 0.0 {
             (
                 -*hit_normal,
-                self.ref_idx,
-                self.ref_idx * perpendicular / ray.direction.length(),
+                ref_idx,
+                ref_idx * perpendicular / ray.direction.length(),
             )
         } else {
             (
                 *hit_normal,
-                1.0 / self.ref_idx,
+                1.0 / ref_idx,
                 -perpendicular / ray.direction.length(),
             )
         };
         if let Some(refracted) = refract(ray.direction, outward_normal, ni_over_nt) {
-            let scl = schlick(cosine, self.ref_idx);
-            let dir = if thread_rng().gen_range::<Dimension>(0.0, 1.0) < scl {
-                reflect(ray.direction.unit(), *hit_normal)
-            } else {
-                refracted
+            let reflected = reflect(ray.direction.unit(), *hit_normal);
+            let u = thread_rng().gen_range::<Dimension>(0.0, 1.0);
+            let (dir, weight) = match self.thin_film {
+                None if u < schlick(cosine, ref_idx) => (reflected, weight),
+                None => (refracted, weight),
+                Some(ref film) => {
+                    // the film lies outside, between the air and the glass
+                    let incident = perpendicular.abs() / ray.direction.length();
+                    let (outside, substrate) = if perpendicular > 0.0 {
+                        (ref_idx, 1.0)
+                    } else {
+                        (1.0, ref_idx)
+                    };
+                    let r = film.reflectance(hit_point, incident, outside, wavelength, |_| {
+                        (substrate, 0.0)
+                    });
+                    // choose by the mean reflectance, weighting the colors
+                    let p = (r.red + r.green + r.blue) / 3.0;
+                    if u < p {
+                        (reflected, weight * r * p.recip())
+                    } else {
+                        let t = ColorSample::WHITE + r * -1.0;
+                        (refracted, weight * t * (1.0 - p).recip())
+                    }
+                }
             };
             Some(HitResult {
-                attenuation: ColorSample::WHITE,
+                attenuation: weight,
                 scattered: Ray {
                     origin: *hit_point,
                     direction: dir,
+                    wavelength: wavelength,
                 },
+                specular: true,
             })
         } else {
             Some(HitResult {
-                attenuation: ColorSample::WHITE,
+                attenuation: weight,
                 scattered: Ray {
                     origin: *hit_point,
                     direction: reflect(ray.direction.unit(), *hit_normal),
+                    wavelength: wavelength,
                 },
+                specular: true,
             })
         }
     }
+
+    fn interior_absorption(&self) -> Option<ColorSample> {
+        Some(self.absorption)
+    }
+
+    fn is_wavelength_dependent(&self) -> bool {
+        self.dispersion.is_some() || self.thin_film.is_some()
+    }
 }
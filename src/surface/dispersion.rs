@@ -0,0 +1,66 @@
+use geometry::vec3::*;
+
+/// Refractive index varying with wavelength. Wavelengths are in micrometers
+/// in the formulas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dispersion {
+    /// n = a + b / λ²
+    Cauchy { a: Dimension, b: Dimension },
+    /// n² = 1 + Σ b λ² / (λ² - c)
+    Sellmeier {
+        b: [Dimension; 3],
+        c: [Dimension; 3],
+    },
+}
+
+impl Dispersion {
+    /// Looks up the Sellmeier coefficients of a glass or gem by name.
+    pub fn by_name(name: &str) -> Option<Dispersion> {
+        match name {
+            "bk7" => Some(Dispersion::Sellmeier {
+                b: [1.039_612_12, 0.231_792_344, 1.010_469_45],
+                c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+            }),
+            "fused-silica" => Some(Dispersion::Sellmeier {
+                b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                c: [0.004_679_148, 0.013_512_063, 97.934_002_5],
+            }),
+            "diamond" => Some(Dispersion::Sellmeier {
+                b: [0.3306, 4.3356, 0.0],
+                c: [0.030_625, 0.011_236, 0.0],
+            }),
+            _ => None,
+        }
+    }
+
+    /// Refractive index at `wavelength` in nanometers.
+    pub fn ref_idx(&self, wavelength: Dimension) -> Dimension {
+        let lambda2 = (wavelength / 1000.0).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * lambda2 / (lambda2 - c))
+                    .sum::<Dimension>())
+            .sqrt(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_dispersion {
+    use super::*;
+
+    #[test]
+    fn presets_match_the_d_line() {
+        let d_line = 587.56;
+        let index = |name| Dispersion::by_name(name).unwrap().ref_idx(d_line);
+        assert!((index("bk7") - 1.5168).abs() < 1e-4);
+        assert!((index("fused-silica") - 1.4585).abs() < 1e-4);
+        assert!((index("diamond") - 2.417).abs() < 2e-3);
+        // blue bends more than red
+        let bk7 = Dispersion::by_name("bk7").unwrap();
+        assert!(bk7.ref_idx(450.0) > bk7.ref_idx(650.0));
+    }
+}
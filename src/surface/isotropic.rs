@@ -0,0 +1,44 @@
+use color::sample::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use surface::material::*;
+
+/// Phase function scattering equally in all directions, for media.
+pub struct Isotropic {
+    pub albedo: ColorSample,
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        _hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        Some(HitResult {
+            attenuation: self.albedo,
+            scattered: Ray {
+                origin: *hit_point,
+                direction: Vec3::random_in_unit_sphere().unit(),
+                wavelength: ray.wavelength,
+            },
+            specular: false,
+        })
+    }
+
+    fn eval(
+        &self,
+        _ray: &Ray,
+        _hit_point: &Vec3,
+        _hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+        _direction: &Vec3,
+    ) -> Option<ColorSample> {
+        Some(self.albedo * (4.0 * PI_DIMENSION).recip())
+    }
+
+    fn albedo(&self, _hit_point: &Vec3) -> ColorSample {
+        self.albedo
+    }
+}
@@ -8,15 +8,40 @@ pub struct Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray: &Ray, hit_point: &Vec3, hit_normal: &Vec3) -> Option<HitResult> {
-        let target = *hit_point + *hit_normal + Vec3::random_in_unit_sphere();
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        // cosine distributed, matching `eval`
+        let target = *hit_point + *hit_normal + Vec3::random_in_unit_sphere().unit();
         let scattered = Ray {
             origin: *hit_point,
             direction: target - *hit_point,
+            wavelength: ray.wavelength,
         };
         Some(HitResult {
             attenuation: self.albedo,
             scattered: scattered,
+            specular: false,
         })
     }
+
+    fn eval(
+        &self,
+        _ray: &Ray,
+        _hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        let cosine = hit_normal.dot(*direction).max(0.0);
+        Some(self.albedo * (cosine / PI_DIMENSION))
+    }
+
+    fn albedo(&self, _hit_point: &Vec3) -> ColorSample {
+        self.albedo
+    }
 }
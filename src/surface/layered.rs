@@ -0,0 +1,346 @@
+use color::sample::*;
+use geometry::frame::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+use surface::material::*;
+use surface::microfacet::*;
+use surface::texture::*;
+
+/// Most times a path bounces between the base and the underside of the coat
+/// before it is given up.
+const MAX_BOUNCES: usize = 16;
+
+/// A dielectric coat over any other material, like the lacquer on car paint
+/// or the varnish on wood. Paths bounce between the coat and the base in a
+/// random walk, evaluated stochastically after Guo et al., "Position-Free
+/// Monte Carlo Simulation for Arbitrary Layered BSDFs" (2018). The layer is
+/// thin, so paths enter and leave where they hit it. Light the base can't
+/// be lit by isn't found by light sampling under the coat either, and nor is
+/// anything under a smooth coat.
+pub struct Layered {
+    pub base: Arc<MaterialSS>,
+    pub ior: Dimension,
+    /// Perceptual roughness of the coat, see `Ggx::from_roughness`.
+    pub roughness: Box<ScalarTextureSS>,
+    /// Absorption per unit distance inside the coat, black for a clear coat.
+    pub absorption: ColorSample,
+    pub thickness: Dimension,
+}
+
+impl Layered {
+    /// Transmittance of the coat for a path crossing it in `direction`.
+    fn transmittance(&self, direction: &Vec3) -> ColorSample {
+        let distance = self.thickness / direction.z.abs().max(1e-6);
+        ColorSample {
+            red: (-self.absorption.red * distance).exp(),
+            green: (-self.absorption.green * distance).exp(),
+            blue: (-self.absorption.blue * distance).exp(),
+        }
+    }
+
+    /// The coat seen from above and from below at `hit_point`.
+    fn coats(&self, hit_point: &Vec3) -> (Coat, Coat) {
+        let roughness = self.roughness.value(hit_point);
+        let distribution = Ggx::from_roughness(roughness, roughness);
+        (
+            Coat {
+                eta: self.ior,
+                distribution: distribution,
+            },
+            Coat {
+                eta: self.ior.recip(),
+                distribution: distribution,
+            },
+        )
+    }
+}
+
+impl Material for Layered {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        let frame = Frame::from_w(*hit_normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z <= 0.0 {
+            // only a base that lets light in is ever hit from below
+            return self.base.scatter(ray, hit_point, hit_normal, hit_tangent);
+        }
+        let (above, below) = self.coats(hit_point);
+        let smooth = above.distribution.is_nearly_smooth();
+        let base_ray = |direction: &Vec3| Ray {
+            origin: *hit_point,
+            direction: frame.to_world(*direction),
+            wavelength: ray.wavelength,
+        };
+        let (direction, weight) = above.sample(&wo)?;
+        let mut throughput = ColorSample::WHITE * weight;
+        if direction.z > 0.0 {
+            return Some(HitResult {
+                attenuation: throughput,
+                scattered: base_ray(&direction),
+                specular: smooth,
+            });
+        }
+        // directions of travel from here on
+        let mut down = direction;
+        for _ in 0..MAX_BOUNCES {
+            throughput *= self.transmittance(&down);
+            let result = self
+                .base
+                .scatter(&base_ray(&down), hit_point, hit_normal, hit_tangent)?;
+            let up = frame.to_local(result.scattered.direction).unit();
+            if up.z <= 0.0 {
+                return None;
+            }
+            throughput *= result.attenuation * self.transmittance(&up);
+            // the coat from below, turned upside down
+            let (direction, weight) = below.sample(&up)?;
+            throughput *= weight;
+            if direction.z < 0.0 {
+                return Some(HitResult {
+                    attenuation: throughput,
+                    scattered: base_ray(&-direction),
+                    specular: smooth,
+                });
+            }
+            down = -direction;
+        }
+        None
+    }
+
+    fn eval(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        let frame = Frame::from_w(*hit_normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z <= 0.0 {
+            return self
+                .base
+                .eval(ray, hit_point, hit_normal, hit_tangent, direction);
+        }
+        let (above, below) = self.coats(hit_point);
+        if above.distribution.is_nearly_smooth() {
+            // the highlight of a smooth coat is left to scattered rays
+            return None;
+        }
+        let wi = frame.to_local(*direction);
+        if wi.z <= 0.0 {
+            return Some(ColorSample::BLACK);
+        }
+        let base_ray = |direction: &Vec3| Ray {
+            origin: *hit_point,
+            direction: frame.to_world(*direction),
+            wavelength: ray.wavelength,
+        };
+        let mut value = ColorSample::WHITE * above.eval_reflection(&wo, &wi);
+        // light from wi refracted into the coat, reached from every vertex
+        // of a walk starting from wo. The refraction was sampled from outside
+        // against the cosine inside; leaving the coat instead takes the
+        // cosine outside and, by reciprocity, a factor of one over eta².
+        let (light, light_weight) = match above.refract(&wi) {
+            Some((light, weight)) => (light, weight * wi.z / (light.z.abs() * self.ior * self.ior)),
+            None => return Some(value),
+        };
+        let to_light = frame.to_world(-light);
+        let (mut down, weight) = match above.refract(&wo) {
+            Some(refracted) => refracted,
+            None => return Some(value),
+        };
+        let mut throughput = ColorSample::WHITE * weight;
+        for _ in 0..MAX_BOUNCES {
+            throughput *= self.transmittance(&down);
+            let f = self.base.eval(
+                &base_ray(&down),
+                hit_point,
+                hit_normal,
+                hit_tangent,
+                &to_light,
+            )?;
+            value += throughput * f * self.transmittance(&light) * light_weight;
+            let result =
+                match self
+                    .base
+                    .scatter(&base_ray(&down), hit_point, hit_normal, hit_tangent)
+                {
+                    Some(result) => result,
+                    None => break,
+                };
+            let up = frame.to_local(result.scattered.direction).unit();
+            if up.z <= 0.0 {
+                break;
+            }
+            throughput *= result.attenuation * self.transmittance(&up);
+            match below.reflect(&up) {
+                Some((reflected, weight)) => {
+                    throughput *= weight;
+                    down = -reflected;
+                }
+                None => break,
+            }
+        }
+        Some(value)
+    }
+
+    fn albedo(&self, hit_point: &Vec3) -> ColorSample {
+        self.base.albedo(hit_point)
+    }
+}
+
+/// One side of a rough dielectric interface, in a frame where the side rays
+/// arrive from is above. `eta` is the index below over the index above.
+struct Coat {
+    eta: Dimension,
+    distribution: Ggx,
+}
+
+impl Coat {
+    fn visible_normal(&self, wo: &Vec3) -> Vec3 {
+        let mut rng = thread_rng();
+        self.distribution.sample_visible_normal(
+            wo,
+            rng.gen_range::<Dimension>(0.0, 1.0),
+            rng.gen_range::<Dimension>(0.0, 1.0),
+        )
+    }
+
+    /// Direction `wo` refracts into about the microfacet normal `h`, None
+    /// under total internal reflection.
+    fn refracted(&self, wo: &Vec3, h: &Vec3) -> Option<Vec3> {
+        let cosine = wo.dot(*h);
+        let cos_t_squared = 1.0 - (1.0 - cosine * cosine) / (self.eta * self.eta);
+        if cos_t_squared < 0.0 {
+            return None;
+        }
+        Some(-*wo / self.eta + (cosine / self.eta - cos_t_squared.sqrt()) * *h)
+    }
+
+    /// Reflects or refracts light arriving from `wo` with the chances given
+    /// by the Fresnel factor, returning the direction it leaves in and its
+    /// weight.
+    fn sample(&self, wo: &Vec3) -> Option<(Vec3, Dimension)> {
+        let h = self.visible_normal(wo);
+        let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+        let wi = if thread_rng().gen_range::<Dimension>(0.0, 1.0) < fresnel {
+            reflect(-*wo, h)
+        } else {
+            self.refracted(wo, &h)?
+        };
+        if wi.z == 0.0 || (wi.z > 0.0) != (wi.dot(h) > 0.0) {
+            return None;
+        }
+        Some((wi, self.distribution.g2(wo, &wi) / self.distribution.g1(wo)))
+    }
+
+    /// Reflects light arriving from `wo`, weighted by the part reflected.
+    fn reflect(&self, wo: &Vec3) -> Option<(Vec3, Dimension)> {
+        let h = self.visible_normal(wo);
+        let wi = reflect(-*wo, h);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+        Some((
+            wi,
+            fresnel * self.distribution.g2(wo, &wi) / self.distribution.g1(wo),
+        ))
+    }
+
+    /// Refracts light arriving from `wo`, weighted by the part transmitted.
+    fn refract(&self, wo: &Vec3) -> Option<(Vec3, Dimension)> {
+        let h = self.visible_normal(wo);
+        let wi = self.refracted(wo, &h)?;
+        if wi.z >= 0.0 {
+            return None;
+        }
+        let fresnel = fresnel_dielectric(wo.dot(h), self.eta);
+        Some((
+            wi,
+            (1.0 - fresnel) * self.distribution.g2(wo, &wi) / self.distribution.g1(wo),
+        ))
+    }
+
+    /// Light reflected by the coat towards `wo` per unit arriving from `wi`,
+    /// including the cosine.
+    fn eval_reflection(&self, wo: &Vec3, wi: &Vec3) -> Dimension {
+        let h = (*wo + *wi).unit();
+        fresnel_dielectric(wo.dot(h), self.eta)
+            * self.distribution.d(&h)
+            * self.distribution.g2(wo, wi)
+            / (4.0 * wo.z)
+    }
+}
+
+#[cfg(test)]
+mod test_layered {
+    use super::*;
+    use surface::lambertian::*;
+    use surface::material::test_util::*;
+
+    #[test]
+    fn sampling_agrees_with_eval() {
+        let material = Layered {
+            base: Arc::new(Lambertian {
+                albedo: ColorSample {
+                    red: 0.9,
+                    green: 0.5,
+                    blue: 0.1,
+                },
+            }),
+            ior: 1.5,
+            roughness: Box::new(0.6),
+            absorption: ColorSample {
+                red: 0.0,
+                green: 1.0,
+                blue: 2.0,
+            },
+            thickness: 0.2,
+        };
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::new(0.5, 0.2, -1.0),
+            wavelength: None,
+        };
+        assert_sampling_matches_eval(&material, &ray, 0.02);
+        // the coat reflects some light before any reaches the base
+        let (sampled, _) = reflected_and_transmitted(&material, &ray);
+        assert!(sampled[0].blue > 0.02 && sampled[0].red < 0.9);
+    }
+
+    #[test]
+    fn smooth_coats_are_left_to_scattered_rays() {
+        let material = Layered {
+            base: Arc::new(Lambertian {
+                albedo: ColorSample::WHITE * 0.5,
+            }),
+            ior: 1.5,
+            roughness: Box::new(0.0),
+            absorption: ColorSample::BLACK,
+            thickness: 0.2,
+        };
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let tangent = Vec3::new(1.0, 0.0, 0.0);
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::new(0.5, 0.2, -1.0),
+            wavelength: None,
+        };
+        assert!(material
+            .eval(&ray, &Vec3::ZERO, &normal, &tangent, &normal)
+            .is_none());
+        let result = material
+            .scatter(&ray, &Vec3::ZERO, &normal, &tangent)
+            .unwrap();
+        assert!(result.specular);
+    }
+}
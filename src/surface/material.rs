@@ -1,18 +1,84 @@
 use color::sample::*;
 use geometry::ray::*;
 use geometry::vec3::*;
+use medium::phase::*;
 
 pub struct HitResult {
     pub attenuation: ColorSample,
     pub scattered: Ray,
+    /// Whether the direction comes from a lobe `eval` leaves out, so lights
+    /// met along it weren't sampled.
+    pub specular: bool,
 }
 
 pub trait Material {
-    fn scatter(&self, ray: &Ray, hit_point: &Vec3, hit_normal: &Vec3) -> Option<HitResult>;
+    /// Chooses a direction to continue `ray` in. `hit_tangent` points along
+    /// the surface where its u coordinate grows, for anisotropic materials.
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+    ) -> Option<HitResult>;
+
+    /// Scattered radiance towards `ray` per unit of light arriving from the
+    /// unit vector `direction`, including the cosine at the surface. None for
+    /// materials that only scatter into directions they choose themselves,
+    /// which can't be lit by sampling lights.
+    fn eval(
+        &self,
+        _ray: &Ray,
+        _hit_point: &Vec3,
+        _hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+        _direction: &Vec3,
+    ) -> Option<ColorSample> {
+        None
+    }
+
+    /// Absorption per unit distance inside objects of this material, for
+    /// materials rays can enter. None for opaque materials.
+    fn interior_absorption(&self) -> Option<ColorSample> {
+        None
+    }
+
+    /// Scattering per unit distance and phase function inside objects of
+    /// this material, for translucent materials. Paths take a random walk
+    /// through them.
+    fn interior_scattering(&self) -> Option<(ColorSample, HenyeyGreenstein)> {
+        None
+    }
+
+    /// Whether scattering depends on the exact wavelength, as with
+    /// dispersion or interference, so paths in spectral mode can only keep
+    /// following one.
+    fn is_wavelength_dependent(&self) -> bool {
+        false
+    }
+
+    /// Surface color for the albedo output, white for clear materials.
+    fn albedo(&self, _hit_point: &Vec3) -> ColorSample {
+        ColorSample::WHITE
+    }
 }
 
 pub type MaterialSS = Material + Send + Sync;
 
+/// Absorption per unit distance that lets `transmittance` of the light
+/// through after `distance`.
+pub fn absorption_from_transmittance(
+    transmittance: ColorSample,
+    distance: Dimension,
+) -> ColorSample {
+    let absorption = |t: SamplePrecision| -t.max(1e-6).min(1.0).ln() / distance;
+    ColorSample {
+        red: absorption(transmittance.red),
+        green: absorption(transmittance.green),
+        blue: absorption(transmittance.blue),
+    }
+}
+
 pub fn reflect(v: Vec3, normal: Vec3) -> Vec3 {
     v - 2.0 * v.dot(normal) * normal
 }
@@ -34,3 +100,94 @@ pub fn schlick(cosine: Dimension, ref_idx: Dimension) -> Dimension {
     let r1 = r0 * r0;
     r1 + (1.0 + r1) * (1.0 - cosine).powi(5)
 }
+
+/// Unpolarized reflectance of an interface between dielectrics for light
+/// arriving at `cosine` to the normal, where `eta` is the refractive index on
+/// the far side over the one on the near side.
+pub fn fresnel_dielectric(cosine: Dimension, eta: Dimension) -> Dimension {
+    let cos_i = cosine.max(0.0).min(1.0);
+    let sin_t2 = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin_t2 >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin_t2).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+#[cfg(test)]
+pub mod test_util {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    /// Mean weight of the paths `scatter` sends back to the side `ray`
+    /// arrives from and through to the other side from lobes `eval` covers,
+    /// and the same from integrating `eval` over the sphere, for a surface
+    /// facing +z with u growing along +x.
+    pub fn reflected_and_transmitted(
+        material: &Material,
+        ray: &Ray,
+    ) -> ([ColorSample; 2], [ColorSample; 2]) {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let tangent = Vec3::new(1.0, 0.0, 0.0);
+        let side = |direction: &Vec3| {
+            if direction.z * ray.direction.z < 0.0 {
+                0
+            } else {
+                1
+            }
+        };
+        let mut rng = thread_rng();
+        // directions stratified over a grid in z and phi, which tames the
+        // variance of peaked lobes
+        let (rows, columns) = (400, 500);
+        let n = rows * columns;
+        let mut sampled = [ColorSample::BLACK; 2];
+        let mut integrated = [ColorSample::BLACK; 2];
+        for i in 0..n {
+            match material.scatter(ray, &Vec3::ZERO, &normal, &tangent) {
+                Some(ref result) if !result.specular => {
+                    sampled[side(&result.scattered.direction)] += result.attenuation
+                }
+                _ => (),
+            }
+            let z = ((i / columns) as Dimension + rng.gen_range::<Dimension>(0.0, 1.0))
+                / rows as Dimension
+                * 2.0
+                - 1.0;
+            let phi = ((i % columns) as Dimension + rng.gen_range::<Dimension>(0.0, 1.0))
+                / columns as Dimension
+                * 2.0
+                * PI_DIMENSION;
+            let r = (1.0 - z * z).sqrt();
+            let direction = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+            let f = material
+                .eval(ray, &Vec3::ZERO, &normal, &tangent, &direction)
+                .expect("eval should light the material");
+            integrated[side(&direction)] += f * (4.0 * PI_DIMENSION);
+        }
+        for i in 0..2 {
+            sampled[i] = sampled[i] / n;
+            integrated[i] = integrated[i] / n;
+        }
+        (sampled, integrated)
+    }
+
+    /// Checks that lighting by `eval` adds up to what `scatter` samples from
+    /// the lobes it covers, on both sides of a surface facing +z and in
+    /// every channel.
+    pub fn assert_sampling_matches_eval(material: &Material, ray: &Ray, tolerance: Dimension) {
+        let (sampled, integrated) = reflected_and_transmitted(material, ray);
+        for (a, b) in sampled.iter().zip(integrated.iter()) {
+            assert!(
+                (a.red - b.red).abs() < tolerance
+                    && (a.green - b.green).abs() < tolerance
+                    && (a.blue - b.blue).abs() < tolerance,
+                "sampled {:?}, integrated {:?}",
+                sampled,
+                integrated
+            );
+        }
+    }
+}
@@ -18,7 +18,13 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, hit_point: &Vec3, hit_normal: &Vec3) -> Option<HitResult> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
         let reflected = reflect(ray.direction.unit(), *hit_normal);
         let direction = if self.fuzz == 0.0 {
             reflected
@@ -28,14 +34,20 @@ impl Material for Metal {
         let scattered = Ray {
             origin: *hit_point,
             direction: direction,
+            wavelength: ray.wavelength,
         };
         if scattered.direction.dot(*hit_normal) > 0.0 {
             Some(HitResult {
                 attenuation: self.albedo,
                 scattered: scattered,
+                specular: true,
             })
         } else {
             None
         }
     }
+
+    fn albedo(&self, _hit_point: &Vec3) -> ColorSample {
+        self.albedo
+    }
 }
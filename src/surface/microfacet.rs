@@ -0,0 +1,159 @@
+use geometry::frame::*;
+use geometry::vec3::*;
+
+/// Below this alpha highlights are too narrow for light sampling to find, so
+/// scattered rays are left to find lights instead.
+const LIGHT_SAMPLING_ALPHA: Dimension = 0.05;
+
+/// GGX (Trowbridge-Reitz) distribution of microfacet normals with Smith
+/// shadowing, in a local frame where the surface normal is z. `alpha_x` and
+/// `alpha_y` are the roughnesses along the frame's u and v axes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ggx {
+    pub alpha_x: Dimension,
+    pub alpha_y: Dimension,
+}
+
+impl Ggx {
+    /// Maps perceptual roughnesses in [0, 1] to alphas by squaring them, which
+    /// spreads highlights more evenly over the range. Alphas are kept above a
+    /// small minimum so the distribution stays finite.
+    pub fn from_roughness(roughness_x: Dimension, roughness_y: Dimension) -> Ggx {
+        let alpha = |r: Dimension| (r * r).max(1e-4).min(1.0);
+        Ggx {
+            alpha_x: alpha(roughness_x),
+            alpha_y: alpha(roughness_y),
+        }
+    }
+
+    /// Whether the surface is so smooth that lights are better found by
+    /// scattered rays than by sampling them.
+    pub fn is_nearly_smooth(&self) -> bool {
+        (self.alpha_x * self.alpha_y).sqrt() < LIGHT_SAMPLING_ALPHA
+    }
+
+    /// Density of microfacet normal `h` per steradian, projected onto the
+    /// surface.
+    pub fn d(&self, h: &Vec3) -> Dimension {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = (h.x / self.alpha_x, h.y / self.alpha_y);
+        let e = x * x + y * y + h.z * h.z;
+        (PI_DIMENSION * self.alpha_x * self.alpha_y * e * e).recip()
+    }
+
+    /// Smith's auxiliary function for direction `w`.
+    fn lambda(&self, w: &Vec3) -> Dimension {
+        let (x, y) = (w.x * self.alpha_x, w.y * self.alpha_y);
+        let tan_squared = (x * x + y * y) / (w.z * w.z);
+        ((1.0 + tan_squared).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of the microfacets facing `w` that are visible from it.
+    pub fn g1(&self, w: &Vec3) -> Dimension {
+        (1.0 + self.lambda(w)).recip()
+    }
+
+    /// Height correlated fraction of microfacets visible from both `wo` and
+    /// `wi`.
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> Dimension {
+        (1.0 + self.lambda(wo) + self.lambda(wi)).recip()
+    }
+
+    /// Samples a microfacet normal visible from `wo`, which must lie above
+    /// the surface, given two uniform numbers in [0, 1). The normals have the
+    /// density `g1(wo) * max(0, wo·h) * d(h) / wo.z` (Heitz, "Sampling the
+    /// GGX Distribution of Visible Normals", 2018).
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: Dimension, u2: Dimension) -> Vec3 {
+        // stretch to the hemisphere configuration
+        let v = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit();
+        // u must lie in the surface plane, so v points away from the horizon
+        let length = (v.x * v.x + v.y * v.y).sqrt();
+        let u = if length > 0.0 {
+            Vec3::new(-v.y / length, v.x / length, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let frame = Frame {
+            u: u,
+            v: v.cross(u),
+            w: v,
+        };
+        // sample the projected area of the hemisphere
+        let r = u1.sqrt();
+        let phi = 2.0 * PI_DIMENSION * u2;
+        let t1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.z);
+        let t2 = (1.0 - s) * (1.0 - t1 * t1).max(0.0).sqrt() + s * r * phi.sin();
+        let t3 = (1.0 - t1 * t1 - t2 * t2).max(0.0).sqrt();
+        let h = frame.to_world(Vec3::new(t1, t2, t3));
+        // unstretch
+        Vec3::new(self.alpha_x * h.x, self.alpha_y * h.y, h.z.max(1e-6)).unit()
+    }
+}
+
+#[cfg(test)]
+mod test_microfacet {
+    use super::*;
+    use rand::{thread_rng, Rng};
+
+    fn directions(n: usize) -> Vec<Vec3> {
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| {
+                let z = rng.gen_range::<Dimension>(0.0, 1.0);
+                let phi = rng.gen_range::<Dimension>(0.0, 2.0 * PI_DIMENSION);
+                let r = (1.0 - z * z).sqrt();
+                Vec3::new(r * phi.cos(), r * phi.sin(), z)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn projected_normals_cover_the_surface_once() {
+        let ggx = Ggx {
+            alpha_x: 0.3,
+            alpha_y: 0.6,
+        };
+        let n = 200_000;
+        let total = directions(n)
+            .iter()
+            .map(|h| ggx.d(h) * h.z * 2.0 * PI_DIMENSION)
+            .sum::<Dimension>()
+            / n as Dimension;
+        assert!((total - 1.0).abs() < 0.02);
+    }
+
+    #[test]
+    fn visible_normals_follow_their_density() {
+        let ggx = Ggx {
+            alpha_x: 0.5,
+            alpha_y: 0.2,
+        };
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let mut rng = thread_rng();
+        // mean of h.z under the sampled density against uniform integration
+        let n = 200_000;
+        let sampled = (0..n)
+            .map(|_| {
+                ggx.sample_visible_normal(
+                    &wo,
+                    rng.gen_range::<Dimension>(0.0, 1.0),
+                    rng.gen_range::<Dimension>(0.0, 1.0),
+                )
+                .z
+            })
+            .sum::<Dimension>()
+            / n as Dimension;
+        let integrated = directions(n)
+            .iter()
+            .map(|h| {
+                let density = ggx.g1(&wo) * wo.dot(*h).max(0.0) * ggx.d(h) / wo.z;
+                h.z * density * 2.0 * PI_DIMENSION
+            })
+            .sum::<Dimension>()
+            / n as Dimension;
+        assert!((sampled - integrated).abs() < 0.02);
+    }
+}
@@ -1,4 +1,15 @@
+pub mod conductor;
 pub mod dielectric;
+pub mod dispersion;
+pub mod isotropic;
 pub mod lambertian;
+pub mod layered;
 pub mod material;
 pub mod metal;
+pub mod microfacet;
+pub mod principled;
+pub mod rough_dielectric;
+pub mod shading_normal;
+pub mod subsurface;
+pub mod texture;
+pub mod thin_film;
@@ -0,0 +1,534 @@
+use color::sample::*;
+use geometry::frame::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use rand::{thread_rng, Rng};
+use surface::material::*;
+use surface::microfacet::*;
+use surface::texture::*;
+
+/// Refractive index of the clearcoat layer, as in Burley's model.
+const CLEARCOAT_IOR: Dimension = 1.5;
+
+/// One material covering most surfaces artists need: the principled BSDF of
+/// Burley, "Physically Based Shading at Disney" (2012), extended with rough
+/// glass for transmission (2015). A diffuse base with sheen sits under a GGX
+/// specular layer that takes on the base color as it becomes metallic, with
+/// an optional clearcoat on top. Parameters are in [0, 1] and mean what they
+/// mean in common principled shaders, so assets carry over. Anisotropic
+/// highlights are stretched along the same u axis as `Conductor`'s.
+pub struct Principled {
+    pub base_color: Box<ColorTextureSS>,
+    pub metallic: Box<ScalarTextureSS>,
+    /// Perceptual roughness of the specular and glass lobes, see
+    /// `Ggx::from_roughness`.
+    pub roughness: Box<ScalarTextureSS>,
+    /// Reflectance of dielectric surfaces, 0.5 being 4% at normal incidence.
+    pub specular: Box<ScalarTextureSS>,
+    /// Tints dielectric reflections towards the base color.
+    pub specular_tint: Box<ScalarTextureSS>,
+    /// Extra reflection at grazing angles, for cloth.
+    pub sheen: Box<ScalarTextureSS>,
+    pub sheen_tint: Box<ScalarTextureSS>,
+    /// Strength of a clear white coat of fixed index over everything.
+    pub clearcoat: Box<ScalarTextureSS>,
+    /// Smoothness of the clearcoat.
+    pub clearcoat_gloss: Box<ScalarTextureSS>,
+    /// Fraction of the non-metallic surface that is glass instead of diffuse.
+    pub transmission: Box<ScalarTextureSS>,
+    /// Stretches highlights along the u axis.
+    pub anisotropic: Box<ScalarTextureSS>,
+    /// Refractive index of the glass.
+    pub ior: Dimension,
+}
+
+impl Principled {
+    fn frame(normal: &Vec3, tangent: &Vec3) -> Frame {
+        Frame::from_w_and_tangent(*normal, *tangent)
+    }
+
+    /// The lobes at `point`, seen from `wo` in the local frame of the
+    /// outward normal.
+    fn lobes(&self, point: &Vec3, wo: &Vec3) -> Lobes {
+        let value = |texture: &ScalarTextureSS| texture.value(point).max(0.0).min(1.0);
+        let base = self.base_color.color(point);
+        let metallic = value(&*self.metallic);
+        let roughness = value(&*self.roughness);
+        let transmission = value(&*self.transmission);
+        let luminance = base.luminance();
+        let tint = if luminance > 0.0 {
+            base * luminance.recip()
+        } else {
+            ColorSample::WHITE
+        };
+        let mix = |a: ColorSample, b: ColorSample, t: Dimension| a * (1.0 - t) + b * t;
+        let dielectric_specular = mix(ColorSample::WHITE, tint, value(&*self.specular_tint))
+            * (0.08 * value(&*self.specular));
+        let aspect = (1.0 - 0.9 * value(&*self.anisotropic)).sqrt();
+        let distribution =
+            Ggx::from_roughness(roughness / aspect.sqrt(), roughness * aspect.sqrt());
+        let clearcoat_alpha = 0.1 - 0.099 * value(&*self.clearcoat_gloss);
+        let smooth_coat = Ggx {
+            alpha_x: clearcoat_alpha,
+            alpha_y: clearcoat_alpha,
+        }
+        .is_nearly_smooth();
+        let lobes = Lobes {
+            base: base,
+            roughness: roughness,
+            sheen: mix(ColorSample::WHITE, tint, value(&*self.sheen_tint)) * value(&*self.sheen),
+            specular: mix(dielectric_specular, base, metallic),
+            clearcoat: 0.25 * value(&*self.clearcoat),
+            clearcoat_alpha: clearcoat_alpha,
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            glass_weight: (1.0 - metallic) * transmission,
+            smooth: [
+                false,
+                distribution.is_nearly_smooth(),
+                smooth_coat,
+                distribution.is_nearly_smooth(),
+            ],
+            distribution: distribution,
+            ior: self.ior,
+            probabilities: [0.0; 4],
+        };
+        lobes.with_probabilities(wo)
+    }
+}
+
+/// Lobe parameters at one point, in the local frame of the outward normal.
+struct Lobes {
+    base: ColorSample,
+    roughness: Dimension,
+    /// Sheen color times strength.
+    sheen: ColorSample,
+    /// Specular reflectance at normal incidence.
+    specular: ColorSample,
+    clearcoat: Dimension,
+    clearcoat_alpha: Dimension,
+    diffuse_weight: Dimension,
+    glass_weight: Dimension,
+    distribution: Ggx,
+    ior: Dimension,
+    /// Chances of sampling the diffuse, specular, clearcoat and glass lobes.
+    probabilities: [Dimension; 4],
+    /// Which lobes are too narrow for light sampling to find, leaving them
+    /// to scattered rays.
+    smooth: [bool; 4],
+}
+
+const DIFFUSE: usize = 0;
+const SPECULAR: usize = 1;
+const CLEARCOAT: usize = 2;
+const GLASS: usize = 3;
+
+impl Lobes {
+    /// Picks lobes roughly in proportion to the light they reflect towards
+    /// `wo`. From inside only the glass is seen.
+    fn with_probabilities(mut self, wo: &Vec3) -> Lobes {
+        let mut weights = if wo.z > 0.0 {
+            let specular = schlick_color(self.specular, wo.z).luminance().max(0.05);
+            [
+                self.diffuse_weight * (self.base.luminance() + self.sheen.luminance()),
+                (1.0 - self.glass_weight) * specular,
+                self.clearcoat,
+                self.glass_weight,
+            ]
+        } else {
+            [0.0, 0.0, 0.0, self.glass_weight]
+        };
+        let total = weights.iter().sum::<Dimension>();
+        if total > 0.0 {
+            for weight in weights.iter_mut() {
+                *weight /= total;
+            }
+        }
+        self.probabilities = weights;
+        self
+    }
+
+    /// Whether every lobe that can be sampled is too narrow for light
+    /// sampling to find.
+    fn is_nearly_smooth(&self) -> bool {
+        (0..4).all(|i| self.smooth[i] || self.probabilities[i] == 0.0)
+    }
+
+    /// The lobes that are nearly smooth, or the others.
+    fn part(&self, smooth: bool) -> [bool; 4] {
+        let mut lobes = self.smooth;
+        for lobe in lobes.iter_mut() {
+            *lobe = *lobe == smooth;
+        }
+        lobes
+    }
+
+    /// Relative refractive index across the surface and `wo` and `wi`
+    /// turned so `wo` is above it.
+    fn oriented(&self, wo: &Vec3, wi: &Vec3) -> (Dimension, Vec3, Vec3) {
+        if wo.z > 0.0 {
+            (self.ior, *wo, *wi)
+        } else {
+            (self.ior.recip(), -*wo, -*wi)
+        }
+    }
+
+    /// Scattered radiance of the chosen `lobes` towards `wo` per unit of
+    /// light arriving from `wi`, including the cosine.
+    fn eval(&self, wo: &Vec3, wi: &Vec3, lobes: [bool; 4]) -> ColorSample {
+        let outside = wo.z > 0.0;
+        let (eta, wo, wi) = self.oriented(wo, wi);
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return ColorSample::BLACK;
+        }
+        let mut value = ColorSample::BLACK;
+        if wi.z < 0.0 {
+            if !lobes[GLASS] {
+                return value;
+            }
+            if let Some((h, jacobian)) = refraction_half_vector(&wo, &wi, eta) {
+                let o_h = wo.dot(h);
+                let transmitted = (1.0 - fresnel_dielectric(o_h, eta))
+                    * self.distribution.d(&h)
+                    * self.distribution.g2(&wo, &wi)
+                    * o_h
+                    * jacobian
+                    / wo.z;
+                value += self.base * (self.glass_weight * transmitted);
+            }
+            return value;
+        }
+        let h = (wo + wi).unit();
+        let (o_h, i_h) = (wo.dot(h), wi.dot(h));
+        if o_h <= 0.0 {
+            return value;
+        }
+        let microfacets = self.distribution.d(&h) * self.distribution.g2(&wo, &wi) / (4.0 * wo.z);
+        if lobes[GLASS] {
+            value += ColorSample::WHITE
+                * (self.glass_weight * fresnel_dielectric(o_h, eta) * microfacets);
+        }
+        if !outside {
+            return value;
+        }
+        if lobes[DIFFUSE] && self.diffuse_weight > 0.0 {
+            let fd90 = 0.5 + 2.0 * self.roughness * i_h * i_h;
+            let retro = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z))
+                * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z));
+            let diffuse = self.base * (retro / PI_DIMENSION) + self.sheen * schlick_weight(i_h);
+            value += diffuse * (self.diffuse_weight * wi.z);
+        }
+        if lobes[SPECULAR] {
+            value += schlick_color(self.specular, i_h) * ((1.0 - self.glass_weight) * microfacets);
+        }
+        if lobes[CLEARCOAT] && self.clearcoat > 0.0 {
+            let coat = Ggx {
+                alpha_x: 0.25,
+                alpha_y: 0.25,
+            };
+            let f0 = ((CLEARCOAT_IOR - 1.0) / (CLEARCOAT_IOR + 1.0)).powi(2);
+            let fresnel = f0 + (1.0 - f0) * schlick_weight(i_h);
+            value += ColorSample::WHITE
+                * (self.clearcoat * fresnel * gtr1(h.z, self.clearcoat_alpha) * coat.g2(&wo, &wi)
+                    / (4.0 * wo.z));
+        }
+        value
+    }
+
+    /// Probability density per steradian of `sample` choosing `wi` from one
+    /// of the chosen `lobes`.
+    fn pdf(&self, wo: &Vec3, wi: &Vec3, lobes: [bool; 4]) -> Dimension {
+        let mut p = self.probabilities;
+        for (p, &chosen) in p.iter_mut().zip(lobes.iter()) {
+            if !chosen {
+                *p = 0.0;
+            }
+        }
+        let (eta, wo, wi) = self.oriented(wo, wi);
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        let visible = self.distribution.g1(&wo) / wo.z;
+        if wi.z < 0.0 {
+            return match refraction_half_vector(&wo, &wi, eta) {
+                Some((h, jacobian)) => {
+                    let o_h = wo.dot(h);
+                    p[GLASS]
+                        * (1.0 - fresnel_dielectric(o_h, eta))
+                        * visible
+                        * o_h
+                        * self.distribution.d(&h)
+                        * jacobian
+                }
+                None => 0.0,
+            };
+        }
+        let h = (wo + wi).unit();
+        let o_h = wo.dot(h);
+        if o_h <= 0.0 {
+            return 0.0;
+        }
+        // reflecting about h halves and stretches the density of normals
+        let reflected = visible * self.distribution.d(&h) / 4.0;
+        p[DIFFUSE] * wi.z / PI_DIMENSION
+            + p[SPECULAR] * reflected
+            + p[CLEARCOAT] * gtr1(h.z, self.clearcoat_alpha) * h.z / (4.0 * o_h)
+            + p[GLASS] * fresnel_dielectric(o_h, eta) * reflected
+    }
+
+    /// Chooses a lobe and a direction from it, None when the direction falls
+    /// on the wrong side of the surface.
+    fn sample(&self, wo: &Vec3) -> Option<(Vec3, usize)> {
+        let mut rng = thread_rng();
+        let (u1, u2) = (
+            rng.gen_range::<Dimension>(0.0, 1.0),
+            rng.gen_range::<Dimension>(0.0, 1.0),
+        );
+        let outside = wo.z > 0.0;
+        let (eta, wo, _) = self.oriented(wo, wo);
+        let mut choice = rng.gen_range::<Dimension>(0.0, 1.0);
+        let mut lobe = GLASS;
+        for (i, p) in self.probabilities.iter().enumerate() {
+            if choice < *p {
+                lobe = i;
+                break;
+            }
+            choice -= p;
+        }
+        let wi = match lobe {
+            DIFFUSE => {
+                let r = u1.sqrt();
+                let phi = 2.0 * PI_DIMENSION * u2;
+                Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt())
+            }
+            SPECULAR => reflect(-wo, self.distribution.sample_visible_normal(&wo, u1, u2)),
+            CLEARCOAT => {
+                let a2 = self.clearcoat_alpha * self.clearcoat_alpha;
+                let cos_h = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+                let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+                let phi = 2.0 * PI_DIMENSION * u2;
+                let h = Vec3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h);
+                reflect(-wo, h)
+            }
+            _ => {
+                let h = self.distribution.sample_visible_normal(&wo, u1, u2);
+                let cosine = wo.dot(h);
+                if rng.gen_range::<Dimension>(0.0, 1.0) < fresnel_dielectric(cosine, eta) {
+                    reflect(-wo, h)
+                } else {
+                    let cos_t = (1.0 - (1.0 - cosine * cosine) / (eta * eta)).sqrt();
+                    let wi = -wo / eta + (cosine / eta - cos_t) * h;
+                    if wi.z >= 0.0 {
+                        return None;
+                    }
+                    wi
+                }
+            }
+        };
+        if wi.z == 0.0 || (wi.z < 0.0 && lobe != GLASS) {
+            return None;
+        }
+        // back to the frame of the outward normal
+        Some((if outside { wi } else { -wi }, lobe))
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        let frame = Principled::frame(hit_normal, hit_tangent);
+        let wo = frame.to_local(-ray.direction.unit());
+        let lobes = self.lobes(hit_point, &wo);
+        let (wi, lobe) = lobes.sample(&wo)?;
+        // weighed against the lobes of the same kind only, as lights are
+        // sampled for the others
+        let part = lobes.part(lobes.smooth[lobe]);
+        let pdf = lobes.pdf(&wo, &wi, part);
+        if !(pdf > 0.0) {
+            return None;
+        }
+        Some(HitResult {
+            attenuation: lobes.eval(&wo, &wi, part) * pdf.recip(),
+            scattered: Ray {
+                origin: *hit_point,
+                direction: frame.to_world(wi),
+                wavelength: ray.wavelength,
+            },
+            specular: lobes.smooth[lobe],
+        })
+    }
+
+    fn eval(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        let frame = Principled::frame(hit_normal, hit_tangent);
+        let wo = frame.to_local(-ray.direction.unit());
+        let lobes = self.lobes(hit_point, &wo);
+        if lobes.is_nearly_smooth() {
+            return None;
+        }
+        Some(lobes.eval(&wo, &frame.to_local(*direction), lobes.part(false)))
+    }
+
+    fn albedo(&self, hit_point: &Vec3) -> ColorSample {
+        self.base_color.color(hit_point)
+    }
+}
+
+/// Microfacet normal refracting `wo` into `wi` across a relative index
+/// `eta`, with the Jacobian from normals to refracted directions. None when
+/// no microfacet facing `wo` does.
+fn refraction_half_vector(wo: &Vec3, wi: &Vec3, eta: Dimension) -> Option<(Vec3, Dimension)> {
+    let mut h = -(*wo + eta * *wi).unit();
+    if h.z < 0.0 {
+        h = -h;
+    }
+    let (o_h, i_h) = (wo.dot(h), wi.dot(h));
+    if o_h <= 0.0 || i_h >= 0.0 {
+        return None;
+    }
+    let denominator = o_h + eta * i_h;
+    Some((h, eta * eta * -i_h / (denominator * denominator)))
+}
+
+/// Generalized Trowbridge-Reitz distribution with exponent one, whose long
+/// tail gives the clearcoat its haze.
+fn gtr1(cos_h: Dimension, alpha: Dimension) -> Dimension {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI_DIMENSION * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+}
+
+fn schlick_weight(cosine: Dimension) -> Dimension {
+    (1.0 - cosine).max(0.0).min(1.0).powi(5)
+}
+
+fn schlick_color(f0: ColorSample, cosine: Dimension) -> ColorSample {
+    f0 + (ColorSample::WHITE + f0 * -1.0) * schlick_weight(cosine)
+}
+
+#[cfg(test)]
+mod test_principled {
+    use super::*;
+    use surface::material::test_util::*;
+
+    /// Smooth-ish plastic of `base_color`, with the defaults of common
+    /// principled shaders for everything else.
+    fn plastic(base_color: ColorSample) -> Principled {
+        Principled {
+            base_color: Box::new(base_color),
+            metallic: Box::new(0.0),
+            roughness: Box::new(0.5),
+            specular: Box::new(0.5),
+            specular_tint: Box::new(0.0),
+            sheen: Box::new(0.0),
+            sheen_tint: Box::new(0.5),
+            clearcoat: Box::new(0.0),
+            clearcoat_gloss: Box::new(1.0),
+            transmission: Box::new(0.0),
+            anisotropic: Box::new(0.0),
+            ior: 1.45,
+        }
+    }
+
+    #[test]
+    fn sampling_agrees_with_eval() {
+        let material = Principled {
+            metallic: Box::new(0.3),
+            roughness: Box::new(0.7),
+            sheen: Box::new(1.0),
+            clearcoat: Box::new(1.0),
+            clearcoat_gloss: Box::new(0.0),
+            transmission: Box::new(0.5),
+            anisotropic: Box::new(0.5),
+            ..plastic(ColorSample {
+                red: 0.8,
+                green: 0.4,
+                blue: 0.2,
+            })
+        };
+        // from outside and from inside
+        for &z in [-1.0, 1.0].iter() {
+            let ray = Ray {
+                origin: Vec3::ZERO,
+                direction: Vec3::new(0.5, 0.2, z),
+                wavelength: None,
+            };
+            assert_sampling_matches_eval(&material, &ray, 0.03);
+        }
+    }
+
+    #[test]
+    fn smooth_lobes_are_left_to_scattered_rays() {
+        // a glossy coat over rough plastic
+        let material = Principled {
+            roughness: Box::new(0.7),
+            clearcoat: Box::new(1.0),
+            clearcoat_gloss: Box::new(1.0),
+            ..plastic(ColorSample::WHITE * 0.5)
+        };
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let tangent = Vec3::new(1.0, 0.0, 0.0);
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::new(0.5, 0.2, -1.0),
+            wavelength: None,
+        };
+        let specular = (0..10_000)
+            .filter_map(|_| material.scatter(&ray, &Vec3::ZERO, &normal, &tangent))
+            .filter(|result| result.specular)
+            .count();
+        assert!(specular > 0 && specular < 10_000);
+        assert_sampling_matches_eval(&material, &ray, 0.03);
+    }
+
+    #[test]
+    fn anisotropy_follows_the_tangent() {
+        let material = Principled {
+            roughness: Box::new(0.3),
+            anisotropic: Box::new(1.0),
+            ..plastic(ColorSample::WHITE * 0.5)
+        };
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let (x, y) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: -normal,
+            wavelength: None,
+        };
+        let eval = |tangent: &Vec3, direction: Vec3| {
+            material
+                .eval(&ray, &Vec3::ZERO, &normal, tangent, &direction.unit())
+                .unwrap()
+                .green
+        };
+        let (along_x, along_y) = (Vec3::new(0.2, 0.0, 1.0), Vec3::new(0.0, 0.2, 1.0));
+        // turning the tangent turns the highlight with it
+        assert!((eval(&x, along_x) - eval(&y, along_y)).abs() < 1e-9);
+        assert!(eval(&x, along_x) > 1.5 * eval(&x, along_y));
+    }
+
+    #[test]
+    fn metals_reflect_their_base_color() {
+        let gold = ColorSample {
+            red: 1.0,
+            green: 0.8,
+            blue: 0.3,
+        };
+        let material = Principled {
+            metallic: Box::new(1.0),
+            ..plastic(gold)
+        };
+        let lobes = material.lobes(&Vec3::ZERO, &Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(gold, lobes.specular);
+        assert_eq!(0.0, lobes.probabilities[DIFFUSE]);
+    }
+}
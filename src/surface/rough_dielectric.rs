@@ -0,0 +1,153 @@
+use color::sample::*;
+use geometry::frame::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use rand::{thread_rng, Rng};
+use surface::material::*;
+use surface::microfacet::*;
+use surface::texture::*;
+
+/// Frosted glass: an interface between dielectrics made of GGX microfacets
+/// that each reflect or refract (Walter et al., "Microfacet Models for
+/// Refraction through Rough Surfaces", 2007).
+pub struct RoughDielectric {
+    pub ref_idx: Dimension,
+    /// Perceptual roughness in [0, 1], see `Ggx::from_roughness`.
+    pub roughness: Box<ScalarTextureSS>,
+    /// Absorption per unit distance inside, black for clear glass.
+    pub absorption: ColorSample,
+}
+
+impl RoughDielectric {
+    /// Frame whose w axis is the normal on the side rays arrive from along
+    /// `view`, the relative refractive index across the surface and the
+    /// microfacet distribution at `hit_point`.
+    fn local(&self, view: &Vec3, hit_point: &Vec3, hit_normal: &Vec3) -> (Frame, Dimension, Ggx) {
+        let (w, eta) = if view.dot(*hit_normal) >= 0.0 {
+            (*hit_normal, self.ref_idx)
+        } else {
+            (-*hit_normal, self.ref_idx.recip())
+        };
+        let roughness = self.roughness.value(hit_point);
+        (
+            Frame::from_w(w),
+            eta,
+            Ggx::from_roughness(roughness, roughness),
+        )
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        let view = -ray.direction.unit();
+        let (frame, eta, distribution) = self.local(&view, hit_point, hit_normal);
+        let wo = frame.to_local(view);
+        if wo.z <= 0.0 {
+            return None;
+        }
+        let mut rng = thread_rng();
+        let h = distribution.sample_visible_normal(
+            &wo,
+            rng.gen_range::<Dimension>(0.0, 1.0),
+            rng.gen_range::<Dimension>(0.0, 1.0),
+        );
+        let cosine = wo.dot(h);
+        let wi = if rng.gen_range::<Dimension>(0.0, 1.0) < fresnel_dielectric(cosine, eta) {
+            let wi = reflect(-wo, h);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            // total internal reflection always takes the branch above
+            let cos_t = (1.0 - (1.0 - cosine * cosine) / (eta * eta)).sqrt();
+            let wi = -wo / eta + (cosine / eta - cos_t) * h;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            wi
+        };
+        // the Fresnel factor is spent choosing between the two
+        Some(HitResult {
+            attenuation: ColorSample::WHITE * (distribution.g2(&wo, &wi) / distribution.g1(&wo)),
+            scattered: Ray {
+                origin: *hit_point,
+                direction: frame.to_world(wi),
+                wavelength: ray.wavelength,
+            },
+            specular: distribution.is_nearly_smooth(),
+        })
+    }
+
+    fn eval(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        let view = -ray.direction.unit();
+        let (frame, eta, distribution) = self.local(&view, hit_point, hit_normal);
+        if distribution.is_nearly_smooth() {
+            return None;
+        }
+        let wo = frame.to_local(view);
+        let wi = frame.to_local(*direction);
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return Some(ColorSample::BLACK);
+        }
+        let g2 = distribution.g2(&wo, &wi);
+        let value = if wi.z > 0.0 {
+            let h = (wo + wi).unit();
+            fresnel_dielectric(wo.dot(h), eta) * distribution.d(&h) * g2 / (4.0 * wo.z)
+        } else {
+            let mut h = -(wo + eta * wi).unit();
+            if h.z < 0.0 {
+                h = -h;
+            }
+            let (o_h, i_h) = (wo.dot(h), wi.dot(h));
+            if o_h <= 0.0 || i_h >= 0.0 {
+                return Some(ColorSample::BLACK);
+            }
+            let denominator = o_h + eta * i_h;
+            (1.0 - fresnel_dielectric(o_h, eta)) * distribution.d(&h) * g2 * o_h * eta * eta * -i_h
+                / (wo.z * denominator * denominator)
+        };
+        Some(ColorSample::WHITE * value)
+    }
+
+    fn interior_absorption(&self) -> Option<ColorSample> {
+        Some(self.absorption)
+    }
+}
+
+#[cfg(test)]
+mod test_rough_dielectric {
+    use super::*;
+    use surface::material::test_util::*;
+
+    #[test]
+    fn sampling_agrees_with_eval() {
+        let glass = RoughDielectric {
+            ref_idx: 1.5,
+            roughness: Box::new(0.8),
+            absorption: ColorSample::BLACK,
+        };
+        // from outside and from inside the glass
+        for &z in [-1.0, 1.0].iter() {
+            let ray = Ray {
+                origin: Vec3::ZERO,
+                direction: Vec3::new(0.5, 0.2, z),
+                wavelength: None,
+            };
+            assert_sampling_matches_eval(&glass, &ray, 0.02);
+        }
+    }
+}
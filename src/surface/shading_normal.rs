@@ -0,0 +1,205 @@
+use color::buffer::*;
+use geometry::frame::*;
+use geometry::vec3::*;
+use hit_detection::hitable::*;
+use surface::texture::*;
+
+/// Distance between the points bump maps compare heights at.
+const BUMP_STEP: Dimension = 1e-4;
+
+/// Detail added to a surface by changing the normal it is shaded with,
+/// without changing its geometry.
+pub trait ShadingNormal {
+    /// Unit shading normal at `hit`, whose `normal` is the geometric one.
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3;
+}
+
+pub type ShadingNormalSS = ShadingNormal + Send + Sync;
+
+/// Frame of the tangent space at `hit`, with u along `dpdu`, v on the side of
+/// `dpdv` and w along the geometric normal. Normals facing inwards, as on
+/// spheres with a negative radius, make it left handed.
+fn tangent_frame(hit: &HitRecord) -> Frame {
+    let frame = Frame::from_w_and_tangent(hit.normal, hit.dpdu);
+    if frame.v.dot(hit.dpdv) < 0.0 {
+        Frame {
+            v: -frame.v,
+            ..frame
+        }
+    } else {
+        frame
+    }
+}
+
+/// Tangent space normal map: an image whose colors are the shading normals,
+/// red along u, green along v and blue along the geometric normal, each
+/// mapped from [-1, 1] to [0, 1]. The top row is at v = 1 and the image
+/// repeats.
+pub struct NormalMap {
+    normals: Vec<Vec3>,
+    width: usize,
+    height: usize,
+}
+
+impl NormalMap {
+    pub fn new(image: &ColorBuffer) -> NormalMap {
+        let (width, height) = (image.imgx, image.imgy);
+        let mut normals = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = image.pixel(x, y);
+                normals.push(Vec3::new(
+                    2.0 * color.red - 1.0,
+                    2.0 * color.green - 1.0,
+                    2.0 * color.blue - 1.0,
+                ));
+            }
+        }
+        NormalMap {
+            normals: normals,
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Bilinearly interpolated normal at surface coordinates `u` and `v`.
+    fn lookup(&self, u: Dimension, v: Dimension) -> Vec3 {
+        let x = u * self.width as Dimension - 0.5;
+        let y = (1.0 - v) * self.height as Dimension - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let texel = |dx: i64, dy: i64| {
+            let i = (x0 as i64 + dx).rem_euclid(self.width as i64) as usize;
+            let j = (y0 as i64 + dy).rem_euclid(self.height as i64) as usize;
+            self.normals[j * self.width + i]
+        };
+        (1.0 - fy) * ((1.0 - fx) * texel(0, 0) + fx * texel(1, 0))
+            + fy * ((1.0 - fx) * texel(0, 1) + fx * texel(1, 1))
+    }
+}
+
+impl ShadingNormal for NormalMap {
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        let local = self.lookup(hit.u, hit.v);
+        if local.z <= 0.0 {
+            return hit.normal;
+        }
+        tangent_frame(hit).to_world(local).unit()
+    }
+}
+
+/// Bump map: the surface shaded as if moved along its normal by `height`
+/// times `scale`. Heights are looked up by position like other textures, so
+/// the slopes come from comparing them a small step along the tangents.
+pub struct BumpMap {
+    pub height: Box<ScalarTextureSS>,
+    pub scale: Dimension,
+}
+
+impl ShadingNormal for BumpMap {
+    fn shading_normal(&self, hit: &HitRecord) -> Vec3 {
+        let frame = tangent_frame(hit);
+        let height = |point: Vec3| self.scale * self.height.value(&point);
+        let here = height(hit.p);
+        let slope_u = (height(hit.p + BUMP_STEP * frame.u) - here) / BUMP_STEP;
+        let slope_v = (height(hit.p + BUMP_STEP * frame.v) - here) / BUMP_STEP;
+        (frame.w - slope_u * frame.u - slope_v * frame.v).unit()
+    }
+}
+
+#[cfg(test)]
+mod test_shading_normal {
+    use super::*;
+    use color::sample::*;
+
+    fn hit() -> HitRecord {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        HitRecord {
+            t: 1.0,
+            p: Vec3::ZERO,
+            normal: normal,
+            shading_normal: normal,
+            u: 0.25,
+            v: 0.5,
+            dpdu: Vec3::new(2.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, -3.0),
+        }
+    }
+
+    #[test]
+    fn normal_maps_turn_normals_in_tangent_space() {
+        let mut image = ColorBuffer::new(2, 1);
+        // flat on the left, leaning along u on the right
+        image.add_color(
+            0,
+            0,
+            ColorSample {
+                red: 0.5,
+                green: 0.5,
+                blue: 1.0,
+            },
+        );
+        image.add_color(
+            1,
+            0,
+            ColorSample {
+                red: 1.0,
+                green: 0.5,
+                blue: 1.0,
+            },
+        );
+        let map = NormalMap::new(&image);
+        let flat = map.shading_normal(&hit());
+        assert!((flat - hit().normal).length() < 1e-9);
+        let leaning = map.shading_normal(&HitRecord { u: 0.75, ..hit() });
+        let expected = Vec3::new(1.0, 1.0, 0.0).unit();
+        assert!((leaning - expected).length() < 1e-9);
+    }
+
+    #[test]
+    fn normal_maps_follow_v_on_inward_normals() {
+        let mut image = ColorBuffer::new(1, 1);
+        // leaning along v
+        image.add_color(
+            0,
+            0,
+            ColorSample {
+                red: 0.5,
+                green: 1.0,
+                blue: 1.0,
+            },
+        );
+        let map = NormalMap::new(&image);
+        let leaning = map.shading_normal(&hit());
+        assert!((leaning - Vec3::new(0.0, 1.0, -1.0).unit()).length() < 1e-9);
+        let inward = Vec3::new(0.0, -1.0, 0.0);
+        let leaning = map.shading_normal(&HitRecord {
+            normal: inward,
+            shading_normal: inward,
+            ..hit()
+        });
+        assert!((leaning - Vec3::new(0.0, -1.0, -1.0).unit()).length() < 1e-9);
+    }
+
+    #[test]
+    fn bumps_lean_normals_away_from_rising_height() {
+        // height rising along x, the direction of u
+        struct Ramp;
+        impl ScalarTexture for Ramp {
+            fn value(&self, point: &Vec3) -> Dimension {
+                point.x
+            }
+        }
+        let flat = BumpMap {
+            height: Box::new(0.3),
+            scale: 1.0,
+        };
+        assert!((flat.shading_normal(&hit()) - hit().normal).length() < 1e-9);
+        let ramp = BumpMap {
+            height: Box::new(Ramp),
+            scale: 1.0,
+        };
+        let expected = Vec3::new(-1.0, 1.0, 0.0).unit();
+        assert!((ramp.shading_normal(&hit()) - expected).length() < 1e-6);
+    }
+}
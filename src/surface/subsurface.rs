@@ -0,0 +1,179 @@
+use color::sample::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use medium::phase::*;
+use rand::{thread_rng, Rng};
+use surface::material::*;
+
+/// Translucent material like skin, marble or milk. Light entering objects of
+/// it scatters through the medium inside in a random walk, taken by the
+/// integrator, until it is absorbed or leaves again. Objects must be closed.
+/// The smooth boundary reflects by the Fresnel factor, and light crossing it
+/// leaves diffusely on the other side, so paths can be lit by sampling
+/// lights where they leave.
+pub struct Subsurface {
+    /// Fraction of the light scattered rather than absorbed at each
+    /// interaction inside.
+    pub albedo: ColorSample,
+    /// Mean distance between interactions inside, per channel.
+    pub mean_free_path: ColorSample,
+    pub ior: Dimension,
+}
+
+impl Subsurface {
+    /// Albedo and mean free path in centimeters of measured materials, from
+    /// Jensen et al., "A Practical Model for Subsurface Light Transport"
+    /// (2001). They were measured with an index of refraction of 1.3.
+    pub fn by_name(name: &str) -> Option<(ColorSample, ColorSample)> {
+        // reduced scattering and absorption per millimeter
+        let (scattering, absorption) = match name {
+            "apple" => ([2.29, 2.39, 1.97], [0.0030, 0.0034, 0.046]),
+            "chicken" => ([0.15, 0.21, 0.38], [0.015, 0.077, 0.19]),
+            "cream" => ([7.38, 5.47, 3.15], [0.0002, 0.0028, 0.0163]),
+            "ketchup" => ([0.18, 0.07, 0.03], [0.061, 0.97, 1.45]),
+            "marble" => ([2.19, 2.62, 3.00], [0.0021, 0.0041, 0.0071]),
+            "potato" => ([0.68, 0.70, 0.55], [0.0024, 0.0090, 0.12]),
+            "skimmilk" => ([0.70, 1.22, 1.90], [0.0014, 0.0025, 0.0142]),
+            "skin" => ([0.74, 0.88, 1.01], [0.032, 0.17, 0.48]),
+            "wholemilk" => ([2.55, 3.21, 3.77], [0.0011, 0.0024, 0.014]),
+            _ => return None,
+        };
+        let channel = |i: usize| {
+            let extinction = scattering[i] + absorption[i];
+            (scattering[i] / extinction, 0.1 / extinction)
+        };
+        let (red, green, blue) = (channel(0), channel(1), channel(2));
+        Some((
+            ColorSample {
+                red: red.0,
+                green: green.0,
+                blue: blue.0,
+            },
+            ColorSample {
+                red: red.1,
+                green: green.1,
+                blue: blue.1,
+            },
+        ))
+    }
+
+    /// Part of the light arriving along `direction` that the boundary
+    /// reflects.
+    fn fresnel(&self, direction: &Vec3, hit_normal: &Vec3) -> Dimension {
+        let perpendicular = direction.dot(*hit_normal) / direction.length();
+        if perpendicular > 0.0 {
+            fresnel_dielectric(perpendicular, self.ior.recip())
+        } else {
+            fresnel_dielectric(-perpendicular, self.ior)
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+    ) -> Option<HitResult> {
+        // normal on the side the ray arrives from
+        let facing = if ray.direction.dot(*hit_normal) > 0.0 {
+            -*hit_normal
+        } else {
+            *hit_normal
+        };
+        let reflected = thread_rng().gen_range::<Dimension>(0.0, 1.0)
+            < self.fresnel(&ray.direction, hit_normal);
+        let direction = if reflected {
+            reflect(ray.direction.unit(), facing)
+        } else {
+            // cosine distributed on the far side, matching `eval`
+            Vec3::random_in_unit_sphere().unit() - facing
+        };
+        Some(HitResult {
+            attenuation: ColorSample::WHITE,
+            scattered: Ray {
+                origin: *hit_point,
+                direction: direction,
+                wavelength: ray.wavelength,
+            },
+            specular: reflected,
+        })
+    }
+
+    fn eval(
+        &self,
+        ray: &Ray,
+        _hit_point: &Vec3,
+        hit_normal: &Vec3,
+        _hit_tangent: &Vec3,
+        direction: &Vec3,
+    ) -> Option<ColorSample> {
+        if ray.direction.dot(*hit_normal) < 0.0 {
+            // light outside only reaches paths arriving from outside by the
+            // mirror reflection
+            return None;
+        }
+        let cosine = hit_normal.dot(*direction).max(0.0);
+        let transmitted = 1.0 - self.fresnel(&ray.direction, hit_normal);
+        Some(ColorSample::WHITE * (transmitted * cosine / PI_DIMENSION))
+    }
+
+    fn interior_absorption(&self) -> Option<ColorSample> {
+        Some(ColorSample {
+            red: (1.0 - self.albedo.red) / self.mean_free_path.red,
+            green: (1.0 - self.albedo.green) / self.mean_free_path.green,
+            blue: (1.0 - self.albedo.blue) / self.mean_free_path.blue,
+        })
+    }
+
+    fn interior_scattering(&self) -> Option<(ColorSample, HenyeyGreenstein)> {
+        let scattering = ColorSample {
+            red: self.albedo.red / self.mean_free_path.red,
+            green: self.albedo.green / self.mean_free_path.green,
+            blue: self.albedo.blue / self.mean_free_path.blue,
+        };
+        Some((scattering, HenyeyGreenstein { g: 0.0 }))
+    }
+
+    fn albedo(&self, _hit_point: &Vec3) -> ColorSample {
+        self.albedo
+    }
+}
+
+#[cfg(test)]
+mod test_subsurface {
+    use super::*;
+    use surface::material::test_util::*;
+
+    #[test]
+    fn leaving_light_agrees_with_eval() {
+        let (albedo, mean_free_path) = Subsurface::by_name("skin").unwrap();
+        let material = Subsurface {
+            albedo: albedo,
+            mean_free_path: mean_free_path,
+            ior: 1.3,
+        };
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let tangent = Vec3::new(1.0, 0.0, 0.0);
+        // leaving the object, below the critical angle
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::new(0.3, 0.1, 1.0),
+            wavelength: None,
+        };
+        assert_sampling_matches_eval(&material, &ray, 0.01);
+        let (sampled, _) = reflected_and_transmitted(&material, &ray);
+        assert!(sampled[1].red > 0.9 && sampled[1].red < 1.0);
+        // past the critical angle everything is reflected back inside
+        let grazing = Ray {
+            direction: Vec3::new(1.0, 0.0, 0.2),
+            ..ray
+        };
+        let f = material
+            .eval(&grazing, &Vec3::ZERO, &normal, &tangent, &normal)
+            .unwrap();
+        assert_eq!(0.0, f.red);
+    }
+}
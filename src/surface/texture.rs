@@ -0,0 +1,73 @@
+use color::sample::*;
+use geometry::vec3::*;
+use medium::noise::*;
+
+/// Material parameter varying over surfaces. Surfaces have no texture
+/// coordinates, so values are looked up by position in space.
+pub trait ScalarTexture {
+    fn value(&self, point: &Vec3) -> Dimension;
+}
+
+pub type ScalarTextureSS = ScalarTexture + Send + Sync;
+
+/// Material color varying over surfaces, looked up like `ScalarTexture`.
+pub trait ColorTexture {
+    fn color(&self, point: &Vec3) -> ColorSample;
+}
+
+pub type ColorTextureSS = ColorTexture + Send + Sync;
+
+/// The same value everywhere.
+impl ScalarTexture for Dimension {
+    fn value(&self, _point: &Vec3) -> Dimension {
+        *self
+    }
+}
+
+/// The same color everywhere.
+impl ColorTexture for ColorSample {
+    fn color(&self, _point: &Vec3) -> ColorSample {
+        *self
+    }
+}
+
+/// Solid checkerboard of cubes `size` wide alternating between two values.
+pub struct Checker<T> {
+    pub size: Dimension,
+    pub even: T,
+    pub odd: T,
+}
+
+impl<T: Copy> Checker<T> {
+    fn at(&self, point: &Vec3) -> T {
+        let cell = |x: Dimension| (x / self.size).floor() as i64;
+        if (cell(point.x) + cell(point.y) + cell(point.z)) % 2 == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+impl ScalarTexture for Checker<Dimension> {
+    fn value(&self, point: &Vec3) -> Dimension {
+        self.at(point)
+    }
+}
+
+impl ColorTexture for Checker<ColorSample> {
+    fn color(&self, point: &Vec3) -> ColorSample {
+        self.at(point)
+    }
+}
+
+/// Fractal noise with features about `size` wide, in [0, 1].
+pub struct Noise {
+    pub size: Dimension,
+}
+
+impl ScalarTexture for Noise {
+    fn value(&self, point: &Vec3) -> Dimension {
+        0.5 + 0.5 * fractal_noise(*point / self.size)
+    }
+}
@@ -0,0 +1,211 @@
+use color::sample::*;
+use color::spectrum::*;
+use geometry::vec3::*;
+use std::ops::{Add, Div, Mul, Sub};
+use surface::texture::*;
+
+/// A transparent film a few hundred nanometers thick on a surface, like soap,
+/// oil on water or a lens coating. Light reflected by its two sides
+/// interferes, so its reflectance swings with the wavelength, the film's
+/// thickness and the angle it is seen at.
+pub struct ThinFilm {
+    /// Thickness in nanometers.
+    pub thickness: Box<ScalarTextureSS>,
+    pub ior: Dimension,
+}
+
+impl ThinFilm {
+    /// Reflectance of the film at `point` for light arriving at `cosine` to
+    /// the normal from a medium of index `outside`, over a substrate whose
+    /// complex index at each wavelength is given by `substrate`. At the
+    /// ray's `wavelength` if it has one, otherwise the color of the
+    /// reflectance spectrum.
+    pub fn reflectance<F>(
+        &self,
+        point: &Vec3,
+        cosine: Dimension,
+        outside: Dimension,
+        wavelength: Option<Dimension>,
+        substrate: F,
+    ) -> ColorSample
+    where
+        F: Fn(Dimension) -> (Dimension, Dimension),
+    {
+        let thickness = self.thickness.value(point).max(0.0);
+        let at = |lambda: Dimension| {
+            let (eta, k) = substrate(lambda);
+            airy_reflectance(
+                lambda,
+                thickness,
+                cosine,
+                outside,
+                self.ior,
+                Complex { re: eta, im: k },
+            )
+        };
+        match wavelength {
+            Some(lambda) => ColorSample::WHITE * at(lambda),
+            None => {
+                let color = reflectance_color(at);
+                ColorSample {
+                    red: color.red.max(0.0).min(1.0),
+                    green: color.green.max(0.0).min(1.0),
+                    blue: color.blue.max(0.0).min(1.0),
+                }
+            }
+        }
+    }
+}
+
+/// Reflectance at `lambda` of a film `thickness` nanometers thick with index
+/// `film`, between a medium of index `outside` and a substrate of complex
+/// index `substrate`, for light arriving at `cosine`. Averages the Airy sums
+/// of the multiply reflected amplitudes for both polarizations.
+fn airy_reflectance(
+    lambda: Dimension,
+    thickness: Dimension,
+    cosine: Dimension,
+    outside: Dimension,
+    film: Dimension,
+    substrate: Complex,
+) -> Dimension {
+    let cos0 = Complex::real(cosine.max(0.0).min(1.0));
+    let n0 = Complex::real(outside);
+    let n1 = Complex::real(film);
+    let n2 = substrate;
+    // Snell's law with complex angles, which cover total internal reflection
+    let sin0_squared = Complex::real(outside * outside * (1.0 - cosine * cosine));
+    let cos_in = |n: Complex| (Complex::real(1.0) - sin0_squared / (n * n)).sqrt();
+    let (cos1, cos2) = (cos_in(n1), cos_in(n2));
+    // phase difference of a round trip through the film
+    let phase = n1 * cos1 * Complex::real(4.0 * PI_DIMENSION * thickness / lambda);
+    let delay = Complex::exp_i(phase);
+    let airy = |r01: Complex, r12: Complex| {
+        let r = (r01 + r12 * delay) / (Complex::real(1.0) + r01 * r12 * delay);
+        r.norm_squared()
+    };
+    let s = airy(
+        (n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1),
+        (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2),
+    );
+    let p = airy(
+        (n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1),
+        (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2),
+    );
+    (0.5 * (s + p)).min(1.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Complex {
+    re: Dimension,
+    im: Dimension,
+}
+
+impl Complex {
+    fn real(re: Dimension) -> Complex {
+        Complex { re: re, im: 0.0 }
+    }
+
+    /// e to the power of i times `z`.
+    fn exp_i(z: Complex) -> Complex {
+        let magnitude = (-z.im).exp();
+        Complex {
+            re: magnitude * z.re.cos(),
+            im: magnitude * z.re.sin(),
+        }
+    }
+
+    fn norm_squared(self) -> Dimension {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The principal root, whose real part is non-negative. For the
+    /// cosines above its imaginary part is too, so waves decay into
+    /// absorbing substrates and past total internal reflection.
+    fn sqrt(self) -> Complex {
+        let r = self.norm_squared().sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex {
+            re: (0.5 * (r + self.re)).max(0.0).sqrt(),
+            im: if self.im < 0.0 { -im } else { im },
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let d = other.norm_squared();
+        Complex {
+            re: (self.re * other.re + self.im * other.im) / d,
+            im: (self.im * other.re - self.re * other.im) / d,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_thin_film {
+    use super::*;
+    use surface::material::*;
+
+    #[test]
+    fn vanishing_film_leaves_plain_fresnel() {
+        for &cosine in [1.0, 0.7, 0.2].iter() {
+            let r = airy_reflectance(550.0, 0.0, cosine, 1.0, 1.33, Complex::real(1.5));
+            assert!((r - fresnel_dielectric(cosine, 1.5)).abs() < 1e-9);
+        }
+        // total internal reflection from inside glass
+        let r = airy_reflectance(550.0, 0.0, 0.2, 1.5, 1.33, Complex::real(1.0));
+        assert!((r - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_coating_cancels_reflection() {
+        // the ideal anti-reflection coating has the geometric mean index
+        let film = 1.5f64.sqrt();
+        let thickness = 550.0 / (4.0 * film);
+        let r = airy_reflectance(550.0, thickness, 1.0, 1.0, film, Complex::real(1.5));
+        assert!(r < 1e-9);
+        let coating = ThinFilm {
+            thickness: Box::new(thickness),
+            ior: film,
+        };
+        let color = coating.reflectance(&Vec3::ZERO, 1.0, 1.0, None, |_| (1.5, 0.0));
+        // reflects some red and blue but hardly any green
+        assert!(color.green < 0.01 && color.red > color.green && color.blue > color.green);
+    }
+}
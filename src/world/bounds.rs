@@ -63,6 +63,41 @@ impl Bounds {
         )
     }
 
+    /// Parameter range of the ray between `t_min` and `t_max` inside the box.
+    pub fn clip(
+        &self,
+        ray: &Ray,
+        t_min: Dimension,
+        t_max: Dimension,
+    ) -> Option<(Dimension, Dimension)> {
+        let axes = [
+            (self.min.x, self.max.x, ray.origin.x, ray.direction.x),
+            (self.min.y, self.max.y, ray.origin.y, ray.direction.y),
+            (self.min.z, self.max.z, ray.origin.z, ray.direction.z),
+        ];
+        let (mut t0, mut t1) = (t_min, t_max);
+        for &(box_min, box_max, origin, direction) in axes.iter() {
+            let inv_d = direction.recip();
+            let mut near = (box_min - origin) * inv_d;
+            let mut far = (box_max - origin) * inv_d;
+            if inv_d < 0.0 {
+                swap(&mut near, &mut far)
+            }
+            // NaN from a ray lying in a slab plane keeps the previous limit
+            if near > t0 {
+                t0 = near;
+            }
+            if far < t1 {
+                t1 = far;
+            }
+        }
+        if t1 > t0 {
+            Some((t0, t1))
+        } else {
+            None
+        }
+    }
+
     fn hit_1d(
         box_min: Dimension,
         box_max: Dimension,
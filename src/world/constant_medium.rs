@@ -0,0 +1,101 @@
+use geometry::frame::*;
+use geometry::ray::*;
+use geometry::vec3::*;
+use hit_detection::hitable::*;
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+use surface::material::*;
+use world::bounds::*;
+use world::model::*;
+
+/// Fog of uniform density filling a convex boundary. Rays travelling through
+/// it scatter after an exponentially distributed free flight distance, using
+/// the phase function material.
+pub struct ConstantMedium {
+    pub boundary: Box<HitableSS>,
+    /// Scattering events per unit distance.
+    pub density: Dimension,
+    pub phase_function: Arc<MaterialSS>,
+    pub object_id: usize,
+    pub material_id: usize,
+}
+
+impl Model for ConstantMedium {
+    fn hit_model(&self, ray: &Ray, t_min: Dimension, t_max: Dimension) -> Option<ModelHitRecord> {
+        // where the ray's line enters and leaves the boundary, which may lie
+        // behind the origin when the ray starts inside
+        let enter = self.boundary.hit(ray, -MAX_DIMENSION, MAX_DIMENSION)?;
+        let exit = self.boundary.hit(ray, enter.t + 1e-4, MAX_DIMENSION)?;
+        let t_enter = enter.t.max(t_min);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+        let length = ray.direction.length();
+        let distance_inside = (t_exit - t_enter) * length;
+        let u = thread_rng().gen_range::<Dimension>(0.0, 1.0);
+        let hit_distance = -(1.0 - u).ln() / self.density;
+        if hit_distance >= distance_inside {
+            return None;
+        }
+        let t = t_enter + hit_distance / length;
+        // media have no surface; isotropic scattering ignores it
+        let normal = -ray.direction / length;
+        let frame = Frame::from_w(normal);
+        Some(ModelHitRecord {
+            hit_record: HitRecord {
+                t: t,
+                p: ray.point_at_parameter(t),
+                normal: normal,
+                shading_normal: normal,
+                u: 0.0,
+                v: 0.0,
+                dpdu: frame.u,
+                dpdv: frame.v,
+            },
+            material: self.phase_function.clone(),
+            material_id: self.material_id,
+            object_id: self.object_id,
+        })
+    }
+
+    fn bounds(&self) -> Option<Bounds> {
+        self.boundary.bounds()
+    }
+}
+
+#[cfg(test)]
+mod test_constant_medium {
+    use super::*;
+    use color::sample::*;
+    use hit_detection::sphere::*;
+    use surface::isotropic::*;
+
+    #[test]
+    fn transmittance_follows_beer_lambert() {
+        let medium = ConstantMedium {
+            boundary: Box::new(Sphere {
+                center: Vec3::ZERO,
+                radius: 1.0,
+            }),
+            density: 0.5,
+            phase_function: Arc::new(Isotropic {
+                albedo: ColorSample::WHITE,
+            }),
+            object_id: 1,
+            material_id: 1,
+        };
+        // starts inside, so only one unit of the medium lies ahead
+        let ray = Ray {
+            origin: Vec3::ZERO,
+            direction: Vec3::new(0.0, 0.0, 2.0),
+            wavelength: None,
+        };
+        let n = 20000;
+        let passed = (0..n)
+            .filter(|_| medium.hit_model(&ray, 1e-3, MAX_DIMENSION).is_none())
+            .count();
+        let expected = (-0.5 as Dimension).exp();
+        assert!((passed as Dimension / n as Dimension - expected).abs() < 0.02);
+    }
+}
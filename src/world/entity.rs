@@ -3,20 +3,33 @@ use geometry::vec3::*;
 use hit_detection::hitable::*;
 use std::sync::Arc;
 use surface::material::*;
+use surface::shading_normal::*;
 use world::bounds::*;
 use world::model::*;
 
 pub struct WorldEntity {
     pub shape: Box<HitableSS>,
     pub material: Arc<MaterialSS>,
+    /// Identifies the entity in object id outputs.
+    pub object_id: usize,
+    /// Identifies the material in material id outputs. Entities sharing a
+    /// material share its id.
+    pub material_id: usize,
+    /// Normal or bump map applied to hits before they are shaded.
+    pub shading: Option<Arc<ShadingNormalSS>>,
 }
 
 impl Model for WorldEntity {
     fn hit_model(&self, ray: &Ray, t_min: Dimension, t_max: Dimension) -> Option<ModelHitRecord> {
-        if let Some(hit) = self.shape.hit(ray, t_min, t_max) {
+        if let Some(mut hit) = self.shape.hit(ray, t_min, t_max) {
+            if let Some(ref shading) = self.shading {
+                hit.shading_normal = shading.shading_normal(&hit);
+            }
             Some(ModelHitRecord {
                 hit_record: hit,
                 material: self.material.clone(),
+                material_id: self.material_id,
+                object_id: self.object_id,
             })
         } else {
             None
@@ -1,5 +1,6 @@
 pub mod bounds;
 pub mod bvh;
+pub mod constant_medium;
 pub mod entity;
 pub mod model;
 pub mod model_list;
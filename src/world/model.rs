@@ -8,6 +8,8 @@ use world::bounds::*;
 pub struct ModelHitRecord {
     pub hit_record: HitRecord,
     pub material: Arc<Material>,
+    pub material_id: usize,
+    pub object_id: usize,
 }
 
 pub trait Model {
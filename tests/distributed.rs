@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+const RAY_TRACER: &str = env!("CARGO_BIN_EXE_ray_tracer");
+
+#[test]
+fn worker_processes_render_for_a_coordinator() {
+    let output = env::temp_dir().join(format!("distributed-{}.png", std::process::id()));
+    let mut coordinator = Command::new(RAY_TRACER)
+        .args(["coordinator", "--seed", "3", "--samples", "1"])
+        .args(["--listen", "127.0.0.1:0", "--local-workers", "0"])
+        .arg(&output)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // coordinating scene seed 3 on <address>
+    let mut stdout = BufReader::new(coordinator.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    let address = line.trim().rsplit(' ').next().unwrap().to_string();
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            Command::new(RAY_TRACER)
+                .args(["worker", address.as_str(), "--threads", "1"])
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    assert!(coordinator.wait().unwrap().success());
+    for mut worker in workers {
+        // the coordinator said it was done rather than hanging up
+        assert!(worker.wait().unwrap().success());
+    }
+    assert!(fs::metadata(&output).unwrap().len() > 0);
+    fs::remove_file(&output).unwrap();
+}